@@ -1,6 +1,8 @@
+extern crate chrono;
 #[macro_use]
 extern crate clap;
 extern crate env_logger;
+extern crate glacio;
 extern crate glacio_http;
 extern crate iron;
 extern crate serde_json;
@@ -15,17 +17,183 @@ fn main() {
     let matches = App::from_yaml(yaml).get_matches();
     if let Some(matches) = matches.subcommand_matches("api") {
         let api = Api::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        api.watch();
         let addr = matches.value_of("ADDR").unwrap();
         println!("Serving glacio api on http://{}", addr);
         Iron::new(api).http(addr).unwrap();
     } else if let Some(matches) = matches.subcommand_matches("heartbeats") {
+        use glacio_http::Range;
+        use glacio_http::atlas::csv;
+        use std::io;
+
         let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
-        let heartbeats = config
+        let range = Range::parse(matches.value_of("start"), matches.value_of("end")).unwrap();
+        let mut heartbeats = config
             .atlas
-            .read_sbd()
+            .heartbeats()
             .unwrap()
-            .filter_map(|heartbeat| heartbeat.ok())
+            .into_iter()
+            .filter(|heartbeat| range.contains(&heartbeat.datetime))
             .collect::<Vec<_>>();
-        println!("{}", serde_json::to_string(&heartbeats).unwrap());
+        heartbeats.sort();
+        if matches.is_present("csv") {
+            let stdout = io::stdout();
+            csv::write(&heartbeats, &mut stdout.lock()).unwrap();
+        } else {
+            println!("{}", serde_json::to_string(&heartbeats).unwrap());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("alerts") {
+        use chrono::Utc;
+        use glacio_http::alerts::Report;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let report = Report::new(&config.alerts, &config.atlas, Utc::now()).unwrap();
+        if matches.is_present("notify") {
+            let mut dispatcher = config.alerts.dispatcher().unwrap();
+            dispatcher.dispatch(&report).unwrap();
+        }
+        println!("{}", serde_json::to_string(&report).unwrap());
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        use glacio_http::Range;
+        use glacio_http::atlas::netcdf;
+        use std::fs::File;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let range = Range::parse(matches.value_of("start"), matches.value_of("end")).unwrap();
+        let mut heartbeats = config
+            .atlas
+            .heartbeats()
+            .unwrap()
+            .into_iter()
+            .filter(|heartbeat| range.contains(&heartbeat.datetime))
+            .collect::<Vec<_>>();
+        heartbeats.sort();
+        let mut file = File::create(matches.value_of("OUTPUT").unwrap()).unwrap();
+        netcdf::write(&config.atlas, &heartbeats, &mut file).unwrap();
+    } else if let Some(matches) = matches.subcommand_matches("index") {
+        use glacio::atlas::Index;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let path = config.atlas.index.expect(
+            "no index path in the [atlas] configuration",
+        );
+        let mut index = Index::open(&path, &config.atlas.path, &config.atlas.imei).unwrap();
+        let count = index.update().unwrap();
+        println!(
+            "Added {} heartbeats to {} ({} total)",
+            count,
+            path,
+            index.heartbeats().len()
+        );
+    } else if let Some(matches) = matches.subcommand_matches("watch") {
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        for result in config.atlas.watch().unwrap() {
+            match result {
+                Ok(heartbeat) => println!("{}", serde_json::to_string(&heartbeat).unwrap()),
+                Err(err) => eprintln!("Problem while parsing heartbeat: {}", err),
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("directip") {
+        use glacio::directip::Server;
+        use glacio::directip::mt::Queue;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let addr = matches.value_of("ADDR").unwrap();
+        let mut server = Server::bind(addr, &config.atlas.path).unwrap();
+        let queue_path = config.atlas.mt_queue_path();
+        println!("Receiving DirectIP messages on {}", addr);
+        for result in server.incoming() {
+            match result {
+                Ok(message) => {
+                    println!(
+                        "Received message from {} at {}",
+                        message.imei(),
+                        message.time_of_session()
+                    );
+                    // `glacio command` changes the queue too, so it's locked while we confirm.
+                    let confirmed = Queue::update(&queue_path, &config.atlas.imei, |queue| {
+                        Ok(queue.confirm(&message))
+                    });
+                    match confirmed {
+                        Ok(Some(mtmsn)) => println!("Command with MTMSN {} was delivered", mtmsn),
+                        Ok(None) => {}
+                        Err(err) => eprintln!("Problem while confirming commands: {}", err),
+                    }
+                }
+                Err(err) => eprintln!("Problem while receiving message: {}", err),
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("import") {
+        use glacio::mail::Importer;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let mut importer = Importer::open(&config.atlas.path).unwrap();
+        for mailbox in matches.values_of("MAILBOX").unwrap() {
+            let summary = importer.import(mailbox).unwrap();
+            println!(
+                "{}: imported {} messages ({} duplicates, {} invalid attachments)",
+                mailbox,
+                summary.imported,
+                summary.duplicates,
+                summary.invalid
+            );
+        }
+    } else if let Some(matches) = matches.subcommand_matches("cwms") {
+        use glacio::cwms::Importer;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let mut importer = Importer::new();
+        for station in &config.stations.stations {
+            importer.station(station.cwms_location(), &station.name, &station.path);
+        }
+        for export in matches.values_of("EXPORT").unwrap() {
+            let summary = importer.import(export).unwrap();
+            println!(
+                "{}: imported {} observations ({} already imported, {} missing values, \
+                 {} unmapped timeseries)",
+                export,
+                summary.imported,
+                summary.skipped,
+                summary.missing,
+                summary.unmapped
+            );
+        }
+    } else if let Some(matches) = matches.subcommand_matches("command") {
+        use glacio::directip::mt::{Queue, Status};
+        use glacio::sutron::Command;
+
+        let config = Config::from_path(matches.value_of("CONFIG").unwrap()).unwrap();
+        let command: Command = matches
+            .values_of("COMMAND")
+            .unwrap()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .parse()
+            .unwrap();
+        let queue_path = config.atlas.mt_queue_path();
+        let imei = &config.atlas.imei;
+        // The command is saved as pending before it's sent, so its MTMSN isn't reused even if
+        // sending fails.
+        let mtmsn = Queue::update(&queue_path, imei, |queue| queue.push(command.to_string()))
+            .unwrap();
+        Queue::send_saved(&queue_path, imei, matches.value_of("GATEWAY").unwrap()).unwrap();
+        let queue = Queue::open(&queue_path, imei).unwrap();
+        let entry = queue
+            .entries()
+            .iter()
+            .rev()
+            .find(|entry| entry.message.mtmsn() == mtmsn)
+            .unwrap();
+        match entry.status {
+            Status::Queued(ref confirmation) => {
+                println!(
+                    "Queued \"{}\" with MTMSN {} at position {}",
+                    command,
+                    confirmation.client_message_id,
+                    confirmation.status
+                )
+            }
+            ref status => eprintln!("Command was not queued: {:?}", status),
+        }
     }
 }
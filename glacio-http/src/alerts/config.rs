@@ -0,0 +1,101 @@
+//! Configuration objects for alerts.
+
+use {Result, atlas};
+use alerts::notify::{Dispatcher, Ledger, Smtp, Webhook};
+use glacio::atlas::alert::{Monitor, Rule};
+use std::time::Duration;
+
+/// Alerts configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    /// The rules that are evaluated against the ATLAS heartbeats.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Webhooks that receive alert events.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Email recipients of alert events.
+    #[serde(default)]
+    pub emails: Vec<SmtpConfig>,
+    /// The path to the file that records which events have been sent.
+    ///
+    /// If not provided, the record is only kept in memory.
+    #[serde(default)]
+    pub ledger: Option<String>,
+    /// The number of times to retry a failed notification.
+    #[serde(default)]
+    pub retries: usize,
+    /// The number of seconds to wait between retries.
+    #[serde(default)]
+    pub retry_interval: u64,
+}
+
+/// Webhook configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct WebhookConfig {
+    /// The url that receives the JSON POST.
+    pub url: String,
+}
+
+/// Email configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SmtpConfig {
+    /// The SMTP relay, as `host:port`.
+    pub server: String,
+    /// The sender's address.
+    pub from: String,
+    /// The recipients' addresses.
+    pub to: Vec<String>,
+    /// The hostname used to greet the SMTP server, defaults to "localhost".
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+impl Config {
+    /// Returns a monitor for these rules.
+    ///
+    /// The ATLAS configuration is used to set up the EFOYs, so fuel rules can be evaluated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::{alerts, atlas};
+    /// let config = alerts::Config::default();
+    /// let monitor = config.monitor(&atlas::Config::default()).unwrap();
+    /// ```
+    pub fn monitor(&self, atlas: &atlas::Config) -> Result<Monitor> {
+        Ok(Monitor::new(self.rules.clone()).efoy(atlas.efoy()?))
+    }
+
+    /// Returns a dispatcher for the configured notifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::Config;
+    /// let config = Config::default();
+    /// let dispatcher = config.dispatcher().unwrap();
+    /// ```
+    pub fn dispatcher(&self) -> Result<Dispatcher> {
+        let ledger = match self.ledger {
+            Some(ref path) => Ledger::open(path)?,
+            None => Ledger::in_memory(),
+        };
+        let mut dispatcher = Dispatcher::new(ledger).retry(
+            self.retries,
+            Duration::from_secs(self.retry_interval),
+        );
+        for config in &self.webhooks {
+            dispatcher = dispatcher.notifier(Box::new(Webhook::new(&config.url)));
+        }
+        for config in &self.emails {
+            let to = config.to.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            let mut smtp = Smtp::new(&config.server, &config.from, &to);
+            if let Some(ref hostname) = config.hostname {
+                smtp = smtp.hostname(hostname);
+            }
+            dispatcher = dispatcher.notifier(Box::new(smtp));
+        }
+        Ok(dispatcher)
+    }
+}
@@ -0,0 +1,65 @@
+//! Handle alert requests.
+
+use alerts::{Config, Report};
+use atlas;
+use iron::{IronResult, Request, Response};
+use json;
+
+/// Handler for alert requests.
+///
+/// Alerts are evaluated against the ATLAS heartbeats, so this handler needs the ATLAS
+/// configuration as well as the alerts configuration.
+#[derive(Clone, Debug)]
+pub struct Alerts {
+    config: Config,
+    atlas: atlas::Config,
+}
+
+impl Alerts {
+    /// Creates a new alerts handler.
+    pub fn new(config: Config, atlas: atlas::Config) -> Alerts {
+        Alerts {
+            config: config,
+            atlas: atlas,
+        }
+    }
+
+    /// Returns a report of the firing alerts and the alert history.
+    pub fn report(&self, _: &mut Request) -> IronResult<Response> {
+        use chrono::Utc;
+        json::response(itry!(Report::new(&self.config, &self.atlas, Utc::now())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Api, Config};
+    use glacio::atlas::alert::{Kind, Rule};
+    use iron::Headers;
+    use iron_test::{request, response};
+    use serde_json::{self, Value};
+
+    #[test]
+    fn report() {
+        let mut config = Config::default();
+        config.atlas.path = "../glacio/data".to_string();
+        config.alerts.rules.push(Rule {
+            name: "low battery".to_string(),
+            kind: Kind::StateOfCharge,
+            threshold: 90.,
+            hysteresis: 5.,
+        });
+        let api = Api::new(config).unwrap();
+        let response = request::get("http://localhost:3000/alerts", Headers::new(), &api).unwrap();
+        let report: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(2, report["firing"].as_array().unwrap().len());
+        assert_eq!("low battery", report["firing"][0]["rule"]);
+        assert_eq!("state_of_charge", report["firing"][0]["kind"]);
+        assert_eq!(1, report["firing"][0]["subject"]);
+        assert!((85.461 - report["firing"][0]["value"].as_f64().unwrap()).abs() < 1e-3);
+        assert_eq!(2, report["events"].as_array().unwrap().len());
+        assert_eq!("firing", report["events"][0]["status"]);
+        assert_eq!("2017-08-25T15:01:06Z", report["events"][0]["datetime"]);
+    }
+}
@@ -0,0 +1,10 @@
+//! Alerts raised by rules evaluated against the ATLAS heartbeats.
+
+pub mod config;
+pub mod handlers;
+pub mod notify;
+
+mod report;
+
+pub use self::config::{Config, SmtpConfig, WebhookConfig};
+pub use self::report::Report;
@@ -0,0 +1,619 @@
+//! Send alert events to the people who need to hear about them.
+//!
+//! A `Dispatcher` hands alert events to one or more `Notifier`s, e.g. a webhook or an email
+//! address. Every event that was successfully sent is written down in a ledger file, so that
+//! running the dispatcher again (e.g. from cron, or after a restart) doesn't re-send the same
+//! events.
+//!
+//! Only events that matter *now* are sent: firing events for alerts that are still firing, and
+//! resolved events for alerts whose firing event was sent. This keeps a fresh ledger from
+//! spamming the whole alert history.
+
+use {Error, Result};
+use alerts::Report;
+use glacio::atlas::alert::{Event, Status};
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Something that can deliver alert events.
+pub trait Notifier: Debug {
+    /// Returns a name for this notifier, used to record which events it has sent.
+    fn name(&self) -> String;
+
+    /// Sends an event.
+    fn notify(&self, event: &Event) -> Result<()>;
+}
+
+/// Sends alert events as a JSON POST to a url.
+///
+/// The JSON has a `text` field with a human-readable summary, so the url can be a Slack-compatible
+/// incoming webhook. The full event is included under the `event` key. Requests are made with
+/// libcurl, so https urls use the system's TLS library.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    url: String,
+}
+
+/// Sends alert events as emails via an SMTP relay.
+///
+/// This is a plain SMTP client with no authentication or encryption, so it should talk to a local
+/// relay (e.g. the postfix instance on the server).
+#[derive(Clone, Debug)]
+pub struct Smtp {
+    server: String,
+    hostname: String,
+    from: String,
+    to: Vec<String>,
+}
+
+/// A record of which events have been sent by which notifiers.
+#[derive(Debug)]
+pub struct Ledger {
+    path: Option<PathBuf>,
+    sent: BTreeSet<String>,
+}
+
+/// Dispatches events to notifiers, with retries.
+#[derive(Debug)]
+pub struct Dispatcher {
+    notifiers: Vec<Box<Notifier>>,
+    ledger: Ledger,
+    retries: usize,
+    retry_interval: Duration,
+}
+
+impl Webhook {
+    /// Creates a new webhook for the provided url.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::Webhook;
+    /// let webhook = Webhook::new("http://localhost:8080/hooks/glacio");
+    /// ```
+    pub fn new(url: &str) -> Webhook {
+        Webhook { url: url.to_string() }
+    }
+}
+
+impl Notifier for Webhook {
+    fn name(&self) -> String {
+        format!("webhook:{}", self.url)
+    }
+
+    fn notify(&self, event: &Event) -> Result<()> {
+        use curl::easy::{Easy, List};
+
+        let body = json!({
+            "text": summary(event),
+            "event": event,
+        }).to_string();
+        let mut headers = List::new();
+        headers.append("Content-Type: application/json")?;
+        let mut easy = Easy::new();
+        easy.url(&self.url)?;
+        easy.post(true)?;
+        easy.post_fields_copy(body.as_bytes())?;
+        easy.http_headers(headers)?;
+        easy.timeout(Duration::from_secs(30))?;
+        easy.write_function(|data| Ok(data.len()))?;
+        easy.perform()?;
+        let status = easy.response_code()?;
+        if status >= 200 && status < 300 {
+            Ok(())
+        } else {
+            Err(Error::Notify(
+                format!("webhook {} responded {}", self.url, status),
+            ))
+        }
+    }
+}
+
+impl Smtp {
+    /// Creates a new SMTP notifier that sends from one address to one or more addresses.
+    ///
+    /// The server is a `host:port` string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::Smtp;
+    /// let smtp = Smtp::new("localhost:25", "glacio@lidar.io", &["pete@gadom.ski"]);
+    /// ```
+    pub fn new(server: &str, from: &str, to: &[&str]) -> Smtp {
+        Smtp {
+            server: server.to_string(),
+            hostname: "localhost".to_string(),
+            from: from.to_string(),
+            to: to.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Sets the hostname that we use to introduce ourselves to the server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::Smtp;
+    /// let smtp = Smtp::new("localhost:25", "glacio@lidar.io", &["pete@gadom.ski"])
+    ///     .hostname("lidar.io");
+    /// ```
+    pub fn hostname(mut self, hostname: &str) -> Smtp {
+        self.hostname = hostname.to_string();
+        self
+    }
+
+    fn message(&self, event: &Event) -> String {
+        use chrono::Utc;
+        let mut message = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: [glacio] {}\r\nDate: {}\r\n\r\n",
+            self.from,
+            self.to
+                .iter()
+                .map(|to| format!("<{}>", to))
+                .collect::<Vec<_>>()
+                .join(", "),
+            summary(event),
+            Utc::now().to_rfc2822()
+        );
+        let body = format!(
+            "{}\r\n\r\nRule: {}\r\nValue: {}\r\nFiring since: {}\r\n",
+            summary(event),
+            event.alert.rule,
+            event.alert.value,
+            event.alert.datetime.to_rfc3339()
+        );
+        for line in body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push_str(".\r\n");
+        message
+    }
+}
+
+impl Notifier for Smtp {
+    fn name(&self) -> String {
+        format!("smtp:{}", self.to.join(","))
+    }
+
+    fn notify(&self, event: &Event) -> Result<()> {
+        use std::net::TcpStream;
+
+        let stream = TcpStream::connect(self.server.as_str())?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        smtp_reply(&mut reader, '2')?;
+        smtp_command(
+            &mut writer,
+            &mut reader,
+            &format!("HELO {}", self.hostname),
+            '2',
+        )?;
+        smtp_command(
+            &mut writer,
+            &mut reader,
+            &format!("MAIL FROM:<{}>", self.from),
+            '2',
+        )?;
+        for to in &self.to {
+            smtp_command(&mut writer, &mut reader, &format!("RCPT TO:<{}>", to), '2')?;
+        }
+        smtp_command(&mut writer, &mut reader, "DATA", '3')?;
+        writer.write_all(self.message(event).as_bytes())?;
+        smtp_reply(&mut reader, '2')?;
+        smtp_command(&mut writer, &mut reader, "QUIT", '2')
+    }
+}
+
+impl Ledger {
+    /// Opens a ledger backed by a file, reading any previously sent events.
+    ///
+    /// The file doesn't have to exist yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::Ledger;
+    /// let ledger = Ledger::open("/tmp/glacio-alerts-ledger.txt").unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Ledger> {
+        let mut sent = BTreeSet::new();
+        if path.as_ref().exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                sent.insert(line?);
+            }
+        }
+        Ok(Ledger {
+            path: Some(path.as_ref().to_path_buf()),
+            sent: sent,
+        })
+    }
+
+    /// Creates a ledger that only lives in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::Ledger;
+    /// let ledger = Ledger::in_memory();
+    /// ```
+    pub fn in_memory() -> Ledger {
+        Ledger {
+            path: None,
+            sent: BTreeSet::new(),
+        }
+    }
+
+    /// Has this notifier already sent this event?
+    pub fn contains(&self, notifier: &Notifier, event: &Event) -> bool {
+        self.sent.contains(&key(notifier, event))
+    }
+
+    /// Records that this notifier has sent this event.
+    pub fn insert(&mut self, notifier: &Notifier, event: &Event) -> Result<()> {
+        let key = key(notifier, event);
+        if let Some(ref path) = self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", key)?;
+        }
+        self.sent.insert(key);
+        Ok(())
+    }
+}
+
+impl Dispatcher {
+    /// Creates a new dispatcher with no notifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::{Dispatcher, Ledger};
+    /// let dispatcher = Dispatcher::new(Ledger::in_memory());
+    /// ```
+    pub fn new(ledger: Ledger) -> Dispatcher {
+        Dispatcher {
+            notifiers: Vec::new(),
+            ledger: ledger,
+            retries: 0,
+            retry_interval: Duration::from_secs(0),
+        }
+    }
+
+    /// Adds a notifier to this dispatcher.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::alerts::notify::{Dispatcher, Ledger, Webhook};
+    /// let dispatcher = Dispatcher::new(Ledger::in_memory())
+    ///     .notifier(Box::new(Webhook::new("http://localhost:8080")));
+    /// ```
+    pub fn notifier(mut self, notifier: Box<Notifier>) -> Dispatcher {
+        self.notifiers.push(notifier);
+        self
+    }
+
+    /// Sets the number of times a failed notification is retried, and how long to wait between
+    /// tries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use glacio_http::alerts::notify::{Dispatcher, Ledger};
+    /// let dispatcher = Dispatcher::new(Ledger::in_memory()).retry(3, Duration::from_secs(10));
+    /// ```
+    pub fn retry(mut self, retries: usize, interval: Duration) -> Dispatcher {
+        self.retries = retries;
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Sends the report's events that haven't been sent yet, returning the number of sent events.
+    ///
+    /// Every notifier is tried, even if an earlier one fails. If any notifier failed to send an
+    /// event after all retries, the first error is returned after the rest have been tried.
+    pub fn dispatch(&mut self, report: &Report) -> Result<usize> {
+        let mut count = 0;
+        let mut error = None;
+        for event in report.events.iter().rev() {
+            for notifier in &self.notifiers {
+                if self.ledger.contains(notifier.as_ref(), event) ||
+                    !is_relevant(&self.ledger, notifier.as_ref(), event, report)
+                {
+                    continue;
+                }
+                match self.send(notifier.as_ref(), event) {
+                    Ok(()) => {
+                        self.ledger.insert(notifier.as_ref(), event)?;
+                        count += 1;
+                    }
+                    Err(err) => {
+                        if error.is_none() {
+                            error = Some(err);
+                        }
+                    }
+                }
+            }
+        }
+        match error {
+            Some(err) => Err(err),
+            None => Ok(count),
+        }
+    }
+
+    fn send(&self, notifier: &Notifier, event: &Event) -> Result<()> {
+        let mut tries = 0;
+        loop {
+            match notifier.notify(event) {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    if tries >= self.retries {
+                        return Err(err);
+                    }
+                }
+            }
+            tries += 1;
+            thread::sleep(self.retry_interval);
+        }
+    }
+}
+
+fn is_relevant(ledger: &Ledger, notifier: &Notifier, event: &Event, report: &Report) -> bool {
+    match event.status {
+        Status::Firing => report.firing.contains(&event.alert),
+        Status::Resolved => {
+            let firing = Event {
+                status: Status::Firing,
+                datetime: event.alert.datetime,
+                alert: event.alert.clone(),
+            };
+            ledger.contains(notifier, &firing)
+        }
+    }
+}
+
+fn key(notifier: &Notifier, event: &Event) -> String {
+    format!("{} {}", notifier.name(), event.id())
+}
+
+fn summary(event: &Event) -> String {
+    let subject = match event.alert.subject {
+        Some(id) => format!(" ({})", id),
+        None => String::new(),
+    };
+    match event.status {
+        Status::Firing => format!("{}{} is firing", event.alert.rule, subject),
+        Status::Resolved => format!("{}{} is resolved", event.alert.rule, subject),
+    }
+}
+
+fn smtp_command<W: Write, R: BufRead>(
+    writer: &mut W,
+    reader: &mut R,
+    command: &str,
+    expected: char,
+) -> Result<()> {
+    write!(writer, "{}\r\n", command)?;
+    writer.flush()?;
+    smtp_reply(reader, expected)
+}
+
+fn smtp_reply<R: BufRead>(reader: &mut R, expected: char) -> Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::Notify(
+                "smtp server closed the connection".to_string(),
+            ));
+        }
+        if !line.starts_with(expected) {
+            return Err(Error::Notify(
+                format!("unexpected smtp reply: {}", line.trim_right()),
+            ));
+        }
+        if line.chars().nth(3) != Some('-') {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use glacio::atlas::alert::{Alert, Kind};
+    use std::cell::RefCell;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        sent: RefCell<Vec<String>>,
+        failures: RefCell<usize>,
+    }
+
+    impl Notifier for Recorder {
+        fn name(&self) -> String {
+            "recorder".to_string()
+        }
+
+        fn notify(&self, event: &Event) -> Result<()> {
+            let mut failures = self.failures.borrow_mut();
+            if *failures > 0 {
+                *failures -= 1;
+                Err(Error::Notify("planned failure".to_string()))
+            } else {
+                self.sent.borrow_mut().push(event.id());
+                Ok(())
+            }
+        }
+    }
+
+    fn alert(hour: u32) -> Alert {
+        Alert {
+            rule: "low battery".to_string(),
+            kind: Kind::StateOfCharge,
+            subject: Some(1),
+            value: 42.,
+            datetime: Utc.ymd(2017, 8, 1).and_hms(hour, 0, 0),
+        }
+    }
+
+    fn event(status: Status, hour: u32, alert: Alert) -> Event {
+        Event {
+            status: status,
+            datetime: Utc.ymd(2017, 8, 1).and_hms(hour, 0, 0),
+            alert: alert,
+        }
+    }
+
+    fn report() -> Report {
+        Report {
+            firing: vec![alert(3)],
+            events: vec![
+                event(Status::Firing, 3, alert(3)),
+                event(Status::Resolved, 2, alert(1)),
+                event(Status::Firing, 1, alert(1)),
+            ],
+        }
+    }
+
+    #[test]
+    fn dispatch_only_relevant_events_once() {
+        let mut dispatcher = Dispatcher::new(Ledger::in_memory())
+            .notifier(Box::new(Recorder::default()));
+        assert_eq!(1, dispatcher.dispatch(&report()).unwrap());
+        assert_eq!(0, dispatcher.dispatch(&report()).unwrap());
+        let mut report = report();
+        report.firing.clear();
+        report.events.insert(0, event(Status::Resolved, 4, alert(3)));
+        assert_eq!(1, dispatcher.dispatch(&report).unwrap());
+    }
+
+    #[test]
+    fn dispatch_retries() {
+        let recorder = Recorder::default();
+        *recorder.failures.borrow_mut() = 2;
+        let mut dispatcher = Dispatcher::new(Ledger::in_memory())
+            .notifier(Box::new(recorder))
+            .retry(1, Duration::from_secs(0));
+        assert!(dispatcher.dispatch(&report()).is_err());
+        assert_eq!(1, dispatcher.dispatch(&report()).unwrap());
+    }
+
+    #[test]
+    fn ledger_persists() {
+        use std::env;
+        use std::fs;
+
+        let path = env::temp_dir().join("glacio-ledger-persists.txt");
+        let _ = fs::remove_file(&path);
+        let recorder = Recorder::default();
+        let event = event(Status::Firing, 3, alert(3));
+        Ledger::open(&path)
+            .unwrap()
+            .insert(&recorder, &event)
+            .unwrap();
+        assert!(Ledger::open(&path).unwrap().contains(&recorder, &event));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.to_lowercase().starts_with("content-length:") {
+                    content_length = line[15..].trim().parse().unwrap();
+                } else if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            tx.send(String::from_utf8(body).unwrap()).unwrap();
+            let mut stream = stream;
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+        });
+        Webhook::new(&url)
+            .notify(&event(Status::Firing, 3, alert(3)))
+            .unwrap();
+        let body = rx.recv().unwrap();
+        handle.join().unwrap();
+        assert!(body.contains("\"text\":\"low battery (1) is firing\""));
+        assert!(body.contains("\"rule\":\"low battery\""));
+    }
+
+    #[test]
+    fn smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut transcript = String::new();
+            writer.write_all(b"220 localhost ESMTP\r\n").unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                transcript.push_str(&line);
+                let reply: &[u8] = if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        continue;
+                    }
+                } else if line.starts_with("DATA") {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line.starts_with("QUIT") {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else if line.starts_with("HELO") {
+                    b"250-localhost\r\n250 hello\r\n"
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+            }
+            tx.send(transcript).unwrap();
+        });
+        Smtp::new(&server, "glacio@lidar.io", &["pete@gadom.ski"])
+            .notify(&event(Status::Firing, 3, alert(3)))
+            .unwrap();
+        let transcript = rx.recv().unwrap();
+        handle.join().unwrap();
+        assert!(transcript.starts_with("HELO localhost\r\n"));
+        assert!(transcript.contains("MAIL FROM:<glacio@lidar.io>\r\n"));
+        assert!(transcript.contains("RCPT TO:<pete@gadom.ski>\r\n"));
+        assert!(transcript.contains("Subject: [glacio] low battery (1) is firing\r\n"));
+        assert!(transcript.ends_with(".\r\nQUIT\r\n"));
+    }
+}
@@ -0,0 +1,35 @@
+use Result;
+use alerts::Config;
+use atlas;
+use chrono::{DateTime, Utc};
+use glacio::atlas::alert::{Alert, Event};
+
+/// A report of the alerts raised over the ATLAS heartbeat history.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// The alerts that are currently firing.
+    pub firing: Vec<Alert>,
+    /// Every alert status change, most recent first.
+    pub events: Vec<Event>,
+}
+
+impl Report {
+    /// Creates a new report by evaluating the alert rules against all ATLAS heartbeats.
+    ///
+    /// Overdue heartbeats are checked as of `now`.
+    pub fn new(config: &Config, atlas: &atlas::Config, now: DateTime<Utc>) -> Result<Report> {
+        let mut monitor = config.monitor(atlas)?;
+        let mut heartbeats = atlas.heartbeats()?;
+        heartbeats.sort();
+        let mut events = Vec::new();
+        for heartbeat in &heartbeats {
+            events.extend(monitor.process(heartbeat)?);
+        }
+        events.extend(monitor.check(now));
+        events.reverse();
+        Ok(Report {
+            firing: monitor.firing().cloned().collect(),
+            events: events,
+        })
+    }
+}
@@ -1,7 +1,10 @@
 use Result;
+use alerts::handlers::Alerts;
 use atlas::handlers::Atlas;
 use cameras::handlers::Cameras;
 use config::Config;
+use events::Events;
+use stations::handlers::Stations;
 use iron::{AfterMiddleware, Chain, Handler, IronError, IronResult, Request, Response, Url};
 use iron::headers::AccessControlAllowOrigin;
 use logger::Logger;
@@ -12,6 +15,8 @@ use std::path::Path;
 #[allow(missing_debug_implementations)]
 pub struct Api {
     chain: Chain,
+    events: Events,
+    config: Config,
 }
 
 struct Custom404;
@@ -31,6 +36,9 @@ impl Api {
 
     /// Creates a new api from a Config.
     ///
+    /// The api doesn't watch for new heartbeats and images until `Api::watch` is called, so
+    /// `/events` only sends keepalives until then.
+    ///
     /// # Examples
     ///
     /// ```
@@ -42,7 +50,18 @@ impl Api {
         let mut router = Router::new();
         router.get("/", root, "root");
 
-        let cameras = Cameras::from(config.cameras);
+        let events = Events::new();
+        router.get(
+            "/events",
+            {
+                let events = events.clone();
+                move |r: &mut Request| events.stream(r)
+            },
+            "events",
+        );
+        let watch_config = config.clone();
+
+        let cameras = Cameras::from(config.cameras.clone());
         router.get(
             "/cameras",
             {
@@ -75,6 +94,22 @@ impl Api {
             },
             "camera-nearest-image",
         );
+        router.get(
+            "/cameras/:name/images/:datetime",
+            {
+                let cameras = cameras.clone();
+                move |r: &mut Request| cameras.image(r)
+            },
+            "camera-image",
+        );
+        router.get(
+            "/cameras/:name/images/:datetime/thumbnail",
+            {
+                let cameras = cameras.clone();
+                move |r: &mut Request| cameras.thumbnail(r)
+            },
+            "camera-image-thumbnail",
+        );
         router.get(
             "/cameras/:name/images/latest/redirect",
             {
@@ -84,19 +119,86 @@ impl Api {
             "camera-latest-image-redirect",
         );
 
+        let stations = Stations::new(config.stations, config.atlas.clone(), cameras);
+        router.get(
+            "/stations",
+            {
+                let stations = stations.clone();
+                move |r: &mut Request| stations.summary(r)
+            },
+            "stations",
+        );
+        router.get(
+            "/status",
+            {
+                let stations = stations.clone();
+                move |r: &mut Request| stations.status(r)
+            },
+            "status",
+        );
+        router.get(
+            "/sites.geojson",
+            {
+                let stations = stations.clone();
+                move |r: &mut Request| stations.geojson(r)
+            },
+            "sites-geojson",
+        );
+        router.get(
+            "/stations/:name/observations",
+            move |r: &mut Request| stations.observations(r),
+            "station-observations",
+        );
+
+        let alerts = Alerts::new(config.alerts, config.atlas.clone());
+        router.get(
+            "/alerts",
+            move |r: &mut Request| alerts.report(r),
+            "alerts",
+        );
+
         let atlas = Atlas::from(config.atlas);
         router.get(
             "/atlas/status",
-            move |r: &mut Request| atlas.status(r),
+            {
+                let atlas = atlas.clone();
+                move |r: &mut Request| atlas.status(r)
+            },
             "atlas-status",
         );
+        router.get(
+            "/atlas/heartbeats.csv",
+            move |r: &mut Request| atlas.heartbeats_csv(r),
+            "atlas-heartbeats-csv",
+        );
 
         let mut chain = Chain::new(router);
         chain.link(Logger::new(None));
 
         chain.link_after(Custom404);
 
-        Ok(Api { chain: chain })
+        Ok(Api {
+            chain: chain,
+            events: events,
+            config: watch_config,
+        })
+    }
+
+    /// Starts background threads that watch the SBD storage and camera directories, sending an
+    /// event to `/events` clients for each new heartbeat or image.
+    ///
+    /// The threads run until the process exits, so only call this once, on an api that's going
+    /// to be served.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use glacio_http::Api;
+    /// let api = Api::from_path("../data/rdcrlpjg.toml").unwrap();
+    /// api.watch();
+    /// ```
+    pub fn watch(&self) {
+        self.events.watch(&self.config);
     }
 }
 
@@ -145,7 +247,16 @@ fn root(request: &mut Request) -> IronResult<Response> {
         "camera_url": decode(url_for!(request, "camera", "name" => "{name}")),
         "camera_images_url": decode(url_for!(request, "camera-images", "name" => "{name}")),
         "camera_latest_image_redirect_url": decode(url_for!(request, "camera-latest-image-redirect", "name" => "{name}")),
+        "camera_image_url": decode(url_for!(request, "camera-image", "name" => "{name}", "datetime" => "{datetime}")),
+        "camera_image_thumbnail_url": decode(url_for!(request, "camera-image-thumbnail", "name" => "{name}", "datetime" => "{datetime}")),
         "atlas_status_url": url_for!(request, "atlas-status").as_ref().to_string(),
+        "atlas_heartbeats_csv_url": url_for!(request, "atlas-heartbeats-csv").as_ref().to_string(),
+        "stations_url": url_for!(request, "stations").as_ref().to_string(),
+        "status_url": url_for!(request, "status").as_ref().to_string(),
+        "sites_geojson_url": url_for!(request, "sites-geojson").as_ref().to_string(),
+        "station_observations_url": decode(url_for!(request, "station-observations", "name" => "{name}")),
+        "alerts_url": url_for!(request, "alerts").as_ref().to_string(),
+        "events_url": url_for!(request, "events").as_ref().to_string(),
     });
     json::response(data)
 }
@@ -174,6 +285,15 @@ mod tests {
         assert_eq!("http://localhost:3000/cameras/{name}", json["camera_url"]);
         assert_eq!("http://localhost:3000/cameras/{name}/images", json["camera_images_url"]);
         assert_eq!("http://localhost:3000/cameras/{name}/images/latest/redirect", json["camera_latest_image_redirect_url"]);
+        assert_eq!("http://localhost:3000/cameras/{name}/images/{datetime}", json["camera_image_url"]);
+        assert_eq!("http://localhost:3000/cameras/{name}/images/{datetime}/thumbnail", json["camera_image_thumbnail_url"]);
         assert_eq!("http://localhost:3000/atlas/status", json["atlas_status_url"]);
+        assert_eq!("http://localhost:3000/atlas/heartbeats.csv", json["atlas_heartbeats_csv_url"]);
+        assert_eq!("http://localhost:3000/stations", json["stations_url"]);
+        assert_eq!("http://localhost:3000/stations/{name}/observations", json["station_observations_url"]);
+        assert_eq!("http://localhost:3000/status", json["status_url"]);
+        assert_eq!("http://localhost:3000/sites.geojson", json["sites_geojson_url"]);
+        assert_eq!("http://localhost:3000/alerts", json["alerts_url"]);
+        assert_eq!("http://localhost:3000/events", json["events_url"]);
     }
 }
@@ -1,7 +1,18 @@
 //! Configuration objects for the ATLAS system.
 
 use {Error, Result};
-use glacio::atlas::{Efoy, Heartbeat, ReadSbd, SbdSource};
+use chrono::Duration;
+use glacio::Station;
+use glacio::atlas::{Efoy, Heartbeat, Index, ReadSbd, SbdSource, Watch};
+use glacio::station::Location;
+use stations::Site;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The name of the ATLAS system, as a station.
+pub const NAME: &'static str = "ATLAS";
+/// The number of hours between ATLAS heartbeats.
+pub const HEARTBEAT_INTERVAL: i64 = 1;
 
 /// ATLAS configuration.
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -16,6 +27,22 @@ pub struct Config {
     ///
     /// For now, we assume all EFOYs have the same setup.
     pub efoy: EfoyConfig,
+    /// The path to a persistent heartbeat index file.
+    ///
+    /// If provided, heartbeats are read from the index, which is updated with any new SBD files
+    /// on every read, instead of re-parsing all of the SBD storage.
+    #[serde(default)]
+    pub index: Option<String>,
+    /// Where the ATLAS system is.
+    #[serde(default)]
+    pub site: Option<Site>,
+    /// The path to the file that keeps the queue of commands sent to the modem.
+    ///
+    /// Defaults to a file in the SBD storage, named after the IMEI.
+    #[serde(default)]
+    pub mt_queue: Option<String>,
+    #[serde(skip)]
+    opened_index: Arc<Mutex<Option<Index>>>,
 }
 
 /// EFOY configuration.
@@ -41,6 +68,9 @@ pub struct EfoyCartridgeConfig {
 impl Config {
     /// Returns this config's heartbeats, with errors filtered out.
     ///
+    /// If an index is configured, the heartbeats come from the index, and are not guaranteed to be
+    /// sorted.
+    ///
     /// # Examples
     ///
     /// ```
@@ -50,7 +80,25 @@ impl Config {
     /// let heartbeats = config.heartbeats().unwrap();
     /// ```
     pub fn heartbeats(&self) -> Result<Vec<Heartbeat>> {
-        let heartbeats = self.read_sbd()?.flat_map(|r| r.ok()).collect::<Vec<_>>();
+        let heartbeats = if let Some(ref path) = self.index {
+            // Don't let one panicked request take down heartbeats for good.
+            let mut opened_index = self.opened_index.lock().unwrap_or_else(|err| err.into_inner());
+            if opened_index.is_none() {
+                *opened_index = Some(Index::open(path, &self.path, &self.imei)?);
+            }
+            let index = opened_index.as_mut().unwrap();
+            index.update()?;
+            index
+                .heartbeats()
+                .iter()
+                .filter(|heartbeat| {
+                    self.versions.is_empty() || self.versions.contains(&heartbeat.version)
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        } else {
+            self.read_sbd()?.flat_map(|r| r.ok()).collect::<Vec<_>>()
+        };
         if heartbeats.is_empty() {
             Err(Error::Config(
                 format!("No heartbeats in configured path: {}", self.path),
@@ -78,11 +126,23 @@ impl Config {
     /// }
     /// ```
     pub fn read_sbd(&self) -> Result<ReadSbd> {
-        SbdSource::new(&self.path)
-            .imeis(&[&self.imei])
-            .versions(&self.versions)
-            .iter()
-            .map_err(Error::from)
+        self.sbd_source().iter().map_err(Error::from)
+    }
+
+    /// Returns a blocking iterator over new heartbeats, as they arrive in the SBD storage.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use glacio_http::atlas::Config;
+    /// let mut config = Config::default();
+    /// config.path = "/var/iridium".to_string();
+    /// for result in config.watch().unwrap() {
+    ///     println!("New heartbeat: {:?}", result);
+    /// }
+    /// ```
+    pub fn watch(&self) -> Result<Watch> {
+        self.sbd_source().watch().map_err(Error::from)
     }
 
     /// Returns a properly-configured `Efoy`.
@@ -123,6 +183,54 @@ impl Config {
             .map(|config| config.name.as_str())
             .collect()
     }
+
+    /// Returns the path to the file that keeps the queue of commands sent to the modem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::atlas::Config;
+    /// let mut config = Config::default();
+    /// config.path = "/var/iridium".to_string();
+    /// config.imei = "300234063556840".to_string();
+    /// assert_eq!(
+    ///     "/var/iridium/300234063556840-mt-queue.json",
+    ///     config.mt_queue_path().to_str().unwrap()
+    /// );
+    /// ```
+    pub fn mt_queue_path(&self) -> PathBuf {
+        match self.mt_queue {
+            Some(ref path) => PathBuf::from(path),
+            None => Path::new(&self.path).join(format!("{}-mt-queue.json", self.imei)),
+        }
+    }
+
+    fn sbd_source(&self) -> SbdSource {
+        SbdSource::new(&self.path).imeis(&[&self.imei]).versions(
+            &self.versions,
+        )
+    }
+}
+
+impl Station for Config {
+    type Observation = Heartbeat;
+    type Error = Error;
+
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn location(&self) -> Option<Location> {
+        self.site.as_ref().map(|site| site.location())
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::hours(HEARTBEAT_INTERVAL)
+    }
+
+    fn observations<'a>(&'a self) -> Result<Box<Iterator<Item = Heartbeat> + 'a>> {
+        Ok(Box::new(self.heartbeats()?.into_iter()))
+    }
 }
 
 impl From<(String, f32)> for EfoyCartridgeConfig {
@@ -0,0 +1,155 @@
+//! Flatten ATLAS heartbeats into comma-separated values.
+//!
+//! Each heartbeat gets one row. Batteries and EFOYs get their own groups of columns, named by
+//! their id, e.g. `battery_1_state_of_charge` or `efoy_2_voltage`. If a heartbeat is missing a
+//! battery or EFOY that other heartbeats have, those cells are left empty.
+
+use Result;
+use glacio::atlas::Heartbeat;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+const EFOY_FIELDS: &'static [&'static str] = &["state", "voltage", "current", "cartridge", "consumed"];
+const SCANNER_FIELDS: &'static [&'static str] = &[
+    "scanner_power_on_datetime",
+    "scanner_voltage",
+    "scanner_temperature",
+    "scanner_memory_external",
+    "scanner_memory_internal",
+    "scan_start",
+    "scan_stop",
+    "scan_num_points",
+    "scan_range_min",
+    "scan_range_max",
+    "scan_file_size",
+    "scan_amplitude_min",
+    "scan_amplitude_max",
+    "scan_roll",
+    "scan_pitch",
+];
+
+/// Writes the heartbeats as CSV, with a header row.
+///
+/// # Examples
+///
+/// ```
+/// # use glacio_http::atlas::{self, csv};
+/// let mut config = atlas::Config::default();
+/// config.path = "../glacio/data".to_string();
+/// let heartbeats = config.heartbeats().unwrap();
+/// let mut bytes = Vec::new();
+/// csv::write(&heartbeats, &mut bytes).unwrap();
+/// ```
+pub fn write<W: Write>(heartbeats: &[Heartbeat], write: &mut W) -> Result<()> {
+    let batteries = heartbeats
+        .iter()
+        .flat_map(|heartbeat| heartbeat.batteries.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    let efoys = heartbeats
+        .iter()
+        .flat_map(|heartbeat| heartbeat.efoys.keys().cloned())
+        .collect::<BTreeSet<_>>();
+
+    let mut header = vec!["datetime".to_string(), "version".to_string()];
+    for id in &batteries {
+        header.push(format!("battery_{}_state_of_charge", id));
+    }
+    for id in &efoys {
+        for field in EFOY_FIELDS {
+            header.push(format!("efoy_{}_{}", id, field));
+        }
+    }
+    header.extend(SCANNER_FIELDS.iter().map(|s| s.to_string()));
+    header.push("is_riegl_switch_on".to_string());
+    write_row(write, &header)?;
+
+    for heartbeat in heartbeats {
+        let mut row = vec![heartbeat.datetime.to_rfc3339(), heartbeat.version.to_string()];
+        for id in &batteries {
+            row.push(heartbeat.batteries.get(id).map_or(String::new(), |battery| {
+                battery.state_of_charge.to_string()
+            }));
+        }
+        for id in &efoys {
+            match heartbeat.efoys.get(id) {
+                Some(efoy) => {
+                    row.push(String::from(efoy.state));
+                    row.push(efoy.voltage.to_string());
+                    row.push(efoy.current.to_string());
+                    row.push(efoy.cartridge.clone());
+                    row.push(efoy.consumed.to_string());
+                }
+                None => row.extend(EFOY_FIELDS.iter().map(|_| String::new())),
+            }
+        }
+        let scanner_power_on = &heartbeat.scanner_power_on;
+        let scan_stop = &heartbeat.scan_stop;
+        row.extend(vec![
+            scanner_power_on.datetime.to_rfc3339(),
+            scanner_power_on.voltage.to_string(),
+            scanner_power_on.temperature.to_string(),
+            scanner_power_on.memory_external.to_string(),
+            scanner_power_on.memory_internal.to_string(),
+            heartbeat.scan_start.to_rfc3339(),
+            scan_stop.datetime.to_rfc3339(),
+            scan_stop.num_points.to_string(),
+            scan_stop.range_min.to_string(),
+            scan_stop.range_max.to_string(),
+            scan_stop.file_size.to_string(),
+            scan_stop.amplitude_min.to_string(),
+            scan_stop.amplitude_max.to_string(),
+            scan_stop.roll.to_string(),
+            scan_stop.pitch.to_string(),
+            heartbeat.is_riegl_switch_on.to_string(),
+        ]);
+        write_row(write, &row)?;
+    }
+    Ok(())
+}
+
+fn write_row<W: Write>(write: &mut W, row: &[String]) -> Result<()> {
+    let row = row.iter().map(|field| escape(field)).collect::<Vec<_>>();
+    write!(write, "{}\r\n", row.join(","))?;
+    Ok(())
+}
+
+fn escape(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\r' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atlas::Config;
+
+    #[test]
+    fn heartbeats() {
+        let mut config = Config::default();
+        config.path = "../glacio/data".to_string();
+        let heartbeats = config.heartbeats().unwrap();
+        let mut bytes = Vec::new();
+        write(&heartbeats, &mut bytes).unwrap();
+        let csv = String::from_utf8(bytes).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with(
+            "datetime,version,battery_1_state_of_charge,battery_2_state_of_charge,efoy_1_state,",
+        ));
+        assert!(lines[0].ends_with(",scan_roll,scan_pitch,is_riegl_switch_on"));
+        assert!(lines[1].starts_with(
+            "2017-08-01T00:00:55+00:00,3,94.208,94.947,auto off,26.63,-0.03,1.1,3.741,",
+        ));
+        assert!(lines[1].ends_with(",-0.34,-0.198,true"));
+    }
+
+    #[test]
+    fn escape_fields() {
+        assert_eq!("auto off", escape("auto off"));
+        assert_eq!("\"a,b\"", escape("a,b"));
+        assert_eq!("\"a \"\"quote\"\"\"", escape("a \"quote\""));
+    }
+}
@@ -1,7 +1,8 @@
 //! Handle ATLAS requests.
 
-use atlas::{Config, Status};
-use iron::{IronResult, Request, Response};
+use Range;
+use atlas::{Config, Status, csv};
+use iron::{IronResult, Request, Response, status};
 use json;
 
 /// Handler for ATLAS requests.
@@ -24,6 +25,25 @@ impl Atlas {
     pub fn status(&self, _: &mut Request) -> IronResult<Response> {
         json::response(itry!(Status::new(&self.config)))
     }
+
+    /// Returns the heartbeats as CSV, optionally limited to a range of datetimes.
+    pub fn heartbeats_csv(&self, request: &mut Request) -> IronResult<Response> {
+        use iron::headers::ContentType;
+
+        let range = itry!(Range::from_request(request), status::BadRequest);
+        let mut heartbeats = itry!(self.config.heartbeats())
+            .into_iter()
+            .filter(|heartbeat| range.contains(&heartbeat.datetime))
+            .collect::<Vec<_>>();
+        heartbeats.sort();
+        let mut bytes = Vec::new();
+        itry!(csv::write(&heartbeats, &mut bytes));
+        let mut response = Response::with((status::Ok, bytes));
+        response.headers.set(
+            ContentType("text/csv; charset=utf-8".parse().unwrap()),
+        );
+        Ok(response)
+    }
 }
 
 #[cfg(test)]
@@ -92,4 +112,72 @@ mod tests {
         assert_eq!("auto off", status["timeseries"]["efoy_state"]["1"][0]);
         assert_eq!(true, status["timeseries"]["is_riegl_switch_on"][0]);
     }
+
+    #[test]
+    fn heartbeats_csv() {
+        use iron::headers::ContentType;
+
+        let mut config = Config::default();
+        config.atlas.path = "../glacio/data".to_string();
+        let api = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/atlas/heartbeats.csv?start=2017-08-02T00:00:00Z",
+            Headers::new(),
+            &api,
+        ).unwrap();
+        assert_eq!(
+            "text/csv; charset=utf-8",
+            response.headers.get::<ContentType>().unwrap().to_string()
+        );
+        let csv = response::extract_body_to_string(response);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(2, lines.len());
+        assert!(lines[1].starts_with("2017-08-25T15:01:06+00:00,3,85.461,86.604,"));
+    }
+
+    #[test]
+    fn heartbeats_csv_sorted() {
+        use glacio::atlas::Index;
+        use std::env;
+        use std::fs::{self, File};
+        use std::io::{BufRead, BufReader, Write};
+
+        let sorted = env::temp_dir().join("glacio-heartbeats-csv-sorted.jsonl");
+        let reversed = env::temp_dir().join("glacio-heartbeats-csv-reversed.jsonl");
+        let _ = fs::remove_file(&sorted);
+        Index::open(&sorted, "../glacio/data", "300234063556840")
+            .unwrap()
+            .update()
+            .unwrap();
+        let mut lines = BufReader::new(File::open(&sorted).unwrap())
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        lines.reverse();
+        let mut file = File::create(&reversed).unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+
+        let mut config = Config::default();
+        config.atlas.path = "../glacio/data".to_string();
+        config.atlas.index = Some(reversed.to_string_lossy().into_owned());
+        let api = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/atlas/heartbeats.csv",
+            Headers::new(),
+            &api,
+        ).unwrap();
+        let csv = response::extract_body_to_string(response);
+        let datetimes = csv.lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert!(datetimes.len() > 1);
+        let mut expected = datetimes.clone();
+        expected.sort();
+        assert_eq!(expected, datetimes);
+        fs::remove_file(&sorted).unwrap();
+        fs::remove_file(&reversed).unwrap();
+    }
 }
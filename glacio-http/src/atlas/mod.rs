@@ -1,7 +1,9 @@
 //! Our remote LiDAR scanner operating at the Helheim Glacier.
 
 pub mod config;
+pub mod csv;
 pub mod handlers;
+pub mod netcdf;
 
 mod status;
 
@@ -0,0 +1,302 @@
+//! Export the ATLAS timeseries as a CF-compliant NetCDF file.
+//!
+//! The file has a `time` dimension with one entry per heartbeat, and `battery` and `efoy`
+//! dimensions whose coordinate variables hold the battery and EFOY ids. Variables follow version
+//! 1.6 of the [CF conventions](http://cfconventions.org/), so tools like xarray or Panoply can
+//! decode times and units without any extra help.
+
+use {Error, Result};
+use atlas::Config;
+use atlas::status::Timeseries;
+use chrono::Utc;
+use glacio::atlas::Heartbeat;
+use glacio::atlas::efoy::State;
+use netcdf::File;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const EFOY_STATE_FILL_VALUE: i8 = -1;
+
+/// Writes the heartbeats' timeseries as a NetCDF file.
+///
+/// The heartbeats must be sorted. Returns an error if there are no heartbeats, since a NetCDF
+/// file without any times isn't much use to anyone.
+///
+/// # Examples
+///
+/// ```
+/// # use glacio_http::atlas::{self, netcdf};
+/// let mut config = atlas::Config::default();
+/// config.path = "../glacio/data".to_string();
+/// config.efoy.cartridges.push(("1.1".to_string(), 8.0).into());
+/// config.efoy.cartridges.push(("1.2".to_string(), 8.0).into());
+/// let mut heartbeats = config.heartbeats().unwrap();
+/// heartbeats.sort();
+/// let mut bytes = Vec::new();
+/// netcdf::write(&config, &heartbeats, &mut bytes).unwrap();
+/// ```
+pub fn write<W: Write>(config: &Config, heartbeats: &[Heartbeat], write: &mut W) -> Result<()> {
+    if heartbeats.is_empty() {
+        return Err(Error::Config(
+            "cannot export an empty timeseries to netcdf".to_string(),
+        ));
+    }
+    let timeseries = Timeseries::from_heartbeats(config, heartbeats)?;
+    let mut file = File::new();
+
+    file.add_attribute("Conventions", "CF-1.6");
+    file.add_attribute("title", "ATLAS system status");
+    file.add_attribute("summary", summary(config));
+    file.add_attribute("source", "ATLAS heartbeat messages");
+    file.add_attribute("featureType", "timeSeries");
+    file.add_attribute("platform", "ATLAS");
+    if let Some(ref site) = config.site {
+        file.add_attribute("site", site.name.as_str());
+    }
+    file.add_attribute("imei", config.imei.as_str());
+    file.add_attribute(
+        "time_coverage_start",
+        heartbeats[0].datetime.to_rfc3339(),
+    );
+    file.add_attribute(
+        "time_coverage_end",
+        heartbeats[heartbeats.len() - 1].datetime.to_rfc3339(),
+    );
+    file.add_attribute(
+        "history",
+        format!("{} created by glacio", Utc::now().to_rfc3339()),
+    );
+
+    let time = file.add_dimension("time", heartbeats.len());
+    let battery = file.add_dimension("battery", timeseries.states_of_charge.len());
+    let efoy = file.add_dimension("efoy", timeseries.efoy_voltage.len());
+
+    file.add_variable(
+        "time",
+        &[time],
+        map(heartbeats, seconds),
+    ).attribute("standard_name", "time")
+        .attribute("long_name", "heartbeat time")
+        .attribute("units", "seconds since 1970-01-01 00:00:00")
+        .attribute("calendar", "gregorian")
+        .attribute("axis", "T");
+    file.add_variable("battery", &[battery], ids(&timeseries.states_of_charge))
+        .attribute("long_name", "battery id");
+    file.add_variable("efoy", &[efoy], ids(&timeseries.efoy_voltage))
+        .attribute("long_name", "EFOY fuel cell id");
+
+    file.add_variable(
+        "battery_state_of_charge",
+        &[time, battery],
+        by_time(&timeseries.states_of_charge, heartbeats.len()),
+    ).attribute("long_name", "battery state of charge")
+        .attribute("units", "percent");
+    file.add_variable(
+        "efoy_voltage",
+        &[time, efoy],
+        by_time(&timeseries.efoy_voltage, heartbeats.len()),
+    ).attribute("long_name", "EFOY voltage")
+        .attribute("units", "V");
+    file.add_variable(
+        "efoy_current",
+        &[time, efoy],
+        by_time(&timeseries.efoy_current, heartbeats.len()),
+    ).attribute("long_name", "EFOY current")
+        .attribute("units", "A");
+    file.add_variable(
+        "efoy_fuel_percentage",
+        &[time, efoy],
+        by_time(&timeseries.efoy_fuel_percentage, heartbeats.len()),
+    ).attribute("long_name", "EFOY fuel remaining across all cartridges")
+        .attribute("units", "percent");
+    file.add_variable(
+        "efoy_state",
+        &[time, efoy],
+        efoy_states(&timeseries, heartbeats),
+    ).attribute("long_name", "EFOY state")
+        .attribute("_FillValue", vec![EFOY_STATE_FILL_VALUE])
+        .attribute("flag_values", vec![0i8, 1, 2, 3])
+        .attribute("flag_meanings", "auto_off auto_on error freeze_protection");
+    file.add_variable(
+        "riegl_switch",
+        &[time],
+        timeseries
+            .is_riegl_switch_on
+            .iter()
+            .map(|&on| on as i8)
+            .collect::<Vec<_>>(),
+    ).attribute("long_name", "Riegl systems power switch")
+        .attribute("flag_values", vec![0i8, 1])
+        .attribute("flag_meanings", "off on");
+
+    file.add_variable(
+        "scanner_voltage",
+        &[time],
+        map(heartbeats, |h| h.scanner_power_on.voltage),
+    ).attribute("long_name", "scanner voltage at power on")
+        .attribute("units", "V");
+    file.add_variable(
+        "scanner_temperature",
+        &[time],
+        map(heartbeats, |h| h.scanner_power_on.temperature),
+    ).attribute("long_name", "scanner internal temperature at power on")
+        .attribute("units", "degree_Celsius");
+    file.add_variable(
+        "scanner_memory_external",
+        &[time],
+        map(heartbeats, |h| h.scanner_power_on.memory_external),
+    ).attribute("long_name", "scanner external memory available")
+        .attribute("units", "kB");
+    file.add_variable(
+        "scanner_memory_internal",
+        &[time],
+        map(heartbeats, |h| h.scanner_power_on.memory_internal),
+    ).attribute("long_name", "scanner internal memory available")
+        .attribute("units", "kB");
+    file.add_variable(
+        "scan_num_points",
+        &[time],
+        map(heartbeats, |h| h.scan_stop.num_points as i32),
+    ).attribute("long_name", "number of points in the last scan")
+        .attribute("units", "1");
+    file.add_variable(
+        "scan_range_min",
+        &[time],
+        map(heartbeats, |h| h.scan_stop.range_min),
+    ).attribute("long_name", "minimum range of the last scan, before MTA processing")
+        .attribute("units", "m");
+    file.add_variable(
+        "scan_range_max",
+        &[time],
+        map(heartbeats, |h| h.scan_stop.range_max),
+    ).attribute("long_name", "maximum range of the last scan, before MTA processing")
+        .attribute("units", "m");
+    file.add_variable(
+        "scan_file_size",
+        &[time],
+        map(heartbeats, |h| h.scan_stop.file_size),
+    ).attribute("long_name", "size of the last scan file")
+        .attribute("units", "byte");
+    file.add_variable(
+        "scan_roll",
+        &[time],
+        map(heartbeats, |h| h.scan_stop.roll),
+    ).attribute("long_name", "scanner roll")
+        .attribute("units", "degree");
+    file.add_variable(
+        "scan_pitch",
+        &[time],
+        map(heartbeats, |h| h.scan_stop.pitch),
+    ).attribute("long_name", "scanner pitch")
+        .attribute("units", "degree");
+
+    file.write(write)?;
+    Ok(())
+}
+
+fn summary(config: &Config) -> String {
+    let location = config.site.as_ref().map_or(String::new(), |site| {
+        format!(" at {}", site.name)
+    });
+    format!(
+        "Hourly status of ATLAS, a remote LiDAR scanner{}, as reported by heartbeat messages \
+         sent over Iridium SBD.",
+        location
+    )
+}
+
+fn seconds(heartbeat: &Heartbeat) -> f64 {
+    heartbeat.datetime.timestamp() as f64 +
+        f64::from(heartbeat.datetime.timestamp_subsec_millis()) / 1000.
+}
+
+fn ids<T>(map: &BTreeMap<u8, Vec<T>>) -> Vec<i32> {
+    map.keys().map(|&id| i32::from(id)).collect()
+}
+
+fn by_time(map: &BTreeMap<u8, Vec<f32>>, len: usize) -> Vec<f32> {
+    let mut values = Vec::with_capacity(len * map.len());
+    for i in 0..len {
+        values.extend(map.values().map(|series| series[i]));
+    }
+    values
+}
+
+fn map<T, F: Fn(&Heartbeat) -> T>(heartbeats: &[Heartbeat], f: F) -> Vec<T> {
+    heartbeats.iter().map(f).collect()
+}
+
+fn efoy_states(timeseries: &Timeseries, heartbeats: &[Heartbeat]) -> Vec<i8> {
+    let mut values = Vec::new();
+    for heartbeat in heartbeats {
+        for id in timeseries.efoy_state.keys() {
+            values.push(heartbeat.efoys.get(id).map_or(
+                EFOY_STATE_FILL_VALUE,
+                |efoy| match efoy.state {
+                    State::AutoOff => 0,
+                    State::AutoOn => 1,
+                    State::Error => 2,
+                    State::FreezeProtection => 3,
+                },
+            ));
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stations::Site;
+
+    #[test]
+    fn heartbeats() {
+        let mut config = Config::default();
+        config.path = "../glacio/data".to_string();
+        config.efoy.cartridges.push(("1.1".to_string(), 8.0).into());
+        config.efoy.cartridges.push(("1.2".to_string(), 8.0).into());
+        let mut heartbeats = config.heartbeats().unwrap();
+        heartbeats.sort();
+        let mut bytes = Vec::new();
+        write(&config, &heartbeats, &mut bytes).unwrap();
+        assert_eq!(b"CDF\x01", &bytes[0..4]);
+        assert_eq!(&[0, 0, 0, 0x0A, 0, 0, 0, 3], &bytes[8..16]);
+    }
+
+    #[test]
+    fn site() {
+        let mut config = Config::default();
+        config.path = "../glacio/data".to_string();
+        config.efoy.cartridges.push(("1.1".to_string(), 8.0).into());
+        config.efoy.cartridges.push(("1.2".to_string(), 8.0).into());
+        let mut heartbeats = config.heartbeats().unwrap();
+        heartbeats.sort();
+        assert_eq!(
+            "Hourly status of ATLAS, a remote LiDAR scanner, as reported by heartbeat messages \
+             sent over Iridium SBD.",
+            summary(&config)
+        );
+        let mut bytes = Vec::new();
+        write(&config, &heartbeats, &mut bytes).unwrap();
+        assert!(!contains(&bytes, b"site"));
+
+        config.site = Some(Site {
+            name: "Test site".to_string(),
+            ..Default::default()
+        });
+        assert!(summary(&config).contains("scanner at Test site,"));
+        let mut bytes = Vec::new();
+        write(&config, &heartbeats, &mut bytes).unwrap();
+        assert!(contains(&bytes, b"site"));
+        assert!(contains(&bytes, b"Test site"));
+    }
+
+    fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+        bytes.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn empty() {
+        assert!(write(&Config::default(), &[], &mut Vec::new()).is_err());
+    }
+}
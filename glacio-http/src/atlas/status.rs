@@ -93,10 +93,7 @@ impl Status {
     pub fn new(config: &Config) -> Result<Status> {
         let mut heartbeats = config.heartbeats()?;
         heartbeats.sort();
-        let mut timeseries = Timeseries::new(config, &heartbeats[0])?;
-        for heartbeat in &heartbeats {
-            timeseries.process(&heartbeat)?;
-        }
+        let timeseries = Timeseries::from_heartbeats(config, &heartbeats)?;
         let heartbeat = heartbeats.pop().unwrap();
         let batteries = heartbeat
             .batteries
@@ -147,6 +144,15 @@ impl EfoyStatus {
 }
 
 impl Timeseries {
+    /// Creates a timeseries from a sorted, non-empty slice of heartbeats.
+    pub fn from_heartbeats(config: &Config, heartbeats: &[Heartbeat]) -> Result<Timeseries> {
+        let mut timeseries = Timeseries::new(config, &heartbeats[0])?;
+        for heartbeat in heartbeats {
+            timeseries.process(heartbeat)?;
+        }
+        Ok(timeseries)
+    }
+
     fn new(config: &Config, heartbeat: &Heartbeat) -> Result<Timeseries> {
         let states_of_charge = heartbeat
             .batteries
@@ -1,5 +1,6 @@
 use {Error, Result};
 use cameras::{CameraConfig, Config, image};
+use glacio::camera::Index;
 use iron::Request;
 
 /// A serializable summary of a camera.
@@ -31,9 +32,14 @@ pub struct Detail {
     /// The url for this camera's images.
     pub images_url: String,
     /// The most recent image captured by this camera.
+    ///
+    /// If the camera is configured to skip corrupt images, this is the most recent image that
+    /// isn't corrupt.
     pub latest_image: image::Summary,
     /// The hourly interval that this camera takes pictures.
     pub interval: f32,
+    /// The number of this camera's images that are corrupt.
+    pub corrupt_images: usize,
 }
 
 impl Summary {
@@ -58,31 +64,47 @@ impl Summary {
 }
 
 impl Detail {
-    /// Creates a new detail from a configuration and a request.
+    /// Creates a new detail from a configuration, a request, and an up-to-date index of the
+    /// camera's images.
+    ///
+    /// The latest image's checksum is only included if `checksum` is true.
     pub fn new(
         request: &mut Request,
         camera_config: &CameraConfig,
         config: &Config,
+        index: &Index,
+        checksum: bool,
     ) -> Result<Detail> {
         let summary = Summary::new(request, camera_config);
-        let camera = camera_config.to_camera()?;
-        let mut images = camera
-            .images()?
-            .filter_map(|result| result.ok())
-            .collect::<Vec<_>>();
-        if images.is_empty() {
+        if index.images().is_empty() {
             return Err(Error::Config(
-                format!("No images found for camera: {:?}", camera),
+                format!("No images found for camera: {}", camera_config.name),
             ));
         }
-        images.sort();
+        let latest_image = match index.latest_image() {
+            Some(image) => image,
+            None => {
+                return Err(Error::Config(format!(
+                    "No complete images found for camera: {}",
+                    camera_config.name
+                )))
+            }
+        };
         Ok(Detail {
             name: summary.name,
             description: summary.description,
             url: summary.url,
             images_url: summary.images_url,
-            latest_image: image::Summary::new(&images.pop().unwrap(), &config)?,
+            latest_image: image::Summary::new(
+                request,
+                latest_image,
+                &image::properties(index, latest_image),
+                camera_config,
+                config,
+                checksum,
+            )?,
             interval: summary.interval,
+            corrupt_images: index.corrupt_images(),
         })
     }
 }
@@ -1,13 +1,46 @@
 use {Error, Result};
-use glacio::camera::{Camera, Server};
+use chrono::FixedOffset;
+use glacio::camera::{Camera, DatetimePolicy, Pattern, Server};
+use glacio::thumbnail::Cache;
+use stations::Site;
+use std::env;
 
 /// Global configuration for our remote cameras.
 #[derive(Default, Clone, Deserialize, Debug)]
 pub struct Config {
     /// The document root that is used to turn local paths into a url.
     pub document_root: String,
+    /// The url of the image server that serves the document root.
+    ///
+    /// Defaults to http://iridiumcam.lidar.io.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// More document roots, each with its own image server url, e.g. for cameras whose images are
+    /// stored on different disks.
+    #[serde(default)]
+    pub document_roots: Vec<DocumentRootConfig>,
     /// A vector of cameras.
     pub cameras: Vec<CameraConfig>,
+    /// The directory that holds cached image thumbnails.
+    ///
+    /// Defaults to `glacio-thumbnails` in the system's temporary directory.
+    #[serde(default)]
+    pub thumbnail_cache: Option<String>,
+}
+
+/// Configuration for an extra document root.
+///
+/// ```toml
+/// [[cameras.document_roots]]
+/// path = "/mnt/archive/StarDot"
+/// base_url = "http://archive.example.com/StarDot"
+/// ```
+#[derive(Default, Clone, Deserialize, Debug)]
+pub struct DocumentRootConfig {
+    /// The local directory.
+    pub path: String,
+    /// The url of the image server that serves the directory.
+    pub base_url: String,
 }
 
 /// Configuration for a single camera.
@@ -24,6 +57,56 @@ pub struct CameraConfig {
     pub path: String,
     /// The expected hourly interval between pictures.
     pub interval: f32,
+    /// Where the camera is.
+    #[serde(default)]
+    pub site: Option<Site>,
+    /// How many levels of subdirectories to search for images, e.g. two for images that are
+    /// archived into year and month directories.
+    ///
+    /// Defaults to zero, i.e. only images directly in `path` are found.
+    #[serde(default)]
+    pub max_depth: usize,
+    /// Whether to follow symbolic links to directories when searching subdirectories.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// How to find the date and time in the camera's image filenames.
+    ///
+    /// If not provided, filenames are expected to end with `%Y%m%d_%H%M%S` in UTC.
+    #[serde(default)]
+    pub pattern: Option<PatternConfig>,
+    /// Where image datetimes come from: `prefer_filename` (the default), `prefer_exif`, or
+    /// `cross_check`.
+    #[serde(default)]
+    pub datetime_policy: DatetimePolicy,
+    /// Whether to skip over corrupt images, e.g. ones cut short in transmission, when looking for
+    /// the latest image.
+    #[serde(default)]
+    pub skip_corrupt_images: bool,
+    /// Whether the API serves this camera's image files itself, instead of linking to them on the
+    /// image server.
+    ///
+    /// Use this for cameras whose images aren't under the document root, e.g. private cameras.
+    #[serde(default)]
+    pub serve_images: bool,
+}
+
+/// Configuration for a camera's filename pattern.
+///
+/// ```toml
+/// [cameras.cameras.pattern]
+/// regex = '_(?P<datetime>\d{4}-\d{2}-\d{2}_\d{4})$'
+/// format = "%Y-%m-%d_%H%M"
+/// utc_offset = -2.0
+/// ```
+#[derive(Default, Clone, Deserialize, Debug)]
+pub struct PatternConfig {
+    /// A regular expression with a `datetime` capture group, matched against the file stem.
+    pub regex: String,
+    /// The chrono format of the `datetime` capture group, e.g. `%s` for Unix timestamps.
+    pub format: String,
+    /// The offset of the filename datetimes from UTC, in hours.
+    #[serde(default)]
+    pub utc_offset: f32,
 }
 
 impl Config {
@@ -37,7 +120,33 @@ impl Config {
     /// let server = config.server();
     /// ```
     pub fn server(&self) -> Result<Server> {
-        Server::new(&self.document_root).map_err(Error::from)
+        let mut server = match self.base_url {
+            Some(ref base_url) => Server::with_base_url(&self.document_root, base_url)?,
+            None => Server::new(&self.document_root)?,
+        };
+        for document_root in &self.document_roots {
+            server.add_document_root(
+                &document_root.path,
+                &document_root.base_url,
+            )?;
+        }
+        Ok(server)
+    }
+
+    /// Returns the thumbnail cache for this configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::cameras::Config;
+    /// let config = Config::default();
+    /// let cache = config.thumbnail_cache();
+    /// ```
+    pub fn thumbnail_cache(&self) -> Cache {
+        match self.thumbnail_cache {
+            Some(ref path) => Cache::new(path),
+            None => Cache::new(env::temp_dir().join("glacio-thumbnails")),
+        }
     }
 }
 
@@ -52,6 +161,48 @@ impl CameraConfig {
     /// let camera = config.to_camera().unwrap();
     /// ```
     pub fn to_camera(&self) -> Result<Camera> {
-        Camera::new(&self.path).map_err(Error::from)
+        let mut camera = Camera::new(&self.path)?
+            .max_depth(self.max_depth)
+            .follow_symlinks(self.follow_symlinks)
+            .datetime_policy(self.datetime_policy)
+            .skip_corrupt(self.skip_corrupt_images);
+        if let Some(ref pattern) = self.pattern {
+            camera = camera.pattern(pattern.to_pattern()?);
+        }
+        Ok(camera)
+    }
+}
+
+impl PatternConfig {
+    /// Returns the glacio filename pattern for this configuration.
+    ///
+    /// The UTC offset must be less than 24 hours either way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::cameras::PatternConfig;
+    /// let mut config = PatternConfig {
+    ///     regex: r"^(?P<datetime>\d+)$".to_string(),
+    ///     format: "%s".to_string(),
+    ///     utc_offset: 0.,
+    /// };
+    /// let pattern = config.to_pattern().unwrap();
+    /// config.utc_offset = 24.;
+    /// assert!(config.to_pattern().is_err());
+    /// ```
+    pub fn to_pattern(&self) -> Result<Pattern> {
+        // Negated so that a NaN offset is an error too.
+        if !(self.utc_offset.abs() < 24.) {
+            return Err(Error::Config(format!(
+                "utc_offset must be less than 24 hours either way: {}",
+                self.utc_offset
+            )));
+        }
+        Pattern::new(&self.regex, &self.format)
+            .map(|pattern| {
+                pattern.offset(FixedOffset::east((self.utc_offset * 3600.) as i32))
+            })
+            .map_err(Error::from)
     }
 }
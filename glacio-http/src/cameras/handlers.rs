@@ -1,12 +1,29 @@
 //! Iron handlers for our remote camera systems.
 
-use {Error, Paginate, Result};
+use {Error, Paginate, Range, Result};
 use cameras::{CameraConfig, Config, camera, image};
-use glacio::Image;
-use iron::{IronResult, Request, Response, status};
-use iron::headers::Location;
+use chrono::{DateTime, Duration, Utc};
+use glacio::{Image, Station};
+use glacio::camera::{Index, Order, Properties, Sampling};
+use glacio::station;
+use iron::{IronResult, Plugin, Request, Response, status};
+use iron::headers::{ByteRangeSpec, Location};
 use json;
+use params::{Params, Value};
 use router::Router;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The default width of thumbnails, in pixels.
+pub const DEFAULT_THUMBNAIL_WIDTH: u32 = 320;
+
+/// The maximum width of thumbnails, in pixels.
+///
+/// If a wider thumbnail is requested, the width is clamped to this value.
+pub const MAX_THUMBNAIL_WIDTH: u32 = 1024;
+
+/// The longest interval that images can be sampled at, in hours, which is about a century.
+pub const MAX_SAMPLING_INTERVAL: f64 = 100. * 366. * 24.;
 
 /// A multi-route handler for camera-based requests.
 ///
@@ -15,18 +32,54 @@ use router::Router;
 /// together all camera-based handler functions. The Iron `Handler` trait is not actually
 /// implemented here, since we just pass these methods as closure-wrapped functions to our router
 /// setup.
+///
+/// Each camera's images are kept in an in-memory `Index`, which is built on the camera's first
+/// request and refreshed on every request after, so only new images are read.
 #[derive(Clone, Debug)]
 pub struct Cameras {
     config: Config,
+    indexes: Arc<HashMap<String, Mutex<Option<Index>>>>,
+}
+
+/// A configured camera as a `glacio::Station`, whose observations come from the camera's index.
+///
+/// The station listing and status overview check every camera on every request, so they share the
+/// `Cameras` handler's indexes rather than listing each camera's directory again.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraStation<'a> {
+    cameras: &'a Cameras,
+    config: &'a CameraConfig,
 }
 
 impl From<Config> for Cameras {
     fn from(config: Config) -> Cameras {
-        Cameras { config: config }
+        let indexes = config
+            .cameras
+            .iter()
+            .map(|camera_config| (camera_config.name.clone(), Mutex::new(None)))
+            .collect();
+        Cameras {
+            config: config,
+            indexes: Arc::new(indexes),
+        }
     }
 }
 
 impl Cameras {
+    /// Returns all configured cameras as stations, in configuration order.
+    pub fn stations(&self) -> Vec<CameraStation> {
+        self.config
+            .cameras
+            .iter()
+            .map(|camera_config| {
+                CameraStation {
+                    cameras: self,
+                    config: camera_config,
+                }
+            })
+            .collect()
+    }
+
     /// Returns a list of all configured cameras.
     pub fn summary(&self, request: &mut Request) -> IronResult<Response> {
         json::response(
@@ -41,58 +94,178 @@ impl Cameras {
     /// Returns detail about one camera, as requested in the parameters.
     pub fn detail(&self, request: &mut Request) -> IronResult<Response> {
         let camera_config = iexpect!(self.camera_config(request));
-        json::response(itry!(
-            camera::Detail::new(request, camera_config, &self.config)
-        ))
+        let checksum = itry!(checksum(request), status::BadRequest);
+        json::response(itry!(self.with_index(camera_config, |index| {
+            camera::Detail::new(request, camera_config, &self.config, index, checksum)
+        })))
     }
 
     /// Returns a (paginated) list of images associated with the asked-for camera, starting with
     /// the most recent images.
+    ///
+    /// The images can be limited to a range of datetimes with `start` and `end`, sorted oldest
+    /// first with `order=asc`, and thinned out with either `every`, to return every nth image, or
+    /// `interval`, to return at most one image per that many hours.
     pub fn images(&self, request: &mut Request) -> IronResult<Response> {
         let camera_config = iexpect!(self.camera_config(request));
-        let mut images = itry!(self.camera_config_images(camera_config));
-        images.sort_by(|a, b| b.cmp(a));
-        let image_summaries = itry!(images.into_iter().paginate(request).and_then(|iter| {
-            iter.map(|image| image::Summary::new(&image, &self.config))
-                .collect::<Result<Vec<_>>>()
+        let range = itry!(Range::from_request(request), status::BadRequest);
+        let order = itry!(order(request), status::BadRequest);
+        let sampling = itry!(sampling(request), status::BadRequest);
+        let checksum = itry!(checksum(request), status::BadRequest);
+        let images = itry!(self.with_index(camera_config, |index| {
+            let images = index.range(range.start, range.end);
+            let mut images = match order {
+                Order::Ascending => images.iter().collect::<Vec<_>>(),
+                Order::Descending => images.iter().rev().collect::<Vec<_>>(),
+            };
+            if let Some(sampling) = sampling {
+                images = sampling.sample(images);
+            }
+            Ok(
+                images
+                    .into_iter()
+                    .paginate(request)?
+                    .map(|image| (image.clone(), image::properties(index, image)))
+                    .collect::<Vec<_>>(),
+            )
         }));
+        let image_summaries = itry!(
+            images
+                .iter()
+                .map(|&(ref image, ref properties)| {
+                    image::Summary::new(
+                        request,
+                        image,
+                        properties,
+                        camera_config,
+                        &self.config,
+                        checksum,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        );
         json::response(image_summaries)
     }
 
     /// Returns the image nearest to the parsed datetime.
     pub fn nearest_image(&self, request: &mut Request) -> IronResult<Response> {
-        use chrono::{DateTime, Utc};
+        let camera_config = iexpect!(self.camera_config(request));
+        let datetime = itry!(datetime(request), status::BadRequest);
+        let checksum = itry!(checksum(request), status::BadRequest);
+        let (image, properties) = iexpect!(itry!(self.with_index(camera_config, |index| {
+            Ok(index.nearest(datetime).map(|image| {
+                (image.clone(), image::properties(index, image))
+            }))
+        })));
+        json::response(itry!(image::Summary::new(
+            request,
+            &image,
+            &properties,
+            camera_config,
+            &self.config,
+            checksum,
+        )))
+    }
+
+    /// Returns the image file taken at the parsed datetime, if the API serves this camera's
+    /// images.
+    ///
+    /// A single byte range can be requested with the `Range` header, e.g. to resume an
+    /// interrupted download.
+    pub fn image(&self, request: &mut Request) -> IronResult<Response> {
+        use iron::headers::{AcceptRanges, ContentLength, ContentRange, ContentRangeSpec,
+                            ContentType, HttpDate, LastModified, Range, RangeUnit};
+        use iron::response::BodyReader;
+        use std::fs::File;
+        use std::io::{Read, Seek, SeekFrom};
+        use std::time::UNIX_EPOCH;
+        use time::{self, Timespec};
 
         let camera_config = iexpect!(self.camera_config(request));
-        let images = itry!(self.camera_config_images(camera_config));
-        let datetime: DateTime<Utc> = itry!(
-            request
-                .extensions
-                .get::<Router>()
-                .unwrap()
-                .find("datetime")
-                .unwrap()
-                .parse()
+        if !camera_config.serve_images {
+            return Ok(Response::with(status::NotFound));
+        }
+        let datetime = itry!(datetime(request), status::BadRequest);
+        let image = iexpect!(
+            itry!(self.find_image(camera_config, &datetime)),
+            status::NotFound
         );
-        let image = iexpect!(images.iter().min_by_key(|image| {
-            image
-                .datetime()
-                .signed_duration_since(datetime)
-                .num_seconds()
-                .abs()
-        }));
-        json::response(itry!(image::Summary::new(&image, &self.config)))
+        let mut file = itry!(File::open(image.path()));
+        let metadata = itry!(file.metadata());
+        let length = metadata.len();
+        let range = match request.headers.get::<Range>() {
+            Some(&Range::Bytes(ref specs)) if specs.len() == 1 => {
+                Some(byte_range(&specs[0], length))
+            }
+            _ => None,
+        };
+        let mut response = match range {
+            None => Response::with((status::Ok, file)),
+            Some(Some((first, last))) => {
+                itry!(file.seek(SeekFrom::Start(first)));
+                let mut response = Response::with(status::PartialContent);
+                response.headers.set(ContentLength(last - first + 1));
+                response.headers.set(ContentRange(ContentRangeSpec::Bytes {
+                    range: Some((first, last)),
+                    instance_length: Some(length),
+                }));
+                response.body = Some(Box::new(BodyReader(file.take(last - first + 1))));
+                response
+            }
+            Some(None) => {
+                let mut response = Response::with(status::RangeNotSatisfiable);
+                response.headers.set(ContentRange(ContentRangeSpec::Bytes {
+                    range: None,
+                    instance_length: Some(length),
+                }));
+                response
+            }
+        };
+        response.headers.set(AcceptRanges(vec![RangeUnit::Bytes]));
+        response.headers.set(ContentType::jpeg());
+        let modified = metadata.modified().ok().and_then(|modified| {
+            modified.duration_since(UNIX_EPOCH).ok()
+        });
+        if let Some(modified) = modified {
+            let timespec = Timespec::new(modified.as_secs() as i64, 0);
+            response.headers.set(LastModified(HttpDate(time::at_utc(timespec))));
+        }
+        Ok(response)
+    }
+
+    /// Returns a JPEG thumbnail of the image taken at the parsed datetime.
+    ///
+    /// The thumbnail's width can be set with the `width` parameter, which defaults to
+    /// `DEFAULT_THUMBNAIL_WIDTH` and is clamped to `MAX_THUMBNAIL_WIDTH`.
+    pub fn thumbnail(&self, request: &mut Request) -> IronResult<Response> {
+        use iron::headers::ContentType;
+        use std::fs::File;
+        use std::io::Read;
+
+        let camera_config = iexpect!(self.camera_config(request));
+        let datetime = itry!(datetime(request), status::BadRequest);
+        let width = itry!(thumbnail_width(request), status::BadRequest);
+        let image = iexpect!(
+            itry!(self.find_image(camera_config, &datetime)),
+            status::NotFound
+        );
+        let path = itry!(self.config.thumbnail_cache().thumbnail(&image, width));
+        let mut bytes = Vec::new();
+        itry!(File::open(path).and_then(|mut file| file.read_to_end(&mut bytes)));
+        let mut response = Response::with((status::Ok, bytes));
+        response.headers.set(ContentType::jpeg());
+        Ok(response)
     }
 
     /// Returns a redirect to the src url for the latest image for this camera.
     pub fn latest_image_redirect(&self, request: &mut Request) -> IronResult<Response> {
         let camera_config = iexpect!(self.camera_config(request));
-        let camera = itry!(camera_config.to_camera());
-        let image = iexpect!(camera.latest_image());
-        let server = itry!(self.config.server());
-        let url = itry!(server.url_for(&image));
+        let image = iexpect!(itry!(self.with_index(camera_config, |index| {
+            Ok(index.latest_image().cloned())
+        })));
+        let url = itry!(image::url_for(request, &image, camera_config, &self.config));
         let mut response = Response::with(status::Found);
-        response.headers.set(Location(url.to_string()));
+        response.headers.set(Location(url));
         Ok(response)
     }
 
@@ -113,19 +286,228 @@ impl Cameras {
         })
     }
 
-    fn camera_config_images(&self, camera_config: &CameraConfig) -> Result<Vec<Image>> {
-        let camera = camera_config.to_camera()?;
-        camera
-            .images()?
-            .map(|r| r.map_err(Error::from))
-            .collect::<Result<Vec<_>>>()
+    fn find_image(
+        &self,
+        camera_config: &CameraConfig,
+        datetime: &DateTime<Utc>,
+    ) -> Result<Option<Image>> {
+        self.with_index(camera_config, |index| Ok(index.find(*datetime).cloned()))
+    }
+
+    /// Refreshes the camera's index, creating it if needed, and calls the function with it.
+    ///
+    /// The index is locked while the function runs, so the function should copy out what it needs
+    /// rather than doing slow work, like reading image files. If the camera skips corrupt images,
+    /// its latest images are decoded before the function is called, without holding the lock.
+    fn with_index<T, F>(&self, camera_config: &CameraConfig, f: F) -> Result<T>
+    where
+        F: FnOnce(&Index) -> Result<T>,
+    {
+        loop {
+            let image = {
+                let mut guard = self.lock(camera_config)?;
+                let index = guard.as_mut().unwrap();
+                index.refresh()?;
+                match index.undecoded_latest_image() {
+                    Some(image) => image.clone(),
+                    None => return f(index),
+                }
+            };
+            let properties = Properties::decode(image.path());
+            self.lock(camera_config)?.as_mut().unwrap().update(
+                &image,
+                properties,
+            );
+        }
+    }
+
+    /// Refreshes the camera's index, creating it if needed, and calls the function with it,
+    /// without decoding any images.
+    fn with_refreshed_index<T, F>(&self, camera_config: &CameraConfig, f: F) -> Result<T>
+    where
+        F: FnOnce(&Index) -> Result<T>,
+    {
+        let mut guard = self.lock(camera_config)?;
+        let index = guard.as_mut().unwrap();
+        index.refresh()?;
+        f(index)
+    }
+
+    /// Locks the camera's index, creating it if needed.
+    fn lock(&self, camera_config: &CameraConfig) -> Result<MutexGuard<Option<Index>>> {
+        let mutex = match self.indexes.get(&camera_config.name) {
+            Some(mutex) => mutex,
+            None => {
+                return Err(Error::Config(
+                    format!("no index for camera: {}", camera_config.name),
+                ))
+            }
+        };
+        // A panic while refreshing leaves the index out of date, not broken, so we carry on.
+        let mut guard = mutex.lock().unwrap_or_else(|err| err.into_inner());
+        if guard.is_none() {
+            *guard = Some(Index::new(camera_config.to_camera()?));
+        }
+        Ok(guard)
+    }
+}
+
+impl<'a> CameraStation<'a> {
+    /// Returns the station's camera configuration.
+    pub fn config(&self) -> &'a CameraConfig {
+        self.config
     }
 }
 
+impl<'a> Station for CameraStation<'a> {
+    type Observation = Image;
+    type Error = Error;
+
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn location(&self) -> Option<station::Location> {
+        self.config.site.as_ref().map(|site| site.location())
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::seconds((self.config.interval * 3600.) as i64)
+    }
+
+    fn observations<'b>(&'b self) -> Result<Box<Iterator<Item = Image> + 'b>> {
+        let images = self.cameras.with_refreshed_index(
+            self.config,
+            |index| Ok(index.images().to_vec()),
+        )?;
+        Ok(Box::new(images.into_iter()))
+    }
+
+    fn last_report(&self) -> Result<Option<DateTime<Utc>>> {
+        self.cameras.with_refreshed_index(self.config, |index| {
+            Ok(index.images().last().map(|image| image.datetime()))
+        })
+    }
+}
+
+/// Returns the first and last bytes of the range that are in a file of the given length, or `None`
+/// if the range can't be satisfied.
+fn byte_range(spec: &ByteRangeSpec, length: u64) -> Option<(u64, u64)> {
+    match *spec {
+        ByteRangeSpec::FromTo(first, last) if first <= last && first < length => {
+            Some((first, last.min(length - 1)))
+        }
+        ByteRangeSpec::AllFrom(first) if first < length => Some((first, length - 1)),
+        ByteRangeSpec::Last(count) if count > 0 && length > 0 => {
+            Some((length - count.min(length), length - 1))
+        }
+        _ => None,
+    }
+}
+
+fn datetime(request: &mut Request) -> Result<DateTime<Utc>> {
+    request
+        .extensions
+        .get::<Router>()
+        .unwrap()
+        .find("datetime")
+        .unwrap()
+        .parse()
+        .map_err(Error::from)
+}
+
+fn checksum(request: &mut Request) -> Result<bool> {
+    let map = request.get::<Params>().unwrap();
+    match map.find(&["checksum"]) {
+        Some(&Value::Boolean(checksum)) => Ok(checksum),
+        Some(&Value::String(ref checksum)) => {
+            match checksum.as_str() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(Error::Config(format!("invalid checksum: {}", checksum))),
+            }
+        }
+        Some(value) => Err(Error::Config(format!("invalid checksum: {:?}", value))),
+        None => Ok(false),
+    }
+}
+
+fn order(request: &mut Request) -> Result<Order> {
+    let map = request.get::<Params>().unwrap();
+    match map.find(&["order"]) {
+        Some(&Value::String(ref order)) => {
+            match order.as_str() {
+                "asc" => Ok(Order::Ascending),
+                "desc" => Ok(Order::Descending),
+                _ => Err(Error::Config(format!("invalid order: {}", order))),
+            }
+        }
+        _ => Ok(Order::Descending),
+    }
+}
+
+fn sampling(request: &mut Request) -> Result<Option<Sampling>> {
+    use chrono::Duration;
+
+    let map = request.get::<Params>().unwrap();
+    let every = match map.find(&["every"]) {
+        Some(&Value::U64(every)) => Some(every as usize),
+        Some(&Value::String(ref every)) => Some(every.parse::<usize>()?),
+        _ => None,
+    };
+    let interval = match map.find(&["interval"]) {
+        Some(&Value::U64(interval)) => Some(interval as f64),
+        Some(&Value::F64(interval)) => Some(interval),
+        Some(&Value::String(ref interval)) => {
+            Some(interval.parse::<f64>().map_err(|_| {
+                Error::Config(format!("invalid interval: {}", interval))
+            })?)
+        }
+        _ => None,
+    };
+    if let Some(interval) = interval {
+        // Also rejects NaN, which fails every comparison.
+        if !(interval > 0. && interval <= MAX_SAMPLING_INTERVAL) {
+            return Err(Error::Config(format!(
+                "interval must be more than zero and at most {} hours: {}",
+                MAX_SAMPLING_INTERVAL,
+                interval
+            )));
+        }
+    }
+    match (every, interval) {
+        (Some(_), Some(_)) => Err(Error::Config(
+            "every and interval can't be used together".to_string(),
+        )),
+        (Some(every), None) => Ok(Some(Sampling::Every(every))),
+        (None, Some(interval)) => Ok(Some(Sampling::Interval(
+            Duration::seconds((interval * 3600.) as i64),
+        ))),
+        (None, None) => Ok(None),
+    }
+}
+
+fn thumbnail_width(request: &mut Request) -> Result<u32> {
+    let map = request.get::<Params>().unwrap();
+    let width = match map.find(&["width"]) {
+        Some(&Value::U64(width)) => width,
+        Some(&Value::String(ref width)) => width.parse::<u64>()?,
+        _ => u64::from(DEFAULT_THUMBNAIL_WIDTH),
+    };
+    Ok(if width == 0 {
+        DEFAULT_THUMBNAIL_WIDTH
+    } else if width >= u64::from(MAX_THUMBNAIL_WIDTH) {
+        MAX_THUMBNAIL_WIDTH
+    } else {
+        width as u32
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use {Api, Config};
-    use cameras::CameraConfig;
+    use cameras::{CameraConfig, DocumentRootConfig, PatternConfig};
+    use glacio::camera::DatetimePolicy;
     use iron::Headers;
     use iron::headers::Location;
     use iron::status::Status;
@@ -203,6 +585,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn stations_share_indexes() {
+        use super::Cameras;
+        use cameras;
+        use chrono::{TimeZone, Utc};
+        use glacio::Station;
+
+        let mut config = cameras::Config::default();
+        config.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: "../glacio/data/ATLAS_CAM".to_string(),
+            ..Default::default()
+        });
+        let cameras = Cameras::from(config);
+        assert!(cameras.indexes["ATLAS_CAM"].lock().unwrap().is_none());
+        let stations = cameras.stations();
+        assert_eq!(1, stations.len());
+        assert_eq!(
+            Some(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0)),
+            stations[0].last_report().unwrap()
+        );
+        assert_eq!(1, stations[0].observations().unwrap().count());
+        let guard = cameras.indexes["ATLAS_CAM"].lock().unwrap();
+        assert_eq!(1, guard.as_ref().unwrap().images().len());
+    }
+
+    #[test]
+    fn camera_corrupt_images() {
+        use std::fs;
+
+        let builder = ProjectBuilder::new("camera-corrupt");
+        builder.build();
+        let path = builder.root().join("ATLAS_CAM");
+        fs::create_dir_all(&path).unwrap();
+        fs::copy(
+            "../glacio/data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            path.join("ATLAS_CAM_20170806_152500.jpg"),
+        ).unwrap();
+        fs::copy(
+            "../glacio/data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial",
+            path.join("ATLAS_CAM_20170806_182500.jpg"),
+        ).unwrap();
+        let mut config = Config::new();
+        config.cameras.document_root = builder.root().to_string_lossy().into_owned();
+        config.cameras.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            skip_corrupt_images: true,
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let camera: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(1, *camera.get("corrupt_images").unwrap());
+        let image = camera.get("latest_image").unwrap();
+        assert_eq!("2017-08-06T15:25:00+00:00", image.get("datetime").unwrap());
+        assert_eq!(false, *image.get("corrupt").unwrap());
+
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/latest/redirect",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        assert_eq!(
+            &Location(
+                "http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg".to_string(),
+            ),
+            response.headers.get::<Location>().unwrap()
+        );
+
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(true, *images.get(0).unwrap().get("corrupt").unwrap());
+        assert_eq!(false, *images.get(1).unwrap().get("corrupt").unwrap());
+    }
+
     #[test]
     fn camera_images() {
         let mut builder = ProjectBuilder::new("camera");
@@ -212,7 +680,7 @@ mod tests {
         builder.build();
         let handler = build_api(&builder);
         let response = request::get(
-            "http://localhost:3000/cameras/ATLAS_CAM/images?per_page=2&page=2",
+            "http://localhost:3000/cameras/ATLAS_CAM/images?per_page=2&page=2&checksum=true",
             Headers::new(),
             &handler,
         ).unwrap();
@@ -224,6 +692,12 @@ mod tests {
             "http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_152507.jpg",
             image.get("url").unwrap()
         );
+        assert_eq!(0, *image.get("size").unwrap());
+        assert_eq!(&Value::Null, image.get("width").unwrap());
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            image.get("checksum").unwrap()
+        );
         let image = images.get(1).unwrap();
         assert_eq!("2017-08-06T15:25:06+00:00", image.get("datetime").unwrap());
         assert_eq!(
@@ -231,6 +705,413 @@ mod tests {
             image.get("url").unwrap()
         );
         assert_eq!(None, images.get(2));
+
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images?per_page=1",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(&Value::Null, images.get(0).unwrap().get("checksum").unwrap());
+    }
+
+    #[test]
+    fn camera_images_refresh() {
+        use std::fs::File;
+
+        let builder = ProjectBuilder::new("camera-refresh")
+            .file("ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg", "");
+        builder.build();
+        let handler = build_api(&builder);
+        let count = || {
+            let response = request::get(
+                "http://localhost:3000/cameras/ATLAS_CAM/images",
+                Headers::new(),
+                &handler,
+            ).unwrap();
+            let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+                .unwrap();
+            images.as_array().unwrap().len()
+        };
+        assert_eq!(1, count());
+        File::create(builder.root().join("ATLAS_CAM/ATLAS_CAM_20170806_182500.jpg")).unwrap();
+        assert_eq!(2, count());
+
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/latest/redirect",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        assert_eq!(
+            &Location(
+                "http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_182500.jpg".to_string(),
+            ),
+            response.headers.get::<Location>().unwrap()
+        );
+    }
+
+    #[test]
+    fn camera_images_query() {
+        let mut builder = ProjectBuilder::new("camera-query");
+        for hour in 0..10 {
+            builder = builder.file(format!("ATLAS_CAM/ATLAS_CAM_20170806_{:02}0000.jpg", hour), "");
+        }
+        builder.build();
+        let handler = build_api(&builder);
+        let datetimes = |query: &str| -> Vec<String> {
+            let response = request::get(
+                &format!("http://localhost:3000/cameras/ATLAS_CAM/images?{}", query),
+                Headers::new(),
+                &handler,
+            ).unwrap();
+            let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+                .unwrap();
+            images
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|image| image["datetime"].as_str().unwrap()[11..13].to_string())
+                .collect()
+        };
+        assert_eq!(
+            vec!["05", "04", "03"],
+            datetimes("start=2017-08-06T03:00:00Z&end=2017-08-06T05:00:00Z")
+        );
+        assert_eq!(vec!["08", "09"], datetimes("order=asc&start=2017-08-06T08:00:00Z"));
+        assert_eq!(vec!["09", "06", "03", "00"], datetimes("every=3"));
+        assert_eq!(
+            vec!["01", "05", "09"],
+            datetimes("order=asc&start=2017-08-06T01:00:00Z&interval=4")
+        );
+        assert_eq!(vec!["00", "03"], datetimes("order=asc&interval=2.5&per_page=2"));
+
+        for query in &[
+            "order=up",
+            "every=x",
+            "interval=x",
+            "every=2&interval=2",
+            "start=yesterday",
+            "checksum=yes",
+            "interval=1e15",
+            "interval=-1",
+            "interval=0",
+            "interval=NaN",
+            "interval=inf",
+        ]
+        {
+            let response = request::get(
+                &format!("http://localhost:3000/cameras/ATLAS_CAM/images?{}", query),
+                Headers::new(),
+                &handler,
+            );
+            assert_eq!(Some(Status::BadRequest), response.unwrap_err().response.status);
+        }
+    }
+
+    #[test]
+    fn camera_archived_images() {
+        let builder = ProjectBuilder::new("camera-archive")
+            .file("ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg", "")
+            .file("ATLAS_CAM/2017/07/ATLAS_CAM_20170731_120000.jpg", "");
+        builder.build();
+        let mut config = Config::new();
+        config.cameras.document_root = builder.root().to_string_lossy().into_owned();
+        config.cameras.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: format!("{}/ATLAS_CAM", builder.root().display()),
+            max_depth: 2,
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(
+            "http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            images.get(0).unwrap().get("url").unwrap()
+        );
+        assert_eq!(
+            "http://iridiumcam.lidar.io/ATLAS_CAM/2017/07/ATLAS_CAM_20170731_120000.jpg",
+            images.get(1).unwrap().get("url").unwrap()
+        );
+        assert_eq!(None, images.get(2));
+    }
+
+    #[test]
+    fn camera_document_roots() {
+        let builder = ProjectBuilder::new("camera-document-roots")
+            .file("ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg", "");
+        builder.build();
+        let mut config = Config::new();
+        config.cameras.document_root = builder.root().to_string_lossy().into_owned();
+        config.cameras.base_url = Some("https://example.com/primary".to_string());
+        config.cameras.document_roots.push(DocumentRootConfig {
+            path: "../glacio/data/HEL_BERGCAM3".to_string(),
+            base_url: "https://example.com/bergcam".to_string(),
+        });
+        config.cameras.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: format!("{}/ATLAS_CAM", builder.root().display()),
+            ..Default::default()
+        });
+        config.cameras.cameras.push(CameraConfig {
+            name: "StarDot1".to_string(),
+            path: "../glacio/data/HEL_BERGCAM3/StarDot1".to_string(),
+            ..Default::default()
+        });
+        config.cameras.cameras.push(CameraConfig {
+            name: "ELSEWHERE".to_string(),
+            path: "../glacio/data/ATLAS_CAM".to_string(),
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(
+            "https://example.com/primary/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            images.get(0).unwrap().get("url").unwrap()
+        );
+        let response = request::get(
+            "http://localhost:3000/cameras/StarDot1/images",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(
+            "https://example.com/bergcam/StarDot1/HEL_BERGCAM3_StarDot1_20170825_120000.jpg",
+            images.get(0).unwrap().get("url").unwrap()
+        );
+        let err = request::get(
+            "http://localhost:3000/cameras/ELSEWHERE/images",
+            Headers::new(),
+            &handler,
+        ).unwrap_err();
+        assert!(err.error.to_string().contains(
+            "outside every document root",
+        ));
+    }
+
+    #[test]
+    fn camera_images_with_pattern() {
+        let builder = ProjectBuilder::new("camera-pattern")
+            .file("STARDOT/stardot_2017-08-06_1325.jpg", "")
+            .file("STARDOT/stardot_2017-08-06_1425.jpg", "");
+        builder.build();
+        let mut config = Config::new();
+        config.cameras.document_root = builder.root().to_string_lossy().into_owned();
+        config.cameras.cameras.push(CameraConfig {
+            name: "STARDOT".to_string(),
+            path: format!("{}/STARDOT", builder.root().display()),
+            pattern: Some(PatternConfig {
+                regex: r"_(?P<datetime>\d{4}-\d{2}-\d{2}_\d{4})$".to_string(),
+                format: "%Y-%m-%d_%H%M".to_string(),
+                utc_offset: -2.,
+            }),
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/STARDOT/images",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!(
+            "2017-08-06T16:25:00+00:00",
+            images.get(0).unwrap().get("datetime").unwrap()
+        );
+        assert_eq!(
+            "2017-08-06T15:25:00+00:00",
+            images.get(1).unwrap().get("datetime").unwrap()
+        );
+        assert_eq!(None, images.get(2));
+    }
+
+    #[test]
+    fn camera_images_cross_check() {
+        let mut config = Config::new();
+        config.cameras.document_root = "../glacio/data".to_string();
+        config.cameras.cameras.push(CameraConfig {
+            name: "STARDOT".to_string(),
+            path: "../glacio/data/exif".to_string(),
+            datetime_policy: DatetimePolicy::CrossCheck,
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/STARDOT/images?checksum=true",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        let image = images.get(0).unwrap();
+        assert_eq!("2017-08-06T16:25:00+00:00", image.get("datetime").unwrap());
+        assert_eq!(&Value::Null, image.get("camera_model").unwrap());
+        assert_eq!(&Value::Null, image.get("clock_drift").unwrap());
+        let image = images.get(1).unwrap();
+        assert_eq!("2017-08-06T15:25:00+00:00", image.get("datetime").unwrap());
+        assert_eq!("NetCam SC 5MP", image.get("camera_model").unwrap());
+        assert_eq!("SD0123456", image.get("camera_serial_number").unwrap());
+        assert_eq!(150, *image.get("clock_drift").unwrap());
+        assert_eq!(301, *image.get("size").unwrap());
+        assert_eq!(8, *image.get("width").unwrap());
+        assert_eq!(8, *image.get("height").unwrap());
+        assert_eq!(64, image.get("checksum").unwrap().as_str().unwrap().len());
+        assert_eq!(None, images.get(2));
+    }
+
+    #[test]
+    fn camera_image() {
+        use iron::headers::{ContentLength, ContentRange, ContentRangeSpec, ContentType,
+                            LastModified, Range};
+        use std::fs::File;
+        use std::io::Read;
+
+        let mut config = Config::new();
+        config.cameras.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: "../glacio/data/ATLAS_CAM".to_string(),
+            serve_images: true,
+            ..Default::default()
+        });
+        config.cameras.cameras.push(CameraConfig {
+            name: "PUBLIC_CAM".to_string(),
+            path: "../glacio/data/ATLAS_CAM".to_string(),
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        let images: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        let url = images.get(0).unwrap().get("url").unwrap().as_str().unwrap();
+        assert_eq!(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/2017-08-06T15:25:00Z",
+            url
+        );
+
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/latest/redirect",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        assert_eq!(
+            &Location(url.to_string()),
+            response.headers.get::<Location>().unwrap()
+        );
+
+        let response = request::get(url, Headers::new(), &handler).unwrap();
+        assert_eq!(Some(Status::Ok), response.status);
+        assert_eq!(&ContentType::jpeg(), response.headers.get::<ContentType>().unwrap());
+        assert_eq!(&ContentLength(98265), response.headers.get::<ContentLength>().unwrap());
+        assert!(response.headers.get::<LastModified>().is_some());
+        let mut bytes = Vec::new();
+        File::open("../glacio/data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, response::extract_body_to_bytes(response));
+
+        let mut headers = Headers::new();
+        headers.set(Range::bytes(1, 3));
+        let response = request::get(url, headers, &handler).unwrap();
+        assert_eq!(Some(Status::PartialContent), response.status);
+        assert_eq!(
+            &ContentRange(ContentRangeSpec::Bytes {
+                range: Some((1, 3)),
+                instance_length: Some(98265),
+            }),
+            response.headers.get::<ContentRange>().unwrap()
+        );
+        assert_eq!(&ContentLength(3), response.headers.get::<ContentLength>().unwrap());
+        assert_eq!(bytes[1..4].to_vec(), response::extract_body_to_bytes(response));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Range", vec![b"bytes=-2".to_vec()]);
+        let response = request::get(url, headers, &handler).unwrap();
+        assert_eq!(vec![0xff, 0xd9], response::extract_body_to_bytes(response));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Range", vec![b"bytes=100000-".to_vec()]);
+        let response = request::get(url, headers, &handler).unwrap();
+        assert_eq!(Some(Status::RangeNotSatisfiable), response.status);
+
+        let response = request::get(
+            "http://localhost:3000/cameras/PUBLIC_CAM/images/2017-08-06T15:25:00Z",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        assert_eq!(Some(Status::NotFound), response.status);
+    }
+
+    #[test]
+    fn camera_image_thumbnail() {
+        use glacio::jpeg;
+        use iron::headers::ContentType;
+        use std::fs::{self, File};
+        use std::io::Write;
+
+        let builder = ProjectBuilder::new("camera-thumbnail");
+        builder.build();
+        let path = builder.root().join("ATLAS_CAM");
+        fs::create_dir_all(&path).unwrap();
+        fs::copy(
+            "../glacio/data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            path.join("ATLAS_CAM_20170806_152500.jpg"),
+        ).unwrap();
+        let mut config = Config::new();
+        config.cameras.document_root = builder.root().to_string_lossy().into_owned();
+        config.cameras.thumbnail_cache =
+            Some(builder.root().join("thumbnails").to_string_lossy().into_owned());
+        config.cameras.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        });
+        let handler = Api::new(config).unwrap();
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/2017-08-06T15:25:00Z/thumbnail?width=128",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        assert_eq!(Some(Status::Ok), response.status);
+        assert_eq!(&ContentType::jpeg(), response.headers.get::<ContentType>().unwrap());
+        let thumbnail = builder.root().join("thumbnail.jpg");
+        File::create(&thumbnail)
+            .unwrap()
+            .write_all(&response::extract_body_to_bytes(response))
+            .unwrap();
+        let dimensions = jpeg::dimensions(&thumbnail).unwrap();
+        assert_eq!((128, 96), (dimensions.width, dimensions.height));
+
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/2017-08-06T15:26:00Z/thumbnail",
+            Headers::new(),
+            &handler,
+        ).unwrap();
+        assert_eq!(Some(Status::NotFound), response.status);
+        let response = request::get(
+            "http://localhost:3000/cameras/ATLAS_CAM/images/2017-08-06T15:25:00Z/thumbnail?width=x",
+            Headers::new(),
+            &handler,
+        );
+        assert_eq!(Some(Status::BadRequest), response.unwrap_err().response.status);
     }
 
     #[test]
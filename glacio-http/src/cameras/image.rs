@@ -1,23 +1,143 @@
-use Result;
-use cameras::Config;
-use glacio::camera::Image;
+use {Error, Result};
+use cameras::{CameraConfig, Config};
+use glacio::camera::{self, Image, Index, Properties};
+use iron::Request;
+use percent_encoding::{PATH_SEGMENT_ENCODE_SET, utf8_percent_encode};
+
+/// The format of image datetimes in API urls.
+pub const URL_DATETIME_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%SZ";
 
 /// A summary of information about an image.
 #[derive(Debug, Serialize)]
 pub struct Summary {
     /// The image's date and time, as a string.
     pub datetime: String,
-    /// The image's url on a remote server.
+    /// The image's url, either on the image server or, if the API serves the camera's images, on
+    /// the API.
     pub url: String,
+    /// The camera model, from the image's EXIF metadata.
+    pub camera_model: Option<String>,
+    /// The camera serial number, from the image's EXIF metadata.
+    pub camera_serial_number: Option<String>,
+    /// How far ahead of the filename datetime the camera's clock was, in seconds.
+    pub clock_drift: Option<i64>,
+    /// The size of the image file, in bytes.
+    pub size: Option<u64>,
+    /// The width of the image, in pixels, or `None` if the JPEG header can't be read.
+    pub width: Option<u16>,
+    /// The height of the image, in pixels, or `None` if the JPEG header can't be read.
+    pub height: Option<u16>,
+    /// The SHA-256 hash of the image file, if it was asked for.
+    pub checksum: Option<String>,
+    /// Whether the image file is corrupt, e.g. because it was cut short in transmission.
+    pub corrupt: bool,
 }
 
 impl Summary {
-    /// Creates a new summary from a server and an `Image`.
-    pub fn new(image: &Image, config: &Config) -> Result<Summary> {
-        let server = config.server()?;
+    /// Creates a new summary of an `Image` taken by the configured camera, with its file's
+    /// properties from the camera's index.
+    ///
+    /// The image file's properties are `None` if they can't be read, e.g. if the image was
+    /// truncated in transmission. Hashing reads the whole file, so the checksum is only included
+    /// if `checksum` is true.
+    pub fn new(
+        request: &Request,
+        image: &Image,
+        properties: &Properties,
+        camera_config: &CameraConfig,
+        config: &Config,
+        checksum: bool,
+    ) -> Result<Summary> {
+        let dimensions = properties.dimensions();
         Ok(Summary {
             datetime: image.datetime().to_rfc3339(),
-            url: server.url_for(image)?.as_ref().to_string(),
+            url: url_for(request, image, camera_config, config)?,
+            camera_model: image.exif().and_then(|exif| exif.model.clone()),
+            camera_serial_number: image.exif().and_then(|exif| exif.serial_number.clone()),
+            clock_drift: image.clock_drift().map(|clock_drift| clock_drift.num_seconds()),
+            size: properties.size(),
+            width: dimensions.map(|dimensions| dimensions.width),
+            height: dimensions.map(|dimensions| dimensions.height),
+            checksum: if checksum {
+                image.checksum().ok()
+            } else {
+                None
+            },
+            corrupt: properties.is_corrupt(),
         })
     }
 }
+
+/// Returns the properties of an indexed image.
+///
+/// Every indexed image has properties, but if one is somehow missing it's read from the file.
+pub fn properties(index: &Index, image: &Image) -> Properties {
+    index.properties(image).cloned().unwrap_or_else(
+        || Properties::read(image.path()),
+    )
+}
+
+/// Returns the url for an image taken by the configured camera.
+///
+/// If the API serves the camera's images, this is the API's url for the image file. Otherwise, it
+/// is the image's url on the image server, and it's a configuration error if the image isn't under
+/// any of the document roots.
+pub fn url_for(
+    request: &Request,
+    image: &Image,
+    camera_config: &CameraConfig,
+    config: &Config,
+) -> Result<String> {
+    build_url(image, camera_config, config, |name, datetime| {
+        url_for!(request, "camera-image", "name" => name, "datetime" => datetime)
+            .as_ref()
+            .to_string()
+    })
+}
+
+/// Returns the url for an image taken by the configured camera, without a request to build the
+/// API's urls from.
+///
+/// This is the same as `url_for`, except that if the API serves the camera's images, the url is
+/// relative to the API's root, e.g. `/cameras/ATLAS_CAM/images/2017-08-06T15:25:00Z`.
+pub fn relative_url_for(
+    image: &Image,
+    camera_config: &CameraConfig,
+    config: &Config,
+) -> Result<String> {
+    build_url(image, camera_config, config, |name, datetime| {
+        format!(
+            "/cameras/{}/images/{}",
+            utf8_percent_encode(&name, PATH_SEGMENT_ENCODE_SET),
+            datetime
+        )
+    })
+}
+
+fn build_url<F>(
+    image: &Image,
+    camera_config: &CameraConfig,
+    config: &Config,
+    api_url: F,
+) -> Result<String>
+where
+    F: FnOnce(String, String) -> String,
+{
+    if camera_config.serve_images {
+        let name = camera_config.name.clone();
+        let datetime = image.datetime().format(URL_DATETIME_FORMAT).to_string();
+        Ok(api_url(name, datetime))
+    } else {
+        let server = config.server()?;
+        match server.url_for(image) {
+            Ok(url) => Ok(url.as_ref().to_string()),
+            Err(camera::Error::OutsideDocumentRoots(path)) => Err(Error::Config(format!(
+                "image {} from camera {} is outside every document root, add a document root \
+                 for it or set serve_images for the camera",
+                path.display(),
+                camera_config.name
+            ))),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
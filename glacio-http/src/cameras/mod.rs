@@ -9,4 +9,5 @@ mod camera;
 mod config;
 mod image;
 
-pub use self::config::{CameraConfig, Config};
+pub use self::config::{CameraConfig, Config, DocumentRootConfig, PatternConfig};
+pub(crate) use self::image::relative_url_for;
@@ -1,4 +1,4 @@
-use {Error, Result, atlas, cameras};
+use {Error, Result, alerts, atlas, cameras, stations};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -10,10 +10,16 @@ use toml;
 /// (thanks to serde) onto a TOML configuration file.
 #[derive(Clone, Deserialize, Default, Debug)]
 pub struct Config {
+    /// Alert rules, evaluated against the ATLAS heartbeats.
+    #[serde(default)]
+    pub alerts: alerts::Config,
     /// The configuration for the ATLAS system.
     pub atlas: atlas::Config,
     /// Configuration for our remote cameras.
     pub cameras: cameras::Config,
+    /// Configuration for our weather stations.
+    #[serde(default)]
+    pub stations: stations::Config,
 }
 
 impl Config {
@@ -0,0 +1,303 @@
+//! Server-sent events for new ATLAS heartbeats and camera images.
+//!
+//! Instead of polling `/atlas/status` and `/cameras/:name`, clients can open one long-lived
+//! connection to `/events` and get a JSON event pushed to them whenever a new heartbeat is parsed
+//! or a new image lands in a camera directory. Background threads watch the SBD storage and
+//! camera directories and broadcast to every connected client.
+
+use {Config, Error, Result, cameras};
+use cameras::CameraConfig;
+use glacio::Image;
+use glacio::atlas::Heartbeat;
+use glacio::camera::Watch;
+use iron::{IronResult, Request, Response, status};
+use iron::headers::{CacheControl, CacheDirective, ContentType};
+use iron::response::WriteBody;
+use serde_json;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How often to send a comment to idle clients, which keeps proxies from closing the connection
+/// and lets us notice when a client has gone away.
+const KEEPALIVE_SECONDS: u64 = 30;
+
+/// A broadcaster of events to any number of subscribers.
+///
+/// Clones share the same set of subscribers.
+#[derive(Clone, Debug, Default)]
+pub struct Events {
+    subscribers: Arc<Mutex<Vec<Sender<Event>>>>,
+}
+
+/// A single server-sent event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    name: &'static str,
+    data: String,
+}
+
+struct Stream {
+    receiver: Receiver<Event>,
+}
+
+impl Events {
+    /// Creates a new broadcaster without any subscribers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::events::Events;
+    /// let events = Events::new();
+    /// ```
+    pub fn new() -> Events {
+        Default::default()
+    }
+
+    /// Starts background threads that watch the configured SBD storage and camera directories,
+    /// broadcasting an event for each new heartbeat or image.
+    ///
+    /// Sources that can't be watched, e.g. because their directory doesn't exist, are skipped
+    /// with a warning.
+    pub fn watch(&self, config: &Config) {
+        let events = self.clone();
+        let atlas = config.atlas.clone();
+        thread::spawn(move || match atlas.watch() {
+            Ok(watch) => {
+                for result in watch {
+                    match result.map_err(Error::from).and_then(
+                        |heartbeat| Event::heartbeat(&heartbeat),
+                    ) {
+                        Ok(event) => events.send(event),
+                        Err(err) => warn!("Problem while parsing heartbeat: {}", err),
+                    }
+                }
+            }
+            Err(err) => warn!("Not watching for new heartbeats: {}", err),
+        });
+
+        for camera_config in &config.cameras.cameras {
+            let events = self.clone();
+            let cameras = config.cameras.clone();
+            let camera_config = camera_config.clone();
+            thread::spawn(move || {
+                let watch = match watch_camera(&camera_config) {
+                    Ok(watch) => watch,
+                    Err(err) => {
+                        warn!("Not watching camera {}: {}", camera_config.name, err);
+                        return;
+                    }
+                };
+                for result in watch {
+                    match result.map_err(Error::from).and_then(|image| {
+                        Event::image(&image, &camera_config, &cameras)
+                    }) {
+                        Ok(event) => events.send(event),
+                        Err(err) => warn!("Problem with new {} image: {}", camera_config.name, err),
+                    }
+                }
+            });
+        }
+    }
+
+    /// Sends an event to all subscribers.
+    ///
+    /// Subscribers that have gone away are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::events::{Event, Events};
+    /// let events = Events::new();
+    /// let receiver = events.subscribe();
+    /// events.send(Event::new("ping", "{}"));
+    /// assert_eq!(Event::new("ping", "{}"), receiver.recv().unwrap());
+    /// ```
+    pub fn send(&self, event: Event) {
+        self.subscribers.lock().unwrap().retain(|sender| {
+            sender.send(event.clone()).is_ok()
+        });
+    }
+
+    /// Returns a receiver for all events sent after this call.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Streams events to the client as `text/event-stream`.
+    ///
+    /// The response never ends on its own, so this ties up a server thread for as long as the
+    /// client stays connected.
+    pub fn stream(&self, _: &mut Request) -> IronResult<Response> {
+        let body: Box<WriteBody> = Box::new(Stream { receiver: self.subscribe() });
+        let mut response = Response::with((status::Ok, body));
+        response.headers.set(
+            ContentType("text/event-stream".parse().unwrap()),
+        );
+        response.headers.set(
+            CacheControl(vec![CacheDirective::NoCache]),
+        );
+        Ok(response)
+    }
+}
+
+impl Event {
+    /// Creates a new event with a name and a JSON data string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::events::Event;
+    /// let event = Event::new("ping", "{}");
+    /// ```
+    pub fn new(name: &'static str, data: &str) -> Event {
+        Event {
+            name: name,
+            data: data.to_string(),
+        }
+    }
+
+    /// Creates a `heartbeat` event, whose data is the heartbeat.
+    pub fn heartbeat(heartbeat: &Heartbeat) -> Result<Event> {
+        Ok(Event::new("heartbeat", &serde_json::to_string(heartbeat)?))
+    }
+
+    /// Creates an `image` event, whose data is the camera name and the image's datetime and url.
+    ///
+    /// If the API serves the camera's images, the url is relative to the API's root.
+    pub fn image(
+        image: &Image,
+        camera_config: &CameraConfig,
+        config: &cameras::Config,
+    ) -> Result<Event> {
+        let data = json!({
+            "camera": camera_config.name,
+            "datetime": image.datetime().to_rfc3339(),
+            "url": cameras::relative_url_for(image, camera_config, config)?,
+        });
+        Ok(Event::new("image", &serde_json::to_string(&data)?))
+    }
+
+    /// Writes this event in the `text/event-stream` format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::events::Event;
+    /// let mut bytes = Vec::new();
+    /// Event::new("ping", "{}").write(&mut bytes).unwrap();
+    /// assert_eq!(b"event: ping\ndata: {}\n\n".to_vec(), bytes);
+    /// ```
+    pub fn write<W: Write + ?Sized>(&self, write: &mut W) -> io::Result<()> {
+        write!(write, "event: {}\n", self.name)?;
+        for line in self.data.lines() {
+            write!(write, "data: {}\n", line)?;
+        }
+        write!(write, "\n")
+    }
+}
+
+impl WriteBody for Stream {
+    fn write_body(&mut self, res: &mut Write) -> io::Result<()> {
+        write!(res, ": connected\n\n")?;
+        res.flush()?;
+        loop {
+            match self.receiver.recv_timeout(Duration::from_secs(KEEPALIVE_SECONDS)) {
+                Ok(event) => event.write(res)?,
+                Err(RecvTimeoutError::Timeout) => write!(res, ": keepalive\n\n")?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            res.flush()?;
+        }
+    }
+}
+
+fn watch_camera(camera_config: &CameraConfig) -> Result<Watch> {
+    Ok(camera_config.to_camera()?.watch()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glacio::Camera;
+    use iron::Headers;
+    use iron_test::{request, response};
+
+    #[test]
+    fn drop_subscriber() {
+        let events = Events::new();
+        drop(events.subscribe());
+        let receiver = events.subscribe();
+        events.send(Event::new("ping", "{}"));
+        assert_eq!(1, events.subscribers.lock().unwrap().len());
+        assert_eq!(Event::new("ping", "{}"), receiver.recv().unwrap());
+    }
+
+    fn camera_config() -> CameraConfig {
+        CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: "../glacio/data/ATLAS_CAM".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn image() {
+        let config = cameras::Config {
+            document_root: "../glacio/data".to_string(),
+            ..Default::default()
+        };
+        let camera = Camera::new("../glacio/data/ATLAS_CAM").unwrap();
+        let image = camera.latest_image().unwrap();
+        let event = Event::image(&image, &camera_config(), &config).unwrap();
+        let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        assert_eq!("image", event.name);
+        assert_eq!("ATLAS_CAM", data["camera"]);
+        assert_eq!("2017-08-06T15:25:00+00:00", data["datetime"]);
+        assert_eq!(
+            "http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            data["url"]
+        );
+    }
+
+    #[test]
+    fn served_image() {
+        let config = cameras::Config {
+            document_root: "/somewhere/else".to_string(),
+            ..Default::default()
+        };
+        let mut camera_config = camera_config();
+        let image = camera_config.to_camera().unwrap().latest_image().unwrap();
+        assert!(Event::image(&image, &camera_config, &config).is_err());
+        camera_config.serve_images = true;
+        let event = Event::image(&image, &camera_config, &config).unwrap();
+        let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        assert_eq!("/cameras/ATLAS_CAM/images/2017-08-06T15:25:00Z", data["url"]);
+    }
+
+    #[test]
+    fn stream() {
+        let events = Events::new();
+        let handler = {
+            let events = events.clone();
+            move |r: &mut Request| events.stream(r)
+        };
+        let response = request::get("http://localhost:3000/events", Headers::new(), &handler)
+            .unwrap();
+        drop(handler);
+        assert_eq!(
+            "text/event-stream",
+            response.headers.get::<ContentType>().unwrap().to_string()
+        );
+        events.send(Event::new("ping", "{\"a\": 1}"));
+        drop(events);
+        assert_eq!(
+            ": connected\n\nevent: ping\ndata: {\"a\": 1}\n\n",
+            response::extract_body_to_string(response)
+        );
+    }
+}
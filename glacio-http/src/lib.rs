@@ -8,11 +8,14 @@
         unused_qualifications)]
 
 extern crate chrono;
+extern crate curl;
 extern crate glacio;
 #[macro_use]
 extern crate iron;
 #[cfg(test)]
 extern crate iron_test;
+#[macro_use]
+extern crate log;
 extern crate logger;
 extern crate params;
 extern crate percent_encoding;
@@ -23,19 +26,26 @@ extern crate serde;
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
+extern crate time;
 extern crate toml;
 
+pub mod alerts;
 pub mod atlas;
 pub mod cameras;
+pub mod events;
 pub mod paginate;
+pub mod range;
+pub mod stations;
 
 mod api;
 mod config;
 mod json;
+mod netcdf;
 
 pub use api::Api;
 pub use config::Config;
 pub use paginate::Paginate;
+pub use range::Range;
 
 /// Our custom error enum.
 #[derive(Debug)]
@@ -44,14 +54,26 @@ pub enum Error {
     Atlas(glacio::atlas::Error),
     /// Wrapper around `glacio::camera::Error`.
     Camera(glacio::camera::Error),
+    /// Wrapper around `chrono::ParseError`.
+    ChronoParse(chrono::ParseError),
     /// Invalid configuration.
     Config(String),
+    /// Wrapper around `curl::Error`.
+    Curl(curl::Error),
     /// Wrapper around `std::io::Error`.
     Io(std::io::Error),
+    /// Wrapper around `serde_json::Error`.
+    Json(serde_json::Error),
+    /// An alert notification could not be delivered.
+    Notify(String),
     /// Wrapper around `std::num::ParseIntError`.
     ParseInt(std::num::ParseIntError),
+    /// Wrapper around `glacio::thumbnail::Error`.
+    Thumbnail(glacio::thumbnail::Error),
     /// Wrapper around `toml::de::Error`.
     TomlDe(toml::de::Error),
+    /// Wrapper around `glacio::weather::Error`.
+    Weather(glacio::weather::Error),
 }
 
 /// Our custom result type.
@@ -63,6 +85,24 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<chrono::ParseError> for Error {
+    fn from(err: chrono::ParseError) -> Error {
+        Error::ChronoParse(err)
+    }
+}
+
+impl From<curl::Error> for Error {
+    fn from(err: curl::Error) -> Error {
+        Error::Curl(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
 impl From<std::num::ParseIntError> for Error {
     fn from(err: std::num::ParseIntError) -> Error {
         Error::ParseInt(err)
@@ -87,15 +127,33 @@ impl From<glacio::camera::Error> for Error {
     }
 }
 
+impl From<glacio::thumbnail::Error> for Error {
+    fn from(err: glacio::thumbnail::Error) -> Error {
+        Error::Thumbnail(err)
+    }
+}
+
+impl From<glacio::weather::Error> for Error {
+    fn from(err: glacio::weather::Error) -> Error {
+        Error::Weather(err)
+    }
+}
+
 impl std::error::Error for Error {
     fn description(&self) -> &str {
         match *self {
             Error::Atlas(ref err) => err.description(),
             Error::Camera(ref err) => err.description(),
+            Error::ChronoParse(ref err) => err.description(),
             Error::Config(_) => "api configuration error",
+            Error::Curl(ref err) => err.description(),
             Error::Io(ref err) => err.description(),
+            Error::Json(ref err) => err.description(),
+            Error::Notify(_) => "alert notification error",
             Error::ParseInt(ref err) => err.description(),
+            Error::Thumbnail(ref err) => err.description(),
             Error::TomlDe(ref err) => err.description(),
+            Error::Weather(ref err) => err.description(),
         }
     }
 
@@ -103,10 +161,16 @@ impl std::error::Error for Error {
         match *self {
             Error::Atlas(ref err) => Some(err),
             Error::Camera(ref err) => Some(err),
+            Error::ChronoParse(ref err) => Some(err),
             Error::Config(_) => None,
+            Error::Curl(ref err) => Some(err),
             Error::Io(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            Error::Notify(_) => None,
             Error::ParseInt(ref err) => Some(err),
+            Error::Thumbnail(ref err) => Some(err),
             Error::TomlDe(ref err) => Some(err),
+            Error::Weather(ref err) => Some(err),
         }
     }
 }
@@ -116,10 +180,16 @@ impl std::fmt::Display for Error {
         match *self {
             Error::Atlas(ref err) => write!(f, "atlas error: {}", err),
             Error::Camera(ref err) => write!(f, "camera error: {}", err),
+            Error::ChronoParse(ref err) => write!(f, "chrono parse error: {}", err),
             Error::Config(ref msg) => write!(f, "api configuration error: {}", msg),
+            Error::Curl(ref err) => write!(f, "curl error: {}", err),
             Error::Io(ref err) => write!(f, "io error: {}", err),
+            Error::Json(ref err) => write!(f, "json error: {}", err),
+            Error::Notify(ref msg) => write!(f, "alert notification error: {}", msg),
             Error::ParseInt(ref err) => write!(f, "parse int error: {}", err),
+            Error::Thumbnail(ref err) => write!(f, "thumbnail error: {}", err),
             Error::TomlDe(ref err) => write!(f, "toml de error: {}", err),
+            Error::Weather(ref err) => write!(f, "weather error: {}", err),
         }
     }
 }
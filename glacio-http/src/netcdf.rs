@@ -0,0 +1,362 @@
+//! A minimal writer for the NetCDF classic file format.
+//!
+//! The classic format (CDF-1) is simple enough that we write it ourselves instead of linking
+//! against the NetCDF C library. Only fixed-size dimensions are supported; there is no unlimited
+//! (record) dimension. See
+//! https://www.unidata.ucar.edu/software/netcdf/docs/file_format_specifications.html for the
+//! specification.
+
+use std::io::{self, Write};
+
+const MAGIC: &'static [u8] = b"CDF\x01";
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+
+/// A NetCDF file, built up in memory and then written all at once.
+#[derive(Debug, Default)]
+pub struct File {
+    dimensions: Vec<(String, usize)>,
+    attributes: Vec<Attribute>,
+    variables: Vec<Variable>,
+}
+
+/// A NetCDF variable.
+#[derive(Debug)]
+pub struct Variable {
+    name: String,
+    dimensions: Vec<usize>,
+    attributes: Vec<Attribute>,
+    values: Values,
+}
+
+/// The values of a variable or attribute, which determine its NetCDF type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Values {
+    /// NC_BYTE.
+    Byte(Vec<i8>),
+    /// NC_CHAR, i.e. text.
+    Char(String),
+    /// NC_INT.
+    Int(Vec<i32>),
+    /// NC_FLOAT.
+    Float(Vec<f32>),
+    /// NC_DOUBLE.
+    Double(Vec<f64>),
+}
+
+#[derive(Debug)]
+struct Attribute {
+    name: String,
+    values: Values,
+}
+
+impl File {
+    /// Creates a new, empty file.
+    pub fn new() -> File {
+        Default::default()
+    }
+
+    /// Adds a dimension, returning its id.
+    pub fn add_dimension(&mut self, name: &str, len: usize) -> usize {
+        self.dimensions.push((name.to_string(), len));
+        self.dimensions.len() - 1
+    }
+
+    /// Adds a global attribute.
+    pub fn add_attribute<V: Into<Values>>(&mut self, name: &str, values: V) {
+        self.attributes.push(Attribute {
+            name: name.to_string(),
+            values: values.into(),
+        });
+    }
+
+    /// Adds a variable with the provided dimension ids.
+    ///
+    /// Values are in row-major order, and there must be exactly as many as the product of the
+    /// dimension lengths.
+    pub fn add_variable<V: Into<Values>>(
+        &mut self,
+        name: &str,
+        dimensions: &[usize],
+        values: V,
+    ) -> &mut Variable {
+        self.variables.push(Variable {
+            name: name.to_string(),
+            dimensions: dimensions.to_vec(),
+            attributes: Vec::new(),
+            values: values.into(),
+        });
+        self.variables.last_mut().unwrap()
+    }
+
+    /// Writes this file.
+    ///
+    /// Returns an `InvalidInput` error if a variable's values don't match its dimensions.
+    pub fn write<W: Write>(&self, write: &mut W) -> io::Result<()> {
+        for variable in &self.variables {
+            let expected = variable.dimensions.iter().fold(1, |product, &id| {
+                product * self.dimensions[id].1
+            });
+            if variable.values.len() != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "variable {} has {} values, expected {}",
+                        variable.name,
+                        variable.values.len(),
+                        expected
+                    ),
+                ));
+            }
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        put_u32(&mut header, 0);
+        put_list_header(&mut header, NC_DIMENSION, self.dimensions.len());
+        for &(ref name, len) in &self.dimensions {
+            put_name(&mut header, name);
+            put_u32(&mut header, len as u32);
+        }
+        put_attributes(&mut header, &self.attributes);
+        put_list_header(&mut header, NC_VARIABLE, self.variables.len());
+        let mut begins = Vec::new();
+        for variable in &self.variables {
+            put_name(&mut header, &variable.name);
+            put_u32(&mut header, variable.dimensions.len() as u32);
+            for &id in &variable.dimensions {
+                put_u32(&mut header, id as u32);
+            }
+            put_attributes(&mut header, &variable.attributes);
+            put_u32(&mut header, variable.values.nc_type());
+            put_u32(&mut header, variable.values.padded_size() as u32);
+            begins.push(header.len());
+            put_u32(&mut header, 0);
+        }
+
+        let mut offset = header.len();
+        for (variable, &position) in self.variables.iter().zip(&begins) {
+            let mut begin = Vec::new();
+            put_u32(&mut begin, offset as u32);
+            header[position..position + 4].copy_from_slice(&begin);
+            offset += variable.values.padded_size();
+        }
+
+        write.write_all(&header)?;
+        for variable in &self.variables {
+            let mut data = Vec::new();
+            variable.values.put(&mut data);
+            write.write_all(&data)?;
+        }
+        Ok(())
+    }
+}
+
+impl Variable {
+    /// Adds an attribute to this variable.
+    pub fn attribute<V: Into<Values>>(&mut self, name: &str, values: V) -> &mut Variable {
+        self.attributes.push(Attribute {
+            name: name.to_string(),
+            values: values.into(),
+        });
+        self
+    }
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match *self {
+            Values::Byte(ref v) => v.len(),
+            Values::Char(ref s) => s.len(),
+            Values::Int(ref v) => v.len(),
+            Values::Float(ref v) => v.len(),
+            Values::Double(ref v) => v.len(),
+        }
+    }
+
+    fn nc_type(&self) -> u32 {
+        match *self {
+            Values::Byte(_) => 1,
+            Values::Char(_) => 2,
+            Values::Int(_) => 4,
+            Values::Float(_) => 5,
+            Values::Double(_) => 6,
+        }
+    }
+
+    fn size(&self) -> usize {
+        let width = match *self {
+            Values::Byte(_) | Values::Char(_) => 1,
+            Values::Int(_) | Values::Float(_) => 4,
+            Values::Double(_) => 8,
+        };
+        width * self.len()
+    }
+
+    fn padded_size(&self) -> usize {
+        (self.size() + 3) / 4 * 4
+    }
+
+    fn put_with_len(&self, buf: &mut Vec<u8>) {
+        put_u32(buf, self.len() as u32);
+        self.put(buf);
+    }
+
+    fn put(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Values::Byte(ref v) => buf.extend(v.iter().map(|&n| n as u8)),
+            Values::Char(ref s) => buf.extend_from_slice(s.as_bytes()),
+            Values::Int(ref v) => {
+                for &n in v {
+                    put_u32(buf, n as u32);
+                }
+            }
+            Values::Float(ref v) => {
+                for &n in v {
+                    put_u32(buf, n.to_bits());
+                }
+            }
+            Values::Double(ref v) => {
+                for &n in v {
+                    let bits = n.to_bits();
+                    put_u32(buf, (bits >> 32) as u32);
+                    put_u32(buf, bits as u32);
+                }
+            }
+        }
+        for _ in self.size()..self.padded_size() {
+            buf.push(0);
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Values {
+    fn from(s: &'a str) -> Values {
+        Values::Char(s.to_string())
+    }
+}
+
+impl From<String> for Values {
+    fn from(s: String) -> Values {
+        Values::Char(s)
+    }
+}
+
+impl From<Vec<i8>> for Values {
+    fn from(v: Vec<i8>) -> Values {
+        Values::Byte(v)
+    }
+}
+
+impl From<i32> for Values {
+    fn from(n: i32) -> Values {
+        Values::Int(vec![n])
+    }
+}
+
+impl From<Vec<i32>> for Values {
+    fn from(v: Vec<i32>) -> Values {
+        Values::Int(v)
+    }
+}
+
+impl From<f32> for Values {
+    fn from(n: f32) -> Values {
+        Values::Float(vec![n])
+    }
+}
+
+impl From<Vec<f32>> for Values {
+    fn from(v: Vec<f32>) -> Values {
+        Values::Float(v)
+    }
+}
+
+impl From<f64> for Values {
+    fn from(n: f64) -> Values {
+        Values::Double(vec![n])
+    }
+}
+
+impl From<Vec<f64>> for Values {
+    fn from(v: Vec<f64>) -> Values {
+        Values::Double(v)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(
+        &[(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8],
+    );
+}
+
+fn put_list_header(buf: &mut Vec<u8>, tag: u32, len: usize) {
+    if len == 0 {
+        put_u32(buf, 0);
+        put_u32(buf, 0);
+    } else {
+        put_u32(buf, tag);
+        put_u32(buf, len as u32);
+    }
+}
+
+fn put_name(buf: &mut Vec<u8>, name: &str) {
+    Values::Char(name.to_string()).put_with_len(buf);
+}
+
+fn put_attributes(buf: &mut Vec<u8>, attributes: &[Attribute]) {
+    put_list_header(buf, NC_ATTRIBUTE, attributes.len());
+    for attribute in attributes {
+        put_name(buf, &attribute.name);
+        put_u32(buf, attribute.values.nc_type());
+        attribute.values.put_with_len(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let mut file = File::new();
+        let time = file.add_dimension("time", 2);
+        file.add_attribute("Conventions", "CF-1.6");
+        file.add_variable("time", &[time], vec![0., 3600.])
+            .attribute("units", "seconds since 1970-01-01 00:00:00");
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+        assert_eq!(b"CDF\x01\0\0\0\0", &bytes[0..8]);
+        assert_eq!(&[0, 0, 0, 0x0A, 0, 0, 0, 1, 0, 0, 0, 4], &bytes[8..20]);
+        assert_eq!(b"time\0\0\0\x02", &bytes[20..28]);
+        assert_eq!(
+            &[0x40, 0xAC, 0x20, 0, 0, 0, 0, 0],
+            &bytes[bytes.len() - 8..]
+        );
+        let begin = bytes.len() - 16;
+        let position = bytes.len() - 20;
+        assert_eq!(
+            &[(begin >> 24) as u8, (begin >> 16) as u8, (begin >> 8) as u8, begin as u8],
+            &bytes[position..position + 4]
+        );
+    }
+
+    #[test]
+    fn padding() {
+        let mut file = File::new();
+        let n = file.add_dimension("n", 3);
+        file.add_variable("flags", &[n], vec![1i8, 0, 1]);
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+        assert_eq!(&[1, 0, 1, 0], &bytes[bytes.len() - 4..]);
+    }
+
+    #[test]
+    fn wrong_number_of_values() {
+        let mut file = File::new();
+        let n = file.add_dimension("n", 3);
+        file.add_variable("values", &[n], vec![1., 2.]);
+        assert!(file.write(&mut Vec::new()).is_err());
+    }
+}
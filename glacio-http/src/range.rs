@@ -0,0 +1,83 @@
+//! Datetime range support for Iron requests.
+//!
+//! Requests that return time-stamped data can be limited to a range of datetimes with the `start`
+//! and `end` parameters, both RFC 3339 datetimes. Both ends of the range are inclusive and
+//! optional:
+//!
+//! ```bash
+//! curl http://localhost:3000/atlas/heartbeats.csv?start=2017-08-01T00:00:00Z
+//! ```
+
+use Result;
+use chrono::{DateTime, Utc};
+use iron::{Plugin, Request};
+use params::{Params, Value};
+
+/// An inclusive range of datetimes, open on either end.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Range {
+    /// The start of the range.
+    pub start: Option<DateTime<Utc>>,
+    /// The end of the range.
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl Range {
+    /// Parses a range from optional start and end strings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::Range;
+    /// let range = Range::parse(Some("2017-08-01T00:00:00Z"), None).unwrap();
+    /// assert!(range.start.is_some());
+    /// assert!(range.end.is_none());
+    /// ```
+    pub fn parse(start: Option<&str>, end: Option<&str>) -> Result<Range> {
+        Ok(Range {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+
+    /// Parses a range from an Iron request's `start` and `end` parameters.
+    pub fn from_request(request: &mut Request) -> Result<Range> {
+        let map = request.get::<Params>().unwrap();
+        let start = match map.find(&["start"]) {
+            Some(&Value::String(ref start)) => Some(start.as_str()),
+            _ => None,
+        };
+        let end = match map.find(&["end"]) {
+            Some(&Value::String(ref end)) => Some(end.as_str()),
+            _ => None,
+        };
+        Range::parse(start, end)
+    }
+
+    /// Returns true if this range contains the datetime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio_http;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio_http::Range;
+    /// # fn main() {
+    /// let range = Range::parse(Some("2017-08-01T00:00:00Z"), None).unwrap();
+    /// assert!(range.contains(&Utc.ymd(2017, 8, 1).and_hms(0, 0, 0)));
+    /// assert!(!range.contains(&Utc.ymd(2017, 7, 31).and_hms(0, 0, 0)));
+    /// # }
+    /// ```
+    pub fn contains(&self, datetime: &DateTime<Utc>) -> bool {
+        self.start.map_or(true, |start| start <= *datetime) &&
+            self.end.map_or(true, |end| *datetime <= end)
+    }
+}
+
+fn parse(s: Option<&str>) -> Result<Option<DateTime<Utc>>> {
+    match s {
+        Some(s) => Ok(Some(s.parse()?)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,181 @@
+use {Error, Result};
+use chrono::{Duration, NaiveDate};
+use glacio::Station;
+use glacio::cwms;
+use glacio::station::Location;
+use glacio::weather::{DatSource, Observation};
+
+/// Global configuration for our weather stations.
+#[derive(Default, Clone, Deserialize, Debug)]
+pub struct Config {
+    /// A vector of stations.
+    pub stations: Vec<StationConfig>,
+}
+
+/// Configuration for a single weather station.
+#[derive(Default, Clone, Deserialize, Debug)]
+pub struct StationConfig {
+    /// The name of the station.
+    pub name: String,
+    /// A multi-sentence description for the station.
+    pub description: String,
+    /// The local directory (or file) that holds the station's data files.
+    pub path: String,
+    /// The expected hourly interval between observations.
+    pub interval: f32,
+    /// The data logger tables to read observations from, e.g. `["Hourly"]`.
+    ///
+    /// If empty, observations are read from every table. Otherwise the `CWMS` table, which holds
+    /// the observations imported from the CWMS server, is always read as well.
+    #[serde(default)]
+    pub tables: Vec<String>,
+    /// The station's location on the CWMS server, if it isn't the station's name.
+    pub cwms_location: Option<String>,
+    /// Where the station is.
+    #[serde(default)]
+    pub site: Option<Site>,
+}
+
+/// Where a station is, and when it was installed.
+///
+/// Several stations can share a site (e.g. the two cameras of a dual-camera system), so sites have
+/// their own names. In a TOML file, the installation date must be quoted, e.g.
+/// `installed = "2017-07-15"`.
+#[derive(Default, Clone, Deserialize, Debug)]
+pub struct Site {
+    /// The name of the site.
+    pub name: String,
+    /// Latitude, in decimal degrees.
+    pub latitude: f64,
+    /// Longitude, in decimal degrees.
+    pub longitude: f64,
+    /// Elevation, in meters above sea level.
+    #[serde(default)]
+    pub elevation: Option<f64>,
+    /// The date that the station was installed at this site.
+    #[serde(default)]
+    pub installed: Option<NaiveDate>,
+}
+
+impl StationConfig {
+    /// Returns the glacio data source for this configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::stations::StationConfig;
+    /// let config = StationConfig { path: ".".to_string(), ..Default::default() };
+    /// let source = config.to_source();
+    /// ```
+    pub fn to_source(&self) -> DatSource {
+        let mut tables = self.tables.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        if !tables.is_empty() && !tables.contains(&cwms::TABLE) {
+            tables.push(cwms::TABLE);
+        }
+        DatSource::new(&self.path).tables(&tables)
+    }
+
+    /// Returns the station's location on the CWMS server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::stations::StationConfig;
+    /// let config = StationConfig { name: "HEL_WX".to_string(), ..Default::default() };
+    /// assert_eq!("HEL_WX", config.cwms_location());
+    /// ```
+    pub fn cwms_location(&self) -> &str {
+        self.cwms_location.as_ref().unwrap_or(&self.name)
+    }
+}
+
+impl Site {
+    /// Returns this site's location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio_http::stations::Site;
+    /// let site = Site { latitude: 66.4, longitude: -38.2, ..Default::default() };
+    /// assert_eq!(66.4, site.location().latitude);
+    /// ```
+    pub fn location(&self) -> Location {
+        Location {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            elevation: self.elevation,
+        }
+    }
+}
+
+impl Station for StationConfig {
+    type Observation = Observation;
+    type Error = Error;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn location(&self) -> Option<Location> {
+        self.site.as_ref().map(|site| site.location())
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::seconds((self.interval * 3600.) as i64)
+    }
+
+    fn observations<'a>(&'a self) -> Result<Box<Iterator<Item = Observation> + 'a>> {
+        let observations = self.to_source().iter()?;
+        Ok(Box::new(observations.filter_map(|result| result.ok())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use toml;
+
+    #[test]
+    fn site() {
+        let config: StationConfig = toml::from_str(
+            r#"
+            name = "HEL_WX"
+            description = "Weather station"
+            path = "/var/weather/HEL_WX"
+            interval = 1.0
+
+            [site]
+            name = "Helheim weather"
+            latitude = 66.4
+            longitude = -38.3
+            installed = "2017-07-15"
+            "#,
+        ).unwrap();
+        let site = config.site.unwrap();
+        assert_eq!("Helheim weather", site.name);
+        assert_eq!(None, site.elevation);
+        assert_eq!(Some(NaiveDate::from_ymd(2017, 7, 15)), site.installed);
+    }
+
+    #[test]
+    fn cwms_table() {
+        use glacio::cwms::Importer;
+        use std::env;
+        use std::fs;
+
+        let path = env::temp_dir().join("glacio-stations-cwms-table");
+        let _ = fs::remove_dir_all(&path);
+        let mut importer = Importer::new();
+        importer.station("HEL_WX", "HEL_WX", &path);
+        importer.import("../glacio/data/cwms/HEL_WX.csv").unwrap();
+        let config = StationConfig {
+            name: "HEL_WX".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            tables: vec!["Hourly".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(3, config.to_source().iter().unwrap().count());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use stations::station::Summary;
+
+/// The GeoJSON object types that we use.
+const FEATURE_COLLECTION: &'static str = "FeatureCollection";
+const FEATURE: &'static str = "Feature";
+const POINT: &'static str = "Point";
+
+/// A serializable GeoJSON feature collection of stations.
+#[derive(Serialize, Debug)]
+pub struct FeatureCollection {
+    /// The GeoJSON object type, always `FeatureCollection`.
+    #[serde(rename = "type")]
+    pub object_type: &'static str,
+    /// The stations, as point features.
+    pub features: Vec<Feature>,
+}
+
+/// A serializable GeoJSON feature for a station.
+#[derive(Serialize, Debug)]
+pub struct Feature {
+    /// The GeoJSON object type, always `Feature`.
+    #[serde(rename = "type")]
+    pub object_type: &'static str,
+    /// The station's location.
+    pub geometry: Point,
+    /// The station's summary, including its latest status.
+    pub properties: Summary,
+}
+
+/// A serializable GeoJSON point.
+#[derive(Serialize, Debug)]
+pub struct Point {
+    /// The GeoJSON object type, always `Point`.
+    #[serde(rename = "type")]
+    pub object_type: &'static str,
+    /// The longitude, latitude, and (if we know it) elevation.
+    pub coordinates: Vec<f64>,
+}
+
+impl FeatureCollection {
+    /// Creates a new feature collection from station summaries.
+    ///
+    /// Stations without a location are left out.
+    pub fn new(summaries: Vec<Summary>) -> FeatureCollection {
+        FeatureCollection {
+            object_type: FEATURE_COLLECTION,
+            features: summaries
+                .into_iter()
+                .filter_map(|summary| {
+                    summary.location.map(|location| {
+                        let mut coordinates = vec![location.longitude, location.latitude];
+                        if let Some(elevation) = location.elevation {
+                            coordinates.push(elevation);
+                        }
+                        Feature {
+                            object_type: FEATURE,
+                            geometry: Point {
+                                object_type: POINT,
+                                coordinates: coordinates,
+                            },
+                            properties: summary,
+                        }
+                    })
+                })
+                .collect(),
+        }
+    }
+}
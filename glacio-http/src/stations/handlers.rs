@@ -0,0 +1,286 @@
+//! Iron handlers for stations of every kind.
+
+use {Paginate, Range, atlas};
+use cameras::handlers::Cameras;
+use chrono::Utc;
+use glacio::Station;
+use iron::{IronResult, Request, Response, status};
+use json;
+use router::Router;
+use stations::{Config, StationConfig};
+use stations::geojson::FeatureCollection;
+use stations::station::{Kind, Overview, Summary};
+
+/// A multi-route handler for station requests.
+///
+/// The station listing and status overview cover the ATLAS system and the cameras as well as the
+/// weather stations, so this handler holds all of their configurations. The cameras' observations
+/// come from the `Cameras` handler's shared indexes. Just like the `Cameras` multi-route handler,
+/// this structure does not implement `Handler` itself. Rather, its methods are passed via closures
+/// into the router.
+#[derive(Clone, Debug)]
+pub struct Stations {
+    config: Config,
+    atlas: atlas::Config,
+    cameras: Cameras,
+}
+
+impl Stations {
+    /// Creates a new handler for the weather stations, the ATLAS system, and the cameras.
+    pub fn new(config: Config, atlas: atlas::Config, cameras: Cameras) -> Stations {
+        Stations {
+            config: config,
+            atlas: atlas,
+            cameras: cameras,
+        }
+    }
+
+    /// Returns a list of all stations, of every kind.
+    pub fn summary(&self, request: &mut Request) -> IronResult<Response> {
+        json::response(self.summaries(request))
+    }
+
+    /// Returns an overview of the health of all stations.
+    pub fn status(&self, request: &mut Request) -> IronResult<Response> {
+        let now = Utc::now();
+        let summaries = self.summaries(request);
+        json::response(Overview::new(&summaries, now))
+    }
+
+    /// Returns a GeoJSON feature collection of all stations whose sites we know.
+    pub fn geojson(&self, request: &mut Request) -> IronResult<Response> {
+        use iron::headers::ContentType;
+
+        json::response(FeatureCollection::new(self.summaries(request)))
+            .map(|mut response| {
+                response.headers.set(ContentType(
+                    "application/geo+json".parse().unwrap(),
+                ));
+                response
+            })
+    }
+
+    /// Returns a (paginated) list of the asked-for weather station's observations, starting with
+    /// the most recent, optionally limited to a range of datetimes.
+    pub fn observations(&self, request: &mut Request) -> IronResult<Response> {
+        let station_config = iexpect!(self.station_config(request), status::NotFound);
+        let range = itry!(Range::from_request(request), status::BadRequest);
+        let mut observations = itry!(station_config.observations())
+            .filter(|observation| range.contains(&observation.datetime))
+            .collect::<Vec<_>>();
+        observations.sort_by(|a, b| b.datetime.cmp(&a.datetime));
+        observations.dedup_by_key(|observation| observation.datetime);
+        let observations = itry!(observations.into_iter().paginate(request)).collect::<Vec<_>>();
+        json::response(observations)
+    }
+
+    fn summaries(&self, request: &mut Request) -> Vec<Summary> {
+        let now = Utc::now();
+        let mut summaries = vec![
+            Summary::new(
+                &self.atlas,
+                self.atlas.site.as_ref(),
+                Kind::Atlas,
+                url_for!(request, "atlas-status").as_ref().to_string(),
+                now
+            ),
+        ];
+        for camera in self.cameras.stations() {
+            let url = url_for!(request, "camera", "name" => camera.name().to_string());
+            summaries.push(Summary::new(
+                &camera,
+                camera.config().site.as_ref(),
+                Kind::Camera,
+                url.as_ref().to_string(),
+                now,
+            ));
+        }
+        for station in &self.config.stations {
+            let url = url_for!(request, "station-observations", "name" => station.name.clone());
+            summaries.push(Summary::new(
+                station,
+                station.site.as_ref(),
+                Kind::Weather,
+                url.as_ref().to_string(),
+                now,
+            ));
+        }
+        summaries
+    }
+
+    fn station_config(&self, request: &mut Request) -> Option<&StationConfig> {
+        let name = request
+            .extensions
+            .get::<Router>()
+            .unwrap()
+            .find("name")
+            .map(|s| s.to_string());
+        name.and_then(|name| {
+            self.config.stations.iter().find(
+                |config| config.name == name,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Api, Config};
+    use cameras::CameraConfig;
+    use chrono::NaiveDate;
+    use iron::Headers;
+    use iron::headers::ContentType;
+    use iron::status::Status;
+    use iron_test::{request, response};
+    use serde_json::{self, Value};
+    use stations::{Site, StationConfig};
+
+    fn build_api() -> Api {
+        let mut config = Config::new();
+        config.atlas.path = "../glacio/data".to_string();
+        config.cameras.cameras.push(CameraConfig {
+            name: "ATLAS_CAM".to_string(),
+            path: "../glacio/data/ATLAS_CAM".to_string(),
+            interval: 3.,
+            site: Some(Site {
+                name: "ATLAS".to_string(),
+                latitude: 66.35,
+                longitude: -38.2,
+                elevation: Some(495.),
+                installed: Some(NaiveDate::from_ymd(2015, 7, 1)),
+            }),
+            ..Default::default()
+        });
+        config.cameras.cameras.push(CameraConfig {
+            name: "NOTACAM".to_string(),
+            path: "../glacio/data/NOTACAM".to_string(),
+            interval: 3.,
+            ..Default::default()
+        });
+        config.stations.stations.push(StationConfig {
+            name: "HEL_WX".to_string(),
+            description: "Weather station".to_string(),
+            path: "../glacio/data/HEL_WX".to_string(),
+            interval: 1.,
+            tables: vec!["Hourly".to_string()],
+            site: Some(Site {
+                name: "Helheim weather".to_string(),
+                latitude: 66.4,
+                longitude: -38.3,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        Api::new(config).unwrap()
+    }
+
+    fn get(url: &str) -> Value {
+        let response = request::get(url, Headers::new(), &build_api()).unwrap();
+        serde_json::from_str(&response::extract_body_to_string(response)).unwrap()
+    }
+
+    #[test]
+    fn stations() {
+        let json = get("http://localhost:3000/stations");
+        let stations = json.as_array().unwrap();
+        assert_eq!(4, stations.len());
+
+        let atlas = &stations[0];
+        assert_eq!("ATLAS", atlas["name"]);
+        assert_eq!("atlas", atlas["kind"]);
+        assert_eq!(1.0, atlas["interval"]);
+        assert_eq!("2017-08-25T15:01:06Z", atlas["last_report"]);
+        assert_eq!("silent", atlas["health"]);
+        assert_eq!("http://localhost:3000/atlas/status", atlas["url"]);
+
+        let camera = &stations[1];
+        assert_eq!("ATLAS_CAM", camera["name"]);
+        assert_eq!("camera", camera["kind"]);
+        assert_eq!(3.0, camera["interval"]);
+        assert_eq!("2017-08-06T15:25:00Z", camera["last_report"]);
+        assert_eq!("ATLAS", camera["site"]);
+        assert_eq!("2015-07-01", camera["installed"]);
+        assert_eq!(66.35, camera["location"]["latitude"]);
+        assert_eq!(Value::Null, atlas["site"]);
+        assert_eq!("http://localhost:3000/cameras/ATLAS_CAM", camera["url"]);
+        assert_eq!(Value::Null, stations[2]["last_report"]);
+        assert_eq!("unknown", stations[2]["health"]);
+
+        let station = &stations[3];
+        assert_eq!("HEL_WX", station["name"]);
+        assert_eq!("weather", station["kind"]);
+        assert_eq!(-38.3, station["location"]["longitude"]);
+        assert_eq!(Value::Null, station["location"]["elevation"]);
+        assert_eq!("2017-08-25T15:00:00Z", station["last_report"]);
+        assert_eq!(
+            "http://localhost:3000/stations/HEL_WX/observations",
+            station["url"]
+        );
+    }
+
+    #[test]
+    fn geojson() {
+        let response = request::get(
+            "http://localhost:3000/sites.geojson",
+            Headers::new(),
+            &build_api(),
+        ).unwrap();
+        assert_eq!(
+            "application/geo+json",
+            response.headers.get::<ContentType>().unwrap().to_string()
+        );
+        let json: Value = serde_json::from_str(&response::extract_body_to_string(response))
+            .unwrap();
+        assert_eq!("FeatureCollection", json["type"]);
+        let features = json["features"].as_array().unwrap();
+        assert_eq!(2, features.len());
+        let camera = &features[0];
+        assert_eq!("Feature", camera["type"]);
+        assert_eq!("Point", camera["geometry"]["type"]);
+        assert_eq!(json!([-38.2, 66.35, 495.]), camera["geometry"]["coordinates"]);
+        assert_eq!("ATLAS_CAM", camera["properties"]["name"]);
+        assert_eq!("ATLAS", camera["properties"]["site"]);
+        assert_eq!("silent", camera["properties"]["health"]);
+        assert_eq!(json!([-38.3, 66.4]), features[1]["geometry"]["coordinates"]);
+    }
+
+    #[test]
+    fn status() {
+        let status = get("http://localhost:3000/status");
+        assert_eq!("unknown", status["health"]);
+        assert_eq!(json!(["ATLAS", "ATLAS_CAM", "HEL_WX"]), status["silent"]);
+        assert_eq!(json!(["NOTACAM"]), status["unknown"]);
+        assert_eq!(json!([]), status["ok"]);
+    }
+
+    #[test]
+    fn observations() {
+        let observations = get("http://localhost:3000/stations/HEL_WX/observations");
+        assert_eq!(6, observations.as_array().unwrap().len());
+        let observation = observations.get(0).unwrap();
+        assert_eq!("2017-08-25T15:00:00Z", observation["datetime"]);
+        assert_eq!(5.97, observation["air_temperature"]);
+        assert_eq!(Value::Null, observations[2]["wind_speed"]);
+
+        let observations = get(
+            "http://localhost:3000/stations/HEL_WX/observations?\
+             start=2017-08-25T11:00:00Z&end=2017-08-25T12:00:00Z",
+        );
+        assert_eq!(2, observations.as_array().unwrap().len());
+        assert_eq!("2017-08-25T12:00:00Z", observations[0]["datetime"]);
+
+        let observations =
+            get("http://localhost:3000/stations/HEL_WX/observations?per_page=4&page=2");
+        assert_eq!(2, observations.as_array().unwrap().len());
+    }
+
+    #[test]
+    fn missing_station() {
+        let response = request::get(
+            "http://localhost:3000/stations/NOTASTATION/observations",
+            Headers::new(),
+            &build_api(),
+        ).unwrap();
+        assert_eq!(Some(Status::NotFound), response.status);
+    }
+}
@@ -0,0 +1,14 @@
+//! Our stations, of every kind.
+//!
+//! The configuration here is for our weather stations, each of which is one directory of TOA5 data
+//! files, read with `glacio::weather::DatSource`. The station listing and status overview cover
+//! everything that implements `glacio::Station`: the ATLAS system, the cameras, and the weather
+//! stations.
+
+pub mod handlers;
+
+mod config;
+mod geojson;
+mod station;
+
+pub use self::config::{Config, Site, StationConfig};
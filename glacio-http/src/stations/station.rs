@@ -0,0 +1,108 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use glacio::Station;
+use glacio::station::{Health, Location};
+use stations::Site;
+
+/// The kinds of stations that we maintain.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// The ATLAS system.
+    Atlas,
+    /// A remote camera.
+    Camera,
+    /// A weather station.
+    Weather,
+}
+
+/// A serializable summary of a station, of any kind.
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    /// The name of the station.
+    pub name: String,
+    /// The kind of station.
+    pub kind: Kind,
+    /// The name of the station's site, if we know it.
+    pub site: Option<String>,
+    /// The station's location, if we know it.
+    pub location: Option<Location>,
+    /// The date that the station was installed at its site, if we know it.
+    pub installed: Option<NaiveDate>,
+    /// The hourly interval that this station reports.
+    pub interval: f32,
+    /// The date and time of the station's most recent report.
+    pub last_report: Option<DateTime<Utc>>,
+    /// The station's health.
+    pub health: Health,
+    /// The url for more information about this station.
+    pub url: String,
+}
+
+/// A serializable overview of the health of all stations.
+#[derive(Serialize, Debug)]
+pub struct Overview {
+    /// The date and time of this overview.
+    pub datetime: DateTime<Utc>,
+    /// The worst health of any station.
+    pub health: Health,
+    /// The names of the stations that are ok.
+    pub ok: Vec<String>,
+    /// The names of the stations that are late.
+    pub late: Vec<String>,
+    /// The names of the stations that are silent.
+    pub silent: Vec<String>,
+    /// The names of the stations whose health is unknown.
+    pub unknown: Vec<String>,
+}
+
+impl Summary {
+    /// Creates a new summary of a station at a site, as of `now`.
+    pub fn new<S: Station>(
+        station: &S,
+        site: Option<&Site>,
+        kind: Kind,
+        url: String,
+        now: DateTime<Utc>,
+    ) -> Summary {
+        let last_report = station.last_report().ok().and_then(|last_report| last_report);
+        Summary {
+            name: station.name().to_string(),
+            kind: kind,
+            site: site.map(|site| site.name.clone()),
+            location: station.location(),
+            installed: site.and_then(|site| site.installed),
+            interval: station.interval().num_seconds() as f32 / 3600.,
+            last_report: last_report,
+            health: Health::new(last_report, station.interval(), now),
+            url: url,
+        }
+    }
+}
+
+impl Overview {
+    /// Creates a new overview from station summaries that were made as of `now`.
+    ///
+    /// If there aren't any stations, the overall health is ok.
+    pub fn new(summaries: &[Summary], now: DateTime<Utc>) -> Overview {
+        let mut overview = Overview {
+            datetime: now,
+            health: summaries.iter().map(|summary| summary.health).max().unwrap_or(
+                Health::Ok,
+            ),
+            ok: Vec::new(),
+            late: Vec::new(),
+            silent: Vec::new(),
+            unknown: Vec::new(),
+        };
+        for summary in summaries {
+            let names = match summary.health {
+                Health::Ok => &mut overview.ok,
+                Health::Late => &mut overview.late,
+                Health::Silent => &mut overview.silent,
+                Health::Unknown => &mut overview.unknown,
+            };
+            names.push(summary.name.clone());
+        }
+        overview
+    }
+}
@@ -0,0 +1,36 @@
+From: sbdservice@sbd.iridium.com
+To: atlas@lidar.io
+Date: Tue, 1 Aug 2017 00:01:01 +0000
+Subject: SBD Msg From Unit: 300234063556840
+MIME-Version: 1.0
+Content-Type: multipart/mixed;
+	boundary="SBD.Boundary.605592468"
+
+This is a multi-part message in MIME format.
+
+--SBD.Boundary.605592468
+Content-Type: text/plain; charset="us-ascii"
+Content-Transfer-Encoding: 7bit
+
+MOMSN: 2497
+MTMSN: 0
+Time of Session (UTC): Tue Aug  1 00:00:55 2017
+Session Status: 00 - Transfer OK
+Message Size (bytes): 340
+
+Unit Location: Lat = 66.38347 Long = -38.15153
+CEPradius = 3
+
+--SBD.Boundary.605592468
+Content-Type: application/x-zip-compressed; name="300234063556840_002497.sbd"
+Content-Transfer-Encoding: base64
+Content-Disposition: attachment; filename="300234063556840_002497.sbd"
+
+MSwxNSwwLDM1NDpBVEhCMDMzNTQNCjA3LzMxLzE3IDE4OjAxOjQ0LDIzLjUsMjIuNTAwLDczMzAz
+ODMyNS43Niw5NDMxMzk1NTMuMjgNCi0yLjA2OCw5NjIuMTIwLDQzLjA4OQ0KMDcvMzEvMTcgMTg6
+MDE6NTINCjA3LzMxLzE3IDE4OjQwOjU2LDE5NTEyNjE3LC00MC41OTIsNTE2My41MzcsMjc1ODQ0
+LjYzNiwxLDM3LC0wLjM0MCwtMC4xOTgNCjA3LzE3LzE3IDE3OjQ0OjQ3LDQsU2NoZWR1bGVyIG5v
+dCBlbmFibGVkDQoxMi41LDk0LjIwOCw5NC45NDcNCmF1dG8gb2ZmLGNhcnRyaWRnZSAxLjEgY29u
+c3VtZWQgMy43NDFsLDI2LjYzLC0wLjAzDQphdXRvIG9mZixjYXJ0cmlkZ2UgMS4xIGNvbnN1bQ==
+
+--SBD.Boundary.605592468--
@@ -0,0 +1,519 @@
+//! Rule-based alerting on the ATLAS heartbeat stream.
+//!
+//! Nobody looks at the status page unless they have a reason to. Alert rules describe the
+//! conditions that should give someone a reason, e.g. a battery state of charge that's dropping too
+//! low, or a heartbeat that's overdue. A `Monitor` evaluates a set of rules against a stream of
+//! heartbeats and reports when alerts start firing and when they resolve.
+//!
+//! Thresholded rules support hysteresis: once an alert is firing, the measured value has to clear
+//! the threshold by the hysteresis margin before the alert is resolved. This keeps a battery
+//! hovering right around its threshold from flapping back and forth. Alerts are de-duplicated, so
+//! a condition that holds over many heartbeats produces one firing event and one resolved event.
+//!
+//! # Examples
+//!
+//! ```
+//! use glacio::atlas::alert::{Kind, Monitor, Rule};
+//! let rule = Rule {
+//!     name: "low battery".to_string(),
+//!     kind: Kind::StateOfCharge,
+//!     threshold: 50.,
+//!     hysteresis: 5.,
+//! };
+//! let monitor = Monitor::new(vec![rule]);
+//! assert_eq!(0, monitor.firing().count());
+//! ```
+
+use atlas::{Efoy, Heartbeat, Result};
+use atlas::efoy::State;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use std::collections::btree_map::Values;
+
+/// A rule that can trigger an alert.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Rule {
+    /// The name of this rule, which must be unique among a monitor's rules.
+    pub name: String,
+    /// The kind of condition that this rule checks.
+    pub kind: Kind,
+    /// The threshold value for this rule.
+    ///
+    /// The units depend on the kind of rule, see `Kind` for details. Ignored for on/off rules.
+    #[serde(default)]
+    pub threshold: f64,
+    /// How far the value must clear the threshold before a firing alert is resolved.
+    #[serde(default)]
+    pub hysteresis: f64,
+}
+
+/// The kinds of conditions that a rule can check.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// A battery's state of charge, as a percentage, is below the threshold.
+    StateOfCharge,
+    /// An EFOY is in its error state.
+    EfoyError,
+    /// The Riegl switch is off.
+    RieglSwitchOff,
+    /// The last heartbeat is more than the threshold number of hours old.
+    HeartbeatOverdue,
+    /// The scanner's internal or external memory, in kB, is below the threshold.
+    ScannerMemory,
+    /// An EFOY's total fuel, as a percentage of its capacity, is below the threshold.
+    EfoyFuel,
+}
+
+/// An alert raised by a rule.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Alert {
+    /// The name of the rule that raised this alert.
+    pub rule: String,
+    /// The kind of the rule that raised this alert.
+    pub kind: Kind,
+    /// The battery or EFOY id that this alert is about, if any.
+    pub subject: Option<u8>,
+    /// The value that triggered the alert.
+    pub value: f64,
+    /// The date and time that the alert started firing.
+    pub datetime: DateTime<Utc>,
+}
+
+/// A change in an alert's status.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    /// Is the alert firing or resolved?
+    pub status: Status,
+    /// The date and time of the status change.
+    pub datetime: DateTime<Utc>,
+    /// The alert itself.
+    pub alert: Alert,
+}
+
+/// The status of an alert.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The alert's condition holds.
+    Firing,
+    /// The alert's condition no longer holds.
+    Resolved,
+}
+
+/// Evaluates alert rules against a stream of heartbeats.
+#[derive(Debug)]
+pub struct Monitor {
+    rules: Vec<Rule>,
+    efoy: Efoy,
+    efoys: BTreeMap<u8, Efoy>,
+    firing: BTreeMap<(String, Option<u8>), Alert>,
+    last_heartbeat: Option<DateTime<Utc>>,
+}
+
+/// An iterator over a monitor's firing alerts.
+#[derive(Debug)]
+pub struct Firing<'a> {
+    iter: Values<'a, (String, Option<u8>), Alert>,
+}
+
+impl Kind {
+    fn is_below(&self) -> bool {
+        match *self {
+            Kind::StateOfCharge | Kind::ScannerMemory | Kind::EfoyFuel => true,
+            Kind::EfoyError | Kind::RieglSwitchOff | Kind::HeartbeatOverdue => false,
+        }
+    }
+}
+
+impl Rule {
+    fn is_firing(&self, value: f64, is_firing: bool) -> bool {
+        match (self.kind, self.kind.is_below(), is_firing) {
+            (Kind::EfoyError, _, _) |
+            (Kind::RieglSwitchOff, _, _) => value > 0.5,
+            (_, true, false) => value < self.threshold,
+            (_, true, true) => value < self.threshold + self.hysteresis,
+            (_, false, false) => value > self.threshold,
+            (_, false, true) => value > self.threshold - self.hysteresis,
+        }
+    }
+}
+
+impl Event {
+    /// Returns an identifier for this event that is stable across monitor runs.
+    ///
+    /// Used to keep track of which events have already been handled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::atlas::alert::{Alert, Event, Kind, Status};
+    /// # fn main() {
+    /// let datetime = Utc.ymd(2017, 8, 1).and_hms(0, 0, 55);
+    /// let event = Event {
+    ///     status: Status::Firing,
+    ///     datetime: datetime,
+    ///     alert: Alert {
+    ///         rule: "low battery".to_string(),
+    ///         kind: Kind::StateOfCharge,
+    ///         subject: Some(1),
+    ///         value: 42.,
+    ///         datetime: datetime,
+    ///     },
+    /// };
+    /// assert_eq!("low battery/1/2017-08-01T00:00:55+00:00/firing", event.id());
+    /// # }
+    /// ```
+    pub fn id(&self) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.alert.rule,
+            self.alert.subject.map(|id| id.to_string()).unwrap_or_default(),
+            self.alert.datetime.to_rfc3339(),
+            match self.status {
+                Status::Firing => "firing",
+                Status::Resolved => "resolved",
+            }
+        )
+    }
+}
+
+impl Monitor {
+    /// Creates a new monitor for the provided rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::alert::Monitor;
+    /// let monitor = Monitor::new(Vec::new());
+    /// ```
+    pub fn new(rules: Vec<Rule>) -> Monitor {
+        Monitor {
+            rules: rules,
+            efoy: Efoy::new(),
+            efoys: BTreeMap::new(),
+            firing: BTreeMap::new(),
+            last_heartbeat: None,
+        }
+    }
+
+    /// Sets the efoy template that is used to track fuel levels.
+    ///
+    /// Each EFOY in the heartbeats gets its own copy of this efoy. Without cartridges, fuel rules
+    /// never fire.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::Efoy;
+    /// # use glacio::atlas::alert::Monitor;
+    /// let mut efoy = Efoy::new();
+    /// efoy.add_cartridge("1.1", 8.0).unwrap();
+    /// let monitor = Monitor::new(Vec::new()).efoy(efoy);
+    /// ```
+    pub fn efoy(mut self, efoy: Efoy) -> Monitor {
+        self.efoy = efoy;
+        self
+    }
+
+    /// Processes a heartbeat, returning any alert status changes.
+    ///
+    /// Heartbeats should be processed in order. If the gap since the previous heartbeat was long
+    /// enough to make a heartbeat overdue, the overdue alert fires and resolves within this call.
+    pub fn process(&mut self, heartbeat: &Heartbeat) -> Result<Vec<Event>> {
+        let mut events = self.check(heartbeat.datetime);
+        self.last_heartbeat = Some(heartbeat.datetime);
+        if self.efoy.iter().next().is_some() {
+            for (&id, efoy_heartbeat) in &heartbeat.efoys {
+                if !self.efoys.contains_key(&id) {
+                    let efoy = self.efoy.clone();
+                    self.efoys.insert(id, efoy);
+                }
+                self.efoys.get_mut(&id).unwrap().process(efoy_heartbeat)?;
+            }
+        }
+        for rule in self.rules.clone() {
+            for (subject, value) in self.values(&rule, heartbeat) {
+                if let Some(event) = self.update(&rule, subject, value, heartbeat.datetime) {
+                    events.push(event);
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Checks for an overdue heartbeat as of the provided date and time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::Utc;
+    /// # use glacio::atlas::alert::Monitor;
+    /// # fn main() {
+    /// let mut monitor = Monitor::new(Vec::new());
+    /// let events = monitor.check(Utc::now());
+    /// assert!(events.is_empty());
+    /// # }
+    /// ```
+    pub fn check(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let last_heartbeat = match self.last_heartbeat {
+            Some(last_heartbeat) => last_heartbeat,
+            None => return Vec::new(),
+        };
+        let hours = hours(now.signed_duration_since(last_heartbeat));
+        let mut events = Vec::new();
+        for rule in self.rules.clone() {
+            if rule.kind == Kind::HeartbeatOverdue {
+                let datetime = if self.firing.contains_key(&(rule.name.clone(), None)) {
+                    now
+                } else {
+                    last_heartbeat + Duration::seconds((rule.threshold * 3600.) as i64)
+                };
+                if let Some(event) = self.update(&rule, None, hours, datetime) {
+                    events.push(event);
+                }
+            }
+        }
+        events
+    }
+
+    /// Returns an iterator over the currently firing alerts.
+    pub fn firing(&self) -> Firing {
+        Firing { iter: self.firing.values() }
+    }
+
+    fn values(&self, rule: &Rule, heartbeat: &Heartbeat) -> Vec<(Option<u8>, f64)> {
+        match rule.kind {
+            Kind::StateOfCharge => {
+                heartbeat
+                    .batteries
+                    .iter()
+                    .map(|(&id, battery)| (Some(id), battery.state_of_charge as f64))
+                    .collect()
+            }
+            Kind::EfoyError => {
+                heartbeat
+                    .efoys
+                    .iter()
+                    .map(|(&id, efoy)| {
+                        (Some(id), if efoy.state == State::Error { 1. } else { 0. })
+                    })
+                    .collect()
+            }
+            Kind::RieglSwitchOff => {
+                vec![(None, if heartbeat.is_riegl_switch_on { 0. } else { 1. })]
+            }
+            Kind::HeartbeatOverdue => vec![(None, 0.)],
+            Kind::ScannerMemory => {
+                let scanner_power_on = &heartbeat.scanner_power_on;
+                vec![
+                    (
+                        None,
+                        scanner_power_on.memory_internal.min(
+                            scanner_power_on.memory_external,
+                        ),
+                    ),
+                ]
+            }
+            Kind::EfoyFuel => {
+                self.efoys
+                    .iter()
+                    .map(|(&id, efoy)| (Some(id), efoy.total_fuel_percentage() as f64))
+                    .filter(|&(_, value)| value.is_finite())
+                    .collect()
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        rule: &Rule,
+        subject: Option<u8>,
+        value: f64,
+        datetime: DateTime<Utc>,
+    ) -> Option<Event> {
+        let key = (rule.name.clone(), subject);
+        let was_firing = self.firing.contains_key(&key);
+        match (was_firing, rule.is_firing(value, was_firing)) {
+            (false, true) => {
+                let alert = Alert {
+                    rule: rule.name.clone(),
+                    kind: rule.kind,
+                    subject: subject,
+                    value: value,
+                    datetime: datetime,
+                };
+                self.firing.insert(key, alert.clone());
+                Some(Event {
+                    status: Status::Firing,
+                    datetime: datetime,
+                    alert: alert,
+                })
+            }
+            (true, false) => {
+                self.firing.remove(&key).map(|alert| {
+                    Event {
+                        status: Status::Resolved,
+                        datetime: datetime,
+                        alert: alert,
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Iterator for Firing<'a> {
+    type Item = &'a Alert;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+fn hours(duration: Duration) -> f64 {
+    duration.num_seconds() as f64 / 3600.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atlas::{battery, efoy};
+    use atlas::scanner::{ScanStop, ScannerPowerOn};
+    use chrono::TimeZone;
+
+    fn heartbeat(hour: u32, state_of_charge: f32) -> Heartbeat {
+        let datetime = Utc.ymd(2017, 8, 1).and_hms(hour, 0, 0);
+        let mut batteries = BTreeMap::new();
+        batteries.insert(1, battery::Heartbeat { state_of_charge: state_of_charge });
+        let mut efoys = BTreeMap::new();
+        efoys.insert(
+            1,
+            efoy::Heartbeat {
+                cartridge: "1.1".to_string(),
+                consumed: 4.,
+                ..Default::default()
+            },
+        );
+        Heartbeat {
+            version: 3,
+            datetime: datetime,
+            batteries: batteries,
+            scanner_power_on: ScannerPowerOn {
+                datetime: datetime,
+                voltage: 23.5,
+                temperature: 22.5,
+                memory_external: 733038325.76,
+                memory_internal: 943139553.28,
+            },
+            scan_start: datetime,
+            scan_stop: ScanStop {
+                datetime: datetime,
+                num_points: 0,
+                range_min: 0.,
+                range_max: 0.,
+                file_size: 0.,
+                amplitude_min: 0,
+                amplitude_max: 0,
+                roll: 0.,
+                pitch: 0.,
+            },
+            efoys: efoys,
+            is_riegl_switch_on: true,
+        }
+    }
+
+    fn rule(kind: Kind, threshold: f64, hysteresis: f64) -> Rule {
+        Rule {
+            name: "rule".to_string(),
+            kind: kind,
+            threshold: threshold,
+            hysteresis: hysteresis,
+        }
+    }
+
+    #[test]
+    fn state_of_charge_hysteresis() {
+        let mut monitor = Monitor::new(vec![rule(Kind::StateOfCharge, 50., 5.)]);
+        assert!(monitor.process(&heartbeat(0, 60.)).unwrap().is_empty());
+        let events = monitor.process(&heartbeat(1, 49.)).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Status::Firing, events[0].status);
+        assert_eq!(Some(1), events[0].alert.subject);
+        assert_eq!(1, monitor.firing().count());
+        assert!(monitor.process(&heartbeat(2, 48.)).unwrap().is_empty());
+        assert!(monitor.process(&heartbeat(3, 52.)).unwrap().is_empty());
+        let events = monitor.process(&heartbeat(4, 56.)).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Status::Resolved, events[0].status);
+        assert_eq!(Utc.ymd(2017, 8, 1).and_hms(4, 0, 0), events[0].datetime);
+        assert_eq!(0, monitor.firing().count());
+    }
+
+    #[test]
+    fn efoy_error() {
+        let mut monitor = Monitor::new(vec![rule(Kind::EfoyError, 0., 0.)]);
+        let mut heartbeat = heartbeat(0, 60.);
+        heartbeat.efoys.get_mut(&1).unwrap().state = State::Error;
+        let events = monitor.process(&heartbeat).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Status::Firing, events[0].status);
+    }
+
+    #[test]
+    fn riegl_switch_off() {
+        let mut monitor = Monitor::new(vec![rule(Kind::RieglSwitchOff, 0., 0.)]);
+        let mut heartbeat = heartbeat(0, 60.);
+        heartbeat.is_riegl_switch_on = false;
+        assert_eq!(1, monitor.process(&heartbeat).unwrap().len());
+        assert_eq!(None, monitor.firing().next().unwrap().subject);
+    }
+
+    #[test]
+    fn heartbeat_overdue() {
+        let mut monitor = Monitor::new(vec![rule(Kind::HeartbeatOverdue, 2., 0.)]);
+        assert!(monitor.process(&heartbeat(0, 60.)).unwrap().is_empty());
+        assert!(monitor.check(Utc.ymd(2017, 8, 1).and_hms(1, 0, 0)).is_empty());
+        let events = monitor.check(Utc.ymd(2017, 8, 1).and_hms(3, 0, 0));
+        assert_eq!(1, events.len());
+        assert_eq!(Utc.ymd(2017, 8, 1).and_hms(2, 0, 0), events[0].datetime);
+        assert!(monitor.check(Utc.ymd(2017, 8, 1).and_hms(4, 0, 0)).is_empty());
+        let events = monitor.process(&heartbeat(5, 60.)).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Status::Resolved, events[0].status);
+    }
+
+    #[test]
+    fn heartbeat_gap() {
+        let mut monitor = Monitor::new(vec![rule(Kind::HeartbeatOverdue, 2., 0.)]);
+        monitor.process(&heartbeat(0, 60.)).unwrap();
+        let events = monitor.process(&heartbeat(5, 60.)).unwrap();
+        assert_eq!(2, events.len());
+        assert_eq!(Status::Firing, events[0].status);
+        assert_eq!(Status::Resolved, events[1].status);
+    }
+
+    #[test]
+    fn scanner_memory() {
+        let mut monitor = Monitor::new(vec![rule(Kind::ScannerMemory, 800000000., 0.)]);
+        let events = monitor.process(&heartbeat(0, 60.)).unwrap();
+        assert_eq!(733038325.76, events[0].alert.value);
+    }
+
+    #[test]
+    fn efoy_fuel() {
+        let mut efoy = Efoy::new();
+        efoy.add_cartridge("1.1", 8.).unwrap();
+        let mut monitor = Monitor::new(vec![rule(Kind::EfoyFuel, 60., 0.)]);
+        assert!(monitor.process(&heartbeat(0, 60.)).unwrap().is_empty());
+        let mut monitor = Monitor::new(vec![rule(Kind::EfoyFuel, 60., 0.)]).efoy(efoy);
+        let events = monitor.process(&heartbeat(0, 60.)).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(50., events[0].alert.value);
+    }
+}
@@ -0,0 +1,98 @@
+//! Piece SBD messages back together into heartbeats.
+//!
+//! Heartbeats are usually too long for a single SBD message, so they arrive as Sutron extended
+//! messages split across several SBD files. The assembler holds on to the partial message until
+//! its last packet arrives, and keeps track of which files went into each heartbeat.
+
+use atlas::{Error, Heartbeat, Result};
+use chrono::{DateTime, Utc};
+use sbd::mo::Message;
+use std::fs;
+use std::io::ErrorKind;
+use std::mem;
+use std::path::{Path, PathBuf};
+use sutron;
+
+/// Assembles heartbeats from a stream of SBD messages from one IMEI, in time of session order.
+#[derive(Debug, Default)]
+pub struct Assembler {
+    message: sutron::Message,
+    paths: Vec<PathBuf>,
+    datetime: Option<DateTime<Utc>>,
+}
+
+/// The result of assembling a heartbeat, and the SBD files that went into it.
+#[derive(Debug)]
+pub struct Assembled {
+    /// The SBD files that make up this heartbeat.
+    pub paths: Vec<PathBuf>,
+    /// The heartbeat, or the error encountered while parsing it.
+    pub heartbeat: Result<Heartbeat>,
+}
+
+impl Assembler {
+    /// Adds an SBD message, returning the assembled heartbeat if this was its last message.
+    ///
+    /// Messages that can't be added to the current heartbeat end it, and are returned as an
+    /// error.
+    pub fn add(&mut self, path: PathBuf, sbd_message: &Message) -> Option<Assembled> {
+        if self.datetime.is_none() {
+            self.datetime = Some(sbd_message.time_of_session());
+        }
+        self.paths.push(path);
+        let payload = match sbd_message.payload_str() {
+            Ok(payload) => payload,
+            Err(_) => {
+                let payload = String::from_utf8_lossy(sbd_message.payload()).into_owned();
+                return Some(self.finish(Err(Error::HeartbeatFormat(payload))));
+            }
+        };
+        let message = mem::replace(&mut self.message, sutron::Message::new());
+        match message.add(payload) {
+            Ok(message) => {
+                if message.is_complete() {
+                    let heartbeat = Heartbeat::new(&String::from(message), self.datetime.unwrap());
+                    Some(self.finish(heartbeat))
+                } else {
+                    self.message = message;
+                    None
+                }
+            }
+            Err(err) => Some(self.finish(Err(err.into()))),
+        }
+    }
+
+    fn finish(&mut self, heartbeat: Result<Heartbeat>) -> Assembled {
+        self.message = sutron::Message::new();
+        self.datetime = None;
+        Assembled {
+            paths: self.paths.drain(..).collect(),
+            heartbeat: heartbeat,
+        }
+    }
+}
+
+/// Recursively finds all SBD files under `dir`.
+///
+/// A missing directory just has no SBD files.
+pub fn sbd_paths(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in read_dir {
+        let path = entry?.path();
+        if path.is_dir() {
+            sbd_paths(&path, paths)?;
+        } else if is_sbd(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if this path looks like an SBD file.
+pub fn is_sbd(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension == "sbd")
+}
@@ -1,11 +1,13 @@
 use atlas::{Error, Result, battery, efoy};
 use atlas::scanner::{ScanStop, ScannerPowerOn};
+use atlas::storage::{Filesystem, Storage};
+use atlas::watch::Watch;
 use chrono::{DateTime, Utc};
 use regex::Regex;
 use sbd::mo::Message;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::vec::IntoIter;
 
 lazy_static! {
@@ -27,7 +29,7 @@ lazy_static! {
 ///
 /// These heartbeats are transmitted via Iridium SBD. Because of the SBD message length
 /// restriction, heartbeats may come in one or more messages, and might have to be pieced together.
-#[derive(Clone, Debug, PartialOrd, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialOrd, Serialize)]
 pub struct Heartbeat {
     /// The version of heartbeat message.
     pub version: u8,
@@ -56,10 +58,11 @@ pub struct Heartbeat {
 
 /// Structure for retrieving ATLAS heartbeats from SBD messages.
 ///
-/// Configure the source to fetch heartbeats of one or more versions from a filesystem sbd storage.
+/// Configure the source to fetch heartbeats of one or more versions from a filesystem sbd storage,
+/// or from any other `Storage`.
 #[derive(Debug)]
 pub struct SbdSource {
-    path: PathBuf,
+    storage: Box<Storage>,
     imeis: Vec<String>,
     versions: Vec<u8>,
 }
@@ -89,7 +92,7 @@ impl Ord for Heartbeat {
 }
 
 impl Heartbeat {
-    fn new(message: &str, datetime: DateTime<Utc>) -> Result<Heartbeat> {
+    pub(crate) fn new(message: &str, datetime: DateTime<Utc>) -> Result<Heartbeat> {
         use sutron;
         use std::collections::BTreeMap;
 
@@ -128,8 +131,21 @@ impl SbdSource {
     /// let source = SbdSource::new("data");
     /// ```
     pub fn new<P: AsRef<Path>>(path: P) -> SbdSource {
+        SbdSource::with_storage(Filesystem::new(path))
+    }
+
+    /// Creates a new source that reads messages from the provided storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::SbdSource;
+    /// # use glacio::atlas::storage::Memory;
+    /// let source = SbdSource::with_storage(Memory::new());
+    /// ```
+    pub fn with_storage<S: Storage + 'static>(storage: S) -> SbdSource {
         SbdSource {
-            path: path.as_ref().to_path_buf(),
+            storage: Box::new(storage),
             imeis: Vec::new(),
             versions: Vec::new(),
         }
@@ -177,22 +193,34 @@ impl SbdSource {
     ///     println!("{:?}", heartbeat);
     /// }
     pub fn iter(&self) -> Result<ReadSbd> {
-        use sbd::storage::{FilesystemStorage, Storage};
-        let storage = FilesystemStorage::open(&self.path)?;
-        let mut messages = Vec::new();
-        if self.imeis.is_empty() {
-            messages = storage.messages()?;
-        } else {
-            for imei in &self.imeis {
-                messages.extend(storage.messages_from_imei(imei)?);
-            }
-        }
+        let mut messages = self.storage.messages(&self.imeis)?;
         messages.sort_by(|a, b| a.time_of_session().cmp(&b.time_of_session()));
         Ok(ReadSbd {
             iter: messages.into_iter(),
             versions: self.versions.clone(),
         })
     }
+
+    /// Watches this source for new heartbeats.
+    ///
+    /// The returned iterator blocks until the next heartbeat arrives. Only filesystem storages can
+    /// be watched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use glacio::atlas::SbdSource;
+    /// let source = SbdSource::new("/var/iridium").imeis(&["300234063556840"]);
+    /// for heartbeat in source.watch().unwrap() {
+    ///     println!("{:?}", heartbeat);
+    /// }
+    /// ```
+    pub fn watch(&self) -> Result<Watch> {
+        match self.storage.watch_path() {
+            Some(path) => Watch::new(path, &self.imeis, &self.versions),
+            None => Err(Error::NotWatchable),
+        }
+    }
 }
 
 impl Iterator for ReadSbd {
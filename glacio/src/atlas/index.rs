@@ -0,0 +1,187 @@
+//! A persistent, on-disk index of parsed heartbeats.
+//!
+//! Reading and parsing every SBD message for an IMEI gets slower as the season goes on. The index
+//! is an append-only file of JSON lines, one line per heartbeat, and each line records the SBD
+//! files that went into that heartbeat. Updating the index only reads the SBD files that aren't
+//! in the index yet.
+//!
+//! Files that make up a partial heartbeat (i.e. we're still waiting for the rest of its messages)
+//! aren't written to the index, so they are picked up again on the next update. Files that can't
+//! be read or parsed are recorded without a heartbeat so we don't keep retrying them.
+
+use atlas::{Heartbeat, Result};
+use atlas::assembler::{self, Assembler};
+use sbd::mo::Message;
+use serde_json;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// A persistent index of the heartbeats from one IMEI in a filesystem SBD storage.
+#[derive(Debug)]
+pub struct Index {
+    path: PathBuf,
+    storage: PathBuf,
+    imei: String,
+    files: HashSet<PathBuf>,
+    heartbeats: Vec<Heartbeat>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    files: Vec<PathBuf>,
+    heartbeat: Option<Heartbeat>,
+}
+
+impl Index {
+    /// Opens the index at `path` for the heartbeats from `imei` in the SBD `storage`.
+    ///
+    /// The index file is created on the first update if it doesn't exist. Lines that can't be
+    /// parsed, e.g. because we crashed in the middle of a write, are ignored, and their files are
+    /// re-read on the next update.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::Index;
+    /// let index = Index::open("/tmp/index.jsonl", "data", "300234063556840").unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>, Q: AsRef<Path>>(path: P, storage: Q, imei: &str) -> Result<Index> {
+        let mut index = Index {
+            path: path.as_ref().to_path_buf(),
+            storage: storage.as_ref().to_path_buf(),
+            imei: imei.to_string(),
+            files: HashSet::new(),
+            heartbeats: Vec::new(),
+        };
+        let file = match File::open(&index.path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(index),
+            Err(err) => return Err(err.into()),
+        };
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str::<Entry>(&line?) {
+                index.add(entry);
+            }
+        }
+        Ok(index)
+    }
+
+    /// Reads any new SBD files into the index, returning the number of new heartbeats.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::Index;
+    /// let mut index = Index::open("/tmp/index.jsonl", "data", "300234063556840").unwrap();
+    /// index.update().unwrap();
+    /// ```
+    pub fn update(&mut self) -> Result<usize> {
+        let mut paths = Vec::new();
+        assembler::sbd_paths(&self.storage.join(&self.imei), &mut paths)?;
+        let mut entries = Vec::new();
+        let mut messages = Vec::new();
+        for path in paths {
+            if self.files.contains(&path) {
+                continue;
+            }
+            match Message::from_path(&path) {
+                Ok(message) => messages.push((path, message)),
+                Err(_) => entries.push(Entry::new(vec![path], None)),
+            }
+        }
+        messages.sort_by(|a, b| a.1.time_of_session().cmp(&b.1.time_of_session()));
+        let mut assembler = Assembler::default();
+        for (path, message) in messages {
+            if let Some(assembled) = assembler.add(path, &message) {
+                entries.push(Entry::new(assembled.paths, assembled.heartbeat.ok()));
+            }
+        }
+
+        if entries.is_empty() {
+            return Ok(0);
+        }
+        let mut buf = Vec::new();
+        for entry in &entries {
+            serde_json::to_writer(&mut buf, entry)?;
+            buf.push(b'\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&buf)?;
+        let before = self.heartbeats.len();
+        for entry in entries {
+            self.add(entry);
+        }
+        Ok(self.heartbeats.len() - before)
+    }
+
+    /// Returns the indexed heartbeats, in the order they were indexed.
+    pub fn heartbeats(&self) -> &[Heartbeat] {
+        &self.heartbeats
+    }
+
+    fn add(&mut self, entry: Entry) {
+        self.files.extend(entry.files);
+        if let Some(heartbeat) = entry.heartbeat {
+            self.heartbeats.push(heartbeat);
+        }
+    }
+}
+
+impl Entry {
+    fn new(files: Vec<PathBuf>, heartbeat: Option<Heartbeat>) -> Entry {
+        Entry {
+            files: files,
+            heartbeat: heartbeat,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn index_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("glacio-index-{}.jsonl", name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn update() {
+        let path = index_path("update");
+        let mut index = Index::open(&path, "data", "300234063556840").unwrap();
+        assert!(index.heartbeats().is_empty());
+        assert_eq!(2, index.update().unwrap());
+        assert_eq!(2, index.heartbeats().len());
+        assert_eq!(0, index.update().unwrap());
+
+        let index = Index::open(&path, "data", "300234063556840").unwrap();
+        assert_eq!(2, index.heartbeats().len());
+    }
+
+    #[test]
+    fn corrupt_line() {
+        let path = index_path("corrupt-line");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"{\"files\": [\"data/300")
+            .unwrap();
+        let mut index = Index::open(&path, "data", "300234063556840").unwrap();
+        assert!(index.heartbeats().is_empty());
+        assert_eq!(2, index.update().unwrap());
+    }
+
+    #[test]
+    fn missing_imei() {
+        let path = index_path("missing-imei");
+        let mut index = Index::open(&path, "data", "300234063909200").unwrap();
+        assert_eq!(0, index.update().unwrap());
+        assert!(!path.exists());
+    }
+}
@@ -39,17 +39,27 @@
 //! undoubtedly update the heartbeat format and use the same format for both systems. This module
 //! will require an update to handle the new heartbeat version.
 
+pub mod alert;
 pub mod battery;
 pub mod efoy;
+pub mod index;
 pub mod scanner;
+pub mod storage;
+pub mod watch;
 
+mod assembler;
 mod heartbeat;
 
 pub use self::efoy::Efoy;
 pub use self::heartbeat::{Heartbeat, ReadSbd, SbdSource};
+pub use self::index::Index;
+pub use self::storage::Storage;
+pub use self::watch::Watch;
 use chrono::ParseError;
+use notify;
 use sbd;
-use std::{error, result};
+use serde_json;
+use std::{error, io, result};
 use std::fmt::{self, Display, Formatter};
 use std::num::{ParseFloatError, ParseIntError};
 use sutron;
@@ -57,6 +67,8 @@ use sutron;
 /// A custom error enum for ATLAS issues.
 #[derive(Debug)]
 pub enum Error {
+    /// The archive of SBD messages is in an invalid format.
+    ArchiveFormat(String),
     /// The efoy cartridge name is invalid.
     CartridgeName(String),
     /// Wrapper around `chrono::ParseError`.
@@ -69,6 +81,14 @@ pub enum Error {
     EfoyHeartbeatFormat(String),
     /// The format of the heartbeat message could not be recognized.
     HeartbeatFormat(String),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+    /// Wrapper around `serde_json::Error`.
+    Json(serde_json::Error),
+    /// The storage of SBD messages can't be watched for new messages.
+    NotWatchable,
+    /// Wrapper around `notify::Error`.
+    Notify(notify::Error),
     /// Wrapper around `std::num::ParseFloatError`.
     ParseFloat(ParseFloatError),
     /// Wrapper around `std::num::ParseIntError`.
@@ -88,6 +108,24 @@ pub enum Error {
 /// A custom result type for ATLAS.
 pub type Result<T> = result::Result<T, Error>;
 
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(err: notify::Error) -> Error {
+        Error::Notify(err)
+    }
+}
+
 impl From<ParseIntError> for Error {
     fn from(err: ParseIntError) -> Error {
         Error::ParseInt(err)
@@ -121,6 +159,7 @@ impl From<sutron::message::Error> for Error {
 impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
+            Error::ArchiveFormat(_) => "the format of the sbd archive is invalid",
             Error::CartridgeName(_) => "invalid EFOY cartridge name",
             Error::ChronoParse(ref err) => err.description(),
             Error::DuplicateEfoyCartridge(_) => {
@@ -129,6 +168,10 @@ impl error::Error for Error {
             Error::EmptyCartridge(_) => "the cartridge is already empty, cannot empty it again",
             Error::EfoyHeartbeatFormat(_) => "the format of this efoy heartbeat message is invalid",
             Error::HeartbeatFormat(_) => "the format of this heartbeat message is invalid",
+            Error::Io(ref err) => err.description(),
+            Error::Json(ref err) => err.description(),
+            Error::NotWatchable => "the sbd storage can't be watched",
+            Error::Notify(ref err) => err.description(),
             Error::ParseFloat(ref err) => err.description(),
             Error::ParseInt(ref err) => err.description(),
             Error::Sbd(ref err) => err.description(),
@@ -144,6 +187,9 @@ impl error::Error for Error {
     fn cause(&self) -> Option<&error::Error> {
         match *self {
             Error::ChronoParse(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            Error::Notify(ref err) => Some(err),
             Error::ParseFloat(ref err) => Some(err),
             Error::ParseInt(ref err) => Some(err),
             Error::Sbd(ref err) => Some(err),
@@ -156,6 +202,7 @@ impl error::Error for Error {
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
+            Error::ArchiveFormat(ref s) => write!(f, "invalid sbd archive format: {}", s),
             Error::CartridgeName(ref name) => write!(f, "invalid EFOY cartridge name: {}", name),
             Error::ChronoParse(ref err) => err.fmt(f),
             Error::DuplicateEfoyCartridge(ref name) => {
@@ -174,6 +221,10 @@ impl Display for Error {
             }
             Error::EfoyHeartbeatFormat(ref s) => write!(f, "invalid efoy heartbeat format: {}", s),
             Error::HeartbeatFormat(ref s) => write!(f, "invalid heartbeat format: {}", s),
+            Error::Io(ref err) => err.fmt(f),
+            Error::Json(ref err) => err.fmt(f),
+            Error::NotWatchable => write!(f, "the sbd storage can't be watched"),
+            Error::Notify(ref err) => err.fmt(f),
             Error::ParseFloat(ref err) => err.fmt(f),
             Error::ParseInt(ref err) => err.fmt(f),
             Error::Sbd(ref err) => err.fmt(f),
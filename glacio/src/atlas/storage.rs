@@ -0,0 +1,381 @@
+//! Places to read SBD messages from.
+//!
+//! On lidar.io the messages live in an `sbd` filesystem storage, but `SbdSource` can read
+//! heartbeats out of anything that implements `Storage`:
+//!
+//! - `Filesystem` is the `sbd` filesystem storage, as written by our DirectIP receiver.
+//! - `Memory` holds messages in memory, which is handy for tests.
+//! - `Tar` reads `.sbd` files out of an (uncompressed) tar archive, e.g. a past season.
+//! - `Payloads` reads raw payload text files named by their time of session, like the ones in
+//! `data/`.
+//!
+//! # Examples
+//!
+//! ```
+//! use glacio::atlas::SbdSource;
+//! use glacio::atlas::storage::Tar;
+//! let heartbeats = SbdSource::with_storage(Tar::new("data/2017-08.tar"))
+//!     .iter()
+//!     .unwrap()
+//!     .collect::<Vec<_>>();
+//! assert_eq!(2, heartbeats.len());
+//! ```
+
+use atlas::{Error, Result};
+use atlas::assembler;
+use chrono::{TimeZone, Utc};
+use directip;
+use sbd::mo::Message;
+use sbd::storage::FilesystemStorage;
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The format of payload file names, e.g. `170801_000055`.
+const PAYLOAD_FILE_NAME_FORMAT: &'static str = "%y%m%d_%H%M%S";
+/// The size of a tar header and of the blocks that tar entries are padded to.
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// A source of SBD messages.
+pub trait Storage: Debug {
+    /// Returns the messages from these IMEIs, or every message if `imeis` is empty.
+    ///
+    /// The messages can be in any order.
+    fn messages(&self, imeis: &[String]) -> Result<Vec<Message>>;
+
+    /// Returns the directory to watch for new SBD files, if this storage can be watched.
+    fn watch_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// An `sbd` filesystem storage, organized by IMEI, year, and month.
+#[derive(Clone, Debug)]
+pub struct Filesystem {
+    path: PathBuf,
+}
+
+/// Messages held in memory.
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    messages: Vec<Message>,
+}
+
+/// An uncompressed tar archive of `.sbd` files.
+///
+/// The layout inside the archive doesn't matter; every `.sbd` file is read.
+#[derive(Clone, Debug)]
+pub struct Tar {
+    path: PathBuf,
+}
+
+/// A directory of raw payload text files from one IMEI.
+///
+/// Each file holds one message's payload, and is named by its time of session
+/// (`yymmdd_HHMMSS.txt`). Files that aren't named that way are ignored. The MOMSNs are made up,
+/// by counting up through the files in time order.
+#[derive(Clone, Debug)]
+pub struct Payloads {
+    path: PathBuf,
+    imei: String,
+}
+
+impl Filesystem {
+    /// Creates a new filesystem storage at the given path.
+    ///
+    /// The storage isn't opened until its messages are read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::storage::Filesystem;
+    /// let storage = Filesystem::new("data");
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P) -> Filesystem {
+        Filesystem { path: path.as_ref().to_path_buf() }
+    }
+}
+
+impl Storage for Filesystem {
+    fn messages(&self, imeis: &[String]) -> Result<Vec<Message>> {
+        use sbd::storage::Storage;
+        let storage = FilesystemStorage::open(&self.path)?;
+        if imeis.is_empty() {
+            storage.messages().map_err(Error::from)
+        } else {
+            let mut messages = Vec::new();
+            for imei in imeis {
+                messages.extend(storage.messages_from_imei(imei)?);
+            }
+            Ok(messages)
+        }
+    }
+
+    fn watch_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+impl Memory {
+    /// Creates a new, empty memory storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::storage::Memory;
+    /// let storage = Memory::new();
+    /// ```
+    pub fn new() -> Memory {
+        Default::default()
+    }
+
+    /// Adds a message to this storage.
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+}
+
+impl From<Vec<Message>> for Memory {
+    fn from(messages: Vec<Message>) -> Memory {
+        Memory { messages: messages }
+    }
+}
+
+impl Storage for Memory {
+    fn messages(&self, imeis: &[String]) -> Result<Vec<Message>> {
+        Ok(
+            self.messages
+                .iter()
+                .filter(|message| is_wanted(message.imei(), imeis))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+impl Tar {
+    /// Creates a new storage for the tar archive at the given path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::storage::Tar;
+    /// let storage = Tar::new("data/2017-08.tar");
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P) -> Tar {
+        Tar { path: path.as_ref().to_path_buf() }
+    }
+}
+
+impl Storage for Tar {
+    fn messages(&self, imeis: &[String]) -> Result<Vec<Message>> {
+        let mut bytes = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut bytes)?;
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while offset + TAR_BLOCK_SIZE <= bytes.len() {
+            let header = &bytes[offset..offset + TAR_BLOCK_SIZE];
+            if header.iter().all(|&byte| byte == 0) {
+                break;
+            }
+            let size = tar_size(&header[124..136])?;
+            let start = offset + TAR_BLOCK_SIZE;
+            let end = match start.checked_add(size) {
+                Some(end) if end <= bytes.len() => end,
+                _ => return Err(Error::ArchiveFormat("truncated archive".to_string())),
+            };
+            let data = &bytes[start..end];
+            let is_file = header[156] == b'0' || header[156] == 0;
+            if is_file && assembler::is_sbd(Path::new(&tar_string(&header[0..100]))) {
+                let message = Message::read_from(data)?;
+                if is_wanted(message.imei(), imeis) {
+                    messages.push(message);
+                }
+            }
+            offset = end + (TAR_BLOCK_SIZE - size % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+        }
+        Ok(messages)
+    }
+}
+
+impl Payloads {
+    /// Creates a new storage for the payload files in a directory, all from one IMEI.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::atlas::storage::Payloads;
+    /// let storage = Payloads::new("data", "300234063556840");
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P, imei: &str) -> Payloads {
+        Payloads {
+            path: path.as_ref().to_path_buf(),
+            imei: imei.to_string(),
+        }
+    }
+}
+
+impl Storage for Payloads {
+    fn messages(&self, imeis: &[String]) -> Result<Vec<Message>> {
+        if !is_wanted(&self.imei, imeis) {
+            return Ok(Vec::new());
+        }
+        let mut payloads = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |extension| extension != "txt") {
+                continue;
+            }
+            let datetime = match path.file_stem().and_then(|stem| stem.to_str()).and_then(
+                |stem| Utc.datetime_from_str(stem, PAYLOAD_FILE_NAME_FORMAT).ok(),
+            ) {
+                Some(datetime) => datetime,
+                None => continue,
+            };
+            payloads.push((datetime, path));
+        }
+        payloads.sort();
+        let mut messages = Vec::new();
+        for (i, (datetime, path)) in payloads.into_iter().enumerate() {
+            let mut payload = Vec::new();
+            File::open(path)?.read_to_end(&mut payload)?;
+            messages.push(directip::mo_message(
+                &self.imei,
+                (i + 1) as u16,
+                0,
+                datetime,
+                &payload,
+            )?);
+        }
+        Ok(messages)
+    }
+}
+
+fn is_wanted(imei: &str, imeis: &[String]) -> bool {
+    imeis.is_empty() || imeis.iter().any(|wanted| wanted == imei)
+}
+
+fn tar_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn tar_size(bytes: &[u8]) -> Result<usize> {
+    let s = tar_string(bytes);
+    usize::from_str_radix(s.trim(), 8).map_err(|_| {
+        Error::ArchiveFormat(format!("invalid entry size: {}", s))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atlas::SbdSource;
+    use chrono::TimeZone;
+
+    fn filesystem_messages() -> Vec<Message> {
+        let mut messages = Filesystem::new("data").messages(&[]).unwrap();
+        messages.sort_by_key(|message| message.time_of_session());
+        messages
+    }
+
+    #[test]
+    fn memory() {
+        let storage = Memory::from(filesystem_messages());
+        assert_eq!(4, storage.messages(&[]).unwrap().len());
+        assert_eq!(
+            4,
+            storage
+                .messages(&["300234063556840".to_string()])
+                .unwrap()
+                .len()
+        );
+        assert!(
+            storage
+                .messages(&["300234063909200".to_string()])
+                .unwrap()
+                .is_empty()
+        );
+        let heartbeats = SbdSource::with_storage(storage)
+            .iter()
+            .unwrap()
+            .filter_map(|result| result.ok())
+            .collect::<Vec<_>>();
+        assert_eq!(2, heartbeats.len());
+    }
+
+    #[test]
+    fn tar() {
+        let mut messages = Tar::new("data/2017-08.tar").messages(&[]).unwrap();
+        messages.sort_by_key(|message| message.time_of_session());
+        assert_eq!(&filesystem_messages()[1..], messages.as_slice());
+    }
+
+    #[test]
+    fn missing_tar() {
+        assert!(Tar::new("data/notafile.tar").messages(&[]).is_err());
+    }
+
+    #[test]
+    fn malformed_tar() {
+        use std::env;
+        use std::io::Write;
+
+        let mut bytes = Vec::new();
+        File::open("data/2017-08.tar")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        let path = env::temp_dir().join("glacio-malformed.tar");
+
+        File::create(&path)
+            .unwrap()
+            .write_all(&bytes[..TAR_BLOCK_SIZE + 10])
+            .unwrap();
+        assert!(Tar::new(&path).messages(&[]).is_err());
+
+        bytes[124..136].copy_from_slice(b"77777777777\0");
+        File::create(&path).unwrap().write_all(&bytes).unwrap();
+        assert!(Tar::new(&path).messages(&[]).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn payloads() {
+        let mut messages = Payloads::new("data", "300234063556840")
+            .messages(&[])
+            .unwrap();
+        messages.sort_by_key(|message| message.time_of_session());
+        assert_eq!(3, messages.len());
+        assert_eq!(
+            Utc.ymd(2017, 8, 1).and_hms(0, 0, 55),
+            messages[1].time_of_session()
+        );
+        assert_eq!(2, messages[1].momsn());
+        assert!(
+            Payloads::new("data", "300234063556840")
+                .messages(&["300234063909200".to_string()])
+                .unwrap()
+                .is_empty()
+        );
+
+        let heartbeats = SbdSource::with_storage(Payloads::new("data", "300234063556840"))
+            .iter()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(2, heartbeats.len());
+        assert!(heartbeats[0].is_err());
+        assert_eq!(
+            Utc.ymd(2017, 8, 1).and_hms(0, 0, 55),
+            heartbeats[1].as_ref().unwrap().datetime
+        );
+    }
+
+    #[test]
+    fn watch() {
+        assert!(SbdSource::with_storage(Memory::new()).watch().is_err());
+        assert!(SbdSource::new("data").watch().is_ok());
+    }
+}
@@ -0,0 +1,161 @@
+//! Watch an SBD storage for new heartbeats as they arrive.
+//!
+//! `SbdSource::iter` reads a snapshot of the storage. `SbdSource::watch` instead monitors the
+//! storage directory (with inotify on Linux) and yields each new heartbeat as soon as its last SBD
+//! message lands.
+
+use atlas::{Heartbeat, Result};
+use atlas::assembler::{self, Assembler};
+use notify::{self, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use sbd::mo::Message;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// How long to wait for writes to a new SBD file to settle before reading it.
+const DELAY_MILLISECONDS: u64 = 500;
+
+/// A blocking iterator over new heartbeats in an SBD storage.
+///
+/// Heartbeats that were already complete when the watch started are not returned. Messages from
+/// a partially arrived heartbeat are remembered, so the heartbeat is returned once the rest of it
+/// arrives.
+#[derive(Debug)]
+pub struct Watch {
+    receiver: Receiver<DebouncedEvent>,
+    imeis: Vec<String>,
+    versions: Vec<u8>,
+    assemblers: HashMap<String, Assembler>,
+    seen: HashSet<PathBuf>,
+    ready: VecDeque<Result<Heartbeat>>,
+    // Dropping the watcher stops the watch, so we hold on to it.
+    _watcher: RecommendedWatcher,
+}
+
+impl Watch {
+    pub(crate) fn new(path: &Path, imeis: &[String], versions: &[u8]) -> Result<Watch> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(DELAY_MILLISECONDS))?;
+        watcher.watch(path, RecursiveMode::Recursive)?;
+        let mut watch = Watch {
+            receiver: receiver,
+            imeis: imeis.to_vec(),
+            versions: versions.to_vec(),
+            assemblers: HashMap::new(),
+            seen: HashSet::new(),
+            ready: VecDeque::new(),
+            _watcher: watcher,
+        };
+
+        let mut paths = Vec::new();
+        assembler::sbd_paths(path, &mut paths)?;
+        let mut messages = paths
+            .into_iter()
+            .filter_map(|path| {
+                Message::from_path(&path).ok().map(|message| (path, message))
+            })
+            .collect::<Vec<_>>();
+        messages.sort_by(|a, b| a.1.time_of_session().cmp(&b.1.time_of_session()));
+        for (path, message) in messages {
+            watch.add(path, &message);
+        }
+        watch.ready.clear();
+        Ok(watch)
+    }
+
+    fn add_path(&mut self, path: PathBuf) {
+        if !assembler::is_sbd(&path) || self.seen.contains(&path) {
+            return;
+        }
+        match Message::from_path(&path) {
+            Ok(message) => self.add(path, &message),
+            Err(err) => self.ready.push_back(Err(err.into())),
+        }
+    }
+
+    fn add(&mut self, path: PathBuf, message: &Message) {
+        self.seen.insert(path.clone());
+        if !self.imeis.is_empty() && !self.imeis.iter().any(|imei| imei == message.imei()) {
+            return;
+        }
+        let imei = message.imei().to_string();
+        if !self.assemblers.contains_key(&imei) {
+            self.assemblers.insert(imei.clone(), Assembler::default());
+        }
+        if let Some(assembled) = self.assemblers.get_mut(&imei).unwrap().add(path, message) {
+            match assembled.heartbeat {
+                Ok(heartbeat) => {
+                    if self.versions.is_empty() || self.versions.contains(&heartbeat.version) {
+                        self.ready.push_back(Ok(heartbeat));
+                    }
+                }
+                Err(err) => self.ready.push_back(Err(err)),
+            }
+        }
+    }
+}
+
+impl Iterator for Watch {
+    type Item = Result<Heartbeat>;
+
+    fn next(&mut self) -> Option<Result<Heartbeat>> {
+        loop {
+            if let Some(result) = self.ready.pop_front() {
+                return Some(result);
+            }
+            match self.receiver.recv() {
+                Ok(DebouncedEvent::Create(path)) |
+                Ok(DebouncedEvent::Write(path)) |
+                Ok(DebouncedEvent::Rename(_, path)) => self.add_path(path),
+                Ok(DebouncedEvent::Error(err, _)) => return Some(Err(err.into())),
+                Ok(_) => {}
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atlas::SbdSource;
+    use chrono::{TimeZone, Utc};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const MONTH: &'static str = "300234063556840/2017/08";
+
+    fn storage(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("glacio-watch-{}", name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join(MONTH)).unwrap();
+        path
+    }
+
+    fn copy(storage: &PathBuf, file_name: &str) {
+        fs::copy(
+            PathBuf::from("data").join(MONTH).join(file_name),
+            storage.join(MONTH).join(file_name),
+        ).unwrap();
+    }
+
+    #[test]
+    fn new_heartbeat() {
+        let storage = storage("new-heartbeat");
+        let mut watch = SbdSource::new(&storage).watch().unwrap();
+        copy(&storage, "170825_150106.sbd");
+        let heartbeat = watch.next().unwrap().unwrap();
+        assert_eq!(Utc.ymd(2017, 8, 25).and_hms(15, 1, 6), heartbeat.datetime);
+    }
+
+    #[test]
+    fn partial_heartbeat() {
+        let storage = storage("partial-heartbeat");
+        copy(&storage, "170801_000055.sbd");
+        let mut watch = SbdSource::new(&storage).watch().unwrap();
+        copy(&storage, "170801_000115.sbd");
+        let heartbeat = watch.next().unwrap().unwrap();
+        assert_eq!(Utc.ymd(2017, 8, 1).and_hms(0, 0, 55), heartbeat.datetime);
+    }
+}
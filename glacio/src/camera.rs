@@ -2,33 +2,67 @@
 //!
 //! These cameras are installed in remote locations, e.g. Greenland or Alaska. They take pictures
 //! at regular intervals, then send those pictures back to a home server via a satellite
-//! connection. The images are served via HTTP, right now by http://iridiumcam.lidar.io.
+//! connection. The images are served via HTTP, by default by http://iridiumcam.lidar.io.
 
-use chrono::{self, DateTime, TimeZone, Utc};
-use std::{error, io, result};
+use chrono::{self, DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use exif::Exif;
+use image::{self, ImageError, ImageFormat};
+use jpeg::{self, Dimensions};
+use notify::{self, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use regex::{self, Regex};
+use sha2::{Digest, Sha256};
+use std::{error, io, mem, result};
+use std::borrow::Borrow;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::fmt::{self, Display, Formatter};
-use std::fs::ReadDir;
-use std::path::{Path, PathBuf, StripPrefixError};
+use std::fs::{self, DirEntry, File, ReadDir};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, SystemTime};
 use url::{self, Url};
 
 const DEFAULT_EXTENSIONS: &'static [&'static str] = &["jpg"];
 const DEFAULT_SERVER_BASE_URL: &'static str = "http://iridiumcam.lidar.io";
+const WATCH_DELAY_MILLISECONDS: u64 = 500;
+const CHECKSUM_BUFFER_SIZE: usize = 8192;
+/// Some filesystems only keep modification times to the nearest two seconds.
+pub(crate) const MODIFIED_RESOLUTION_SECONDS: u64 = 2;
+/// The name of the capture group that holds an image's datetime in a filename pattern.
+const DATETIME_GROUP: &'static str = "datetime";
+
+lazy_static! {
+    static ref DEFAULT_PATTERN: Pattern = Pattern::new(
+        r"(?P<datetime>\d{8}_\d{6})$",
+        "%Y%m%d_%H%M%S",
+    ).unwrap();
+}
 
 /// A custom error enum for cameras.
 #[derive(Debug)]
 pub enum Error {
     /// Wrapper around `chrono::ParseError`.
     ChronoParse(chrono::ParseError),
-    /// The file stem is too short to parse for a datetime.
-    FileStemTooShort(String),
+    /// Wrapper around `image::ImageError`.
+    Image(ImageError),
     /// Wrapper around `std::io::Error`.
     Io(io::Error),
+    /// Wrapper around `glacio::jpeg::Error`.
+    Jpeg(jpeg::Error),
+    /// A filename pattern's regular expression has no `datetime` capture group.
+    MissingDatetimeGroup(String),
     /// No file stem for the provided path.
     NoFileStem(PathBuf),
-    /// Wrapper around `std::path::StripPrefixError`.
-    StripPrefix(StripPrefixError),
+    /// Wrapper around `notify::Error`.
+    Notify(notify::Error),
+    /// The image isn't under any of the server's document roots, so it has no url.
+    OutsideDocumentRoots(PathBuf),
+    /// The file stem doesn't match the camera's filename pattern.
+    PatternMismatch(String),
+    /// Wrapper around `regex::Error`.
+    Regex(regex::Error),
     /// Wrapper around `url::ParseError`.
     UrlParse(url::ParseError),
 }
@@ -41,11 +75,19 @@ pub type Result<T> = result::Result<T, Error>;
 pub struct Camera {
     path: PathBuf,
     extensions: Vec<OsString>,
+    max_depth: usize,
+    follow_symlinks: bool,
+    pattern: Pattern,
+    datetime_policy: DatetimePolicy,
+    skip_corrupt: bool,
 }
 
 /// An iterator over a camera's images, wrapped in a `Result` in case something goes wrong parsing
 /// the image path.
 ///
+/// If the camera is recursive, directories are searched depth-first, so images are not returned
+/// in any particular order.
+///
 /// # Examples
 ///
 /// ```
@@ -57,24 +99,164 @@ pub struct Camera {
 /// ```
 #[derive(Debug)]
 pub struct Images {
-    read_dir: ReadDir,
+    read_dirs: Vec<(ReadDir, usize)>,
+    extensions: Vec<OsString>,
+    max_depth: usize,
+    follow_symlinks: bool,
+    visited: HashSet<PathBuf>,
+    pattern: Pattern,
+    datetime_policy: DatetimePolicy,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+/// A blocking iterator over new images as they arrive in a camera's directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use glacio::Camera;
+/// let camera = Camera::new("data/ATLAS_CAM").unwrap();
+/// for result in camera.watch().unwrap() {
+///     println!("New image: {}", result.unwrap().path().display());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Watch {
+    receiver: Receiver<DebouncedEvent>,
     extensions: Vec<OsString>,
+    pattern: Pattern,
+    datetime_policy: DatetimePolicy,
+    // Dropping the watcher stops the watch, so we hold on to it.
+    _watcher: RecommendedWatcher,
+}
+
+/// An in-memory index of a camera's images, sorted by datetime.
+///
+/// Listing a directory and parsing every filename gets slow once a camera has tens of thousands of
+/// images. An index remembers the modification time of each directory it has read, and
+/// `Index::refresh` only reads the directories that have changed since, so only new filenames are
+/// parsed. Images that can't be read, e.g. because their filename doesn't match the camera's
+/// pattern, are left out of the index.
+///
+/// Each image's file is stat-ed and its JPEG structure checked with `glacio::jpeg::verify` when
+/// it's first indexed, and its `Properties` are kept until its file changes. Those checks only
+/// read the file's headers and last two bytes, so even the first refresh of a big camera is
+/// cheap. Fully decoding an image is slow, so it's left to the caller: if the camera skips corrupt
+/// images, `Index::undecoded_latest_image` returns the candidate that still needs decoding, and
+/// `Index::update` records the result of `Properties::decode`.
+///
+/// # Examples
+///
+/// ```
+/// # use glacio::Camera;
+/// # use glacio::camera::Index;
+/// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+/// index.refresh().unwrap();
+/// assert_eq!(1, index.images().len());
+/// ```
+#[derive(Debug)]
+pub struct Index {
+    camera: Camera,
+    directories: HashMap<PathBuf, Directory>,
+    images: Vec<Image>,
+    properties: HashMap<PathBuf, Properties>,
+    corrupt_images: usize,
+}
+
+/// What an `Index` knows about an image's file.
+#[derive(Clone, Copy, Debug)]
+pub struct Properties {
+    modified: Option<SystemTime>,
+    read: SystemTime,
+    size: Option<u64>,
+    dimensions: Option<Dimensions>,
+    corrupt: bool,
+    decoded: bool,
+}
+
+#[derive(Debug)]
+struct Directory {
+    modified: SystemTime,
+    scanned: SystemTime,
+    images: Vec<(PathBuf, Image)>,
+    subdirectories: Vec<PathBuf>,
 }
 
 /// An image taken by a remote camera and stored on the local filesystem.
 ///
-/// Date and time information are assumed to be stored in the image's filename.
-#[derive(Debug, PartialEq, Eq, PartialOrd)]
+/// Date and time information are read from the image's filename or its EXIF metadata, depending
+/// on the `DatetimePolicy`.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Image {
     datetime: DateTime<Utc>,
     path: PathBuf,
+    exif: Option<Exif>,
+    clock_drift: Option<chrono::Duration>,
+}
+
+/// Where an image's date and time come from.
+///
+/// The EXIF `DateTimeOriginal` is taken to be in the same time zone as the filename, as set by
+/// the camera's `Pattern`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatetimePolicy {
+    /// Use the filename, and only read the EXIF metadata if the filename can't be parsed.
+    PreferFilename,
+    /// Use the EXIF `DateTimeOriginal`, falling back to the filename.
+    PreferExif,
+    /// Read both, use the filename, and report the difference between them as clock drift.
+    CrossCheck,
+}
+
+/// The order of sorted images, by datetime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Oldest first.
+    Ascending,
+    /// Newest first.
+    Descending,
+}
+
+/// How to thin out a sorted list of images, e.g. to make a timelapse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    /// Keep every nth image, starting with the first.
+    Every(usize),
+    /// Keep the first image, then each image that is at least this long after (or before, for
+    /// descending images) the last one kept.
+    Interval(chrono::Duration),
+}
+
+/// How to find an image's date and time in its filename.
+///
+/// A pattern is a regular expression with a `datetime` capture group, which is matched against
+/// the file stem (the filename without its extension). The captured text is parsed with a chrono
+/// format string, and is taken to be in a fixed offset from UTC.
+///
+/// The default pattern, used by `Image::new`, expects the file stem to end with
+/// `%Y%m%d_%H%M%S` in UTC, e.g. `ATLAS_CAM_20170806_152500`.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    regex: Regex,
+    format: String,
+    offset: FixedOffset,
 }
 
 /// An image server, used to translate a local image path to a url.
+///
+/// A server has one or more document roots, each with its own base url, e.g. for cameras whose
+/// images are stored on different disks.
 #[derive(Debug)]
 pub struct Server {
+    roots: Vec<DocumentRoot>,
+}
+
+#[derive(Debug)]
+struct DocumentRoot {
+    path: PathBuf,
     base_url: Url,
-    document_root: PathBuf,
 }
 
 impl From<io::Error> for Error {
@@ -83,9 +265,15 @@ impl From<io::Error> for Error {
     }
 }
 
-impl From<StripPrefixError> for Error {
-    fn from(err: StripPrefixError) -> Error {
-        Error::StripPrefix(err)
+impl From<ImageError> for Error {
+    fn from(err: ImageError) -> Error {
+        Error::Image(err)
+    }
+}
+
+impl From<jpeg::Error> for Error {
+    fn from(err: jpeg::Error) -> Error {
+        Error::Jpeg(err)
     }
 }
 
@@ -95,6 +283,18 @@ impl From<chrono::ParseError> for Error {
     }
 }
 
+impl From<notify::Error> for Error {
+    fn from(err: notify::Error) -> Error {
+        Error::Notify(err)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Error {
+        Error::Regex(err)
+    }
+}
+
 impl From<url::ParseError> for Error {
     fn from(err: url::ParseError) -> Error {
         Error::UrlParse(err)
@@ -105,10 +305,15 @@ impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
             Error::ChronoParse(ref err) => err.description(),
-            Error::FileStemTooShort(_) => "file stem is too short",
+            Error::Image(ref err) => err.description(),
             Error::Io(ref err) => err.description(),
+            Error::Jpeg(ref err) => err.description(),
+            Error::MissingDatetimeGroup(_) => "no datetime capture group in filename pattern",
             Error::NoFileStem(_) => "no file stem for path",
-            Error::StripPrefix(ref err) => err.description(),
+            Error::Notify(ref err) => err.description(),
+            Error::OutsideDocumentRoots(_) => "image is not under any document root",
+            Error::PatternMismatch(_) => "file stem does not match the filename pattern",
+            Error::Regex(ref err) => err.description(),
             Error::UrlParse(ref err) => err.description(),
         }
     }
@@ -116,10 +321,15 @@ impl error::Error for Error {
     fn cause(&self) -> Option<&error::Error> {
         match *self {
             Error::ChronoParse(ref err) => Some(err),
-            Error::FileStemTooShort(_) => None,
+            Error::Image(ref err) => Some(err),
             Error::Io(ref err) => Some(err),
+            Error::Jpeg(ref err) => Some(err),
+            Error::MissingDatetimeGroup(_) => None,
             Error::NoFileStem(_) => None,
-            Error::StripPrefix(ref err) => Some(err),
+            Error::Notify(ref err) => Some(err),
+            Error::OutsideDocumentRoots(_) => None,
+            Error::PatternMismatch(_) => None,
+            Error::Regex(ref err) => Some(err),
             Error::UrlParse(ref err) => Some(err),
         }
     }
@@ -129,16 +339,29 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match *self {
             Error::ChronoParse(ref err) => err.fmt(f),
-            Error::FileStemTooShort(ref file_stem) => {
+            Error::Image(ref err) => err.fmt(f),
+            Error::Io(ref err) => err.fmt(f),
+            Error::Jpeg(ref err) => err.fmt(f),
+            Error::MissingDatetimeGroup(ref regex) => {
+                write!(f, "no datetime capture group in filename pattern: {}", regex)
+            }
+            Error::NoFileStem(ref path) => write!(f, "no file stem for path: {}", path.display()),
+            Error::Notify(ref err) => err.fmt(f),
+            Error::OutsideDocumentRoots(ref path) => {
+                write!(
+                    f,
+                    "image is not under any document root: {}",
+                    path.display()
+                )
+            }
+            Error::PatternMismatch(ref file_stem) => {
                 write!(
                     f,
-                    "file stem is too short for datetime parsing: {}",
+                    "file stem does not match the filename pattern: {}",
                     file_stem
                 )
             }
-            Error::Io(ref err) => err.fmt(f),
-            Error::NoFileStem(ref path) => write!(f, "no file stem for path: {}", path.display()),
-            Error::StripPrefix(ref err) => err.fmt(f),
+            Error::Regex(ref err) => err.fmt(f),
             Error::UrlParse(ref err) => err.fmt(f),
         }
     }
@@ -147,8 +370,9 @@ impl Display for Error {
 impl Camera {
     /// Creates a new camera whose images are located under the provided path.
     ///
-    /// The local image path is canonicalized. The path is *not* searched recursively — all images
-    /// must be located directly under the path.
+    /// The local image path is canonicalized. By default, the path is *not* searched recursively —
+    /// all images must be located directly under the path. Use `Camera::max_depth` to search
+    /// subdirectories, e.g. if old images are archived into year and month directories.
     ///
     /// # Examples
     ///
@@ -162,9 +386,98 @@ impl Camera {
         Ok(Camera {
             path: path.as_ref().canonicalize()?,
             extensions: DEFAULT_EXTENSIONS.iter().map(|&s| s.into()).collect(),
+            max_depth: 0,
+            follow_symlinks: false,
+            pattern: DEFAULT_PATTERN.clone(),
+            datetime_policy: DatetimePolicy::default(),
+            skip_corrupt: false,
         })
     }
 
+    /// Sets whether `Camera::latest_image` skips over corrupt images.
+    ///
+    /// Images are often cut short on their way over the satellite link, and the latest image is
+    /// the one most likely to still be arriving, so a camera that skips corrupt images returns the
+    /// latest complete image instead. See `Image::verify` for what counts as corrupt.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap().skip_corrupt(true);
+    /// ```
+    pub fn skip_corrupt(mut self, skip_corrupt: bool) -> Camera {
+        self.skip_corrupt = skip_corrupt;
+        self
+    }
+
+    /// Sets where this camera's image datetimes come from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::DatetimePolicy;
+    /// let camera = Camera::new("data/exif").unwrap().datetime_policy(DatetimePolicy::PreferExif);
+    /// ```
+    pub fn datetime_policy(mut self, datetime_policy: DatetimePolicy) -> Camera {
+        self.datetime_policy = datetime_policy;
+        self
+    }
+
+    /// Sets the pattern used to find the date and time in this camera's image filenames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Pattern;
+    /// let pattern = Pattern::new(r"_(?P<datetime>\d{4}-\d{2}-\d{2}_\d{4})$", "%Y-%m-%d_%H%M")
+    ///     .unwrap();
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap().pattern(pattern);
+    /// ```
+    pub fn pattern(mut self, pattern: Pattern) -> Camera {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Sets how many levels of subdirectories are searched for images.
+    ///
+    /// Zero, the default, only searches the camera's directory. Two would find images archived
+    /// into year and month subdirectories, e.g. `2017/08/ATLAS_CAM_20170806_152500.jpg`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// let camera = Camera::new("data/HEL_BERGCAM3").unwrap();
+    /// assert_eq!(0, camera.images().unwrap().count());
+    /// let camera = camera.max_depth(1);
+    /// assert_eq!(1, camera.images().unwrap().count());
+    /// ```
+    pub fn max_depth(mut self, max_depth: usize) -> Camera {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets whether symbolic links to directories are followed when searching subdirectories.
+    ///
+    /// Links are not followed by default. Each directory is only searched once, even if several
+    /// links point to it, so links can't send the search in circles. Images found through links
+    /// have their paths canonicalized like any other image, so they must still be under the image
+    /// server's document root to get a url. Symbolic links to images are always followed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap().max_depth(2).follow_symlinks(true);
+    /// ```
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Camera {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
     /// Returns an iterator over this camera's images.
     ///
     /// # Examples
@@ -175,15 +488,20 @@ impl Camera {
     /// let images = camera.images().unwrap().collect::<Vec<_>>();
     /// ```
     pub fn images(&self) -> Result<Images> {
-        self.path
-            .read_dir()
-            .map(|read_dir| {
-                Images {
-                    read_dir: read_dir,
-                    extensions: self.extensions.clone(),
-                }
-            })
-            .map_err(Error::from)
+        let read_dir = self.path.read_dir()?;
+        let mut visited = HashSet::new();
+        visited.insert(self.path.clone());
+        Ok(Images {
+            read_dirs: vec![(read_dir, 0)],
+            extensions: self.extensions.clone(),
+            max_depth: self.max_depth,
+            follow_symlinks: self.follow_symlinks,
+            visited: visited,
+            pattern: self.pattern.clone(),
+            datetime_policy: self.datetime_policy,
+            start: None,
+            end: None,
+        })
     }
 
     /// Returns this camera's latest image, or None if there are no images for this camera.
@@ -191,7 +509,8 @@ impl Camera {
     /// Images are ordered by their time of capture, as determined by their filename.
     ///
     /// Any underlying errors in the image iterator are turned into `None`. If you need to see the
-    /// errors, use `Camera::images()`.
+    /// errors, use `Camera::images()`. If the camera skips corrupt images, the latest image that
+    /// isn't corrupt is returned.
     ///
     /// # Examples
     ///
@@ -204,12 +523,43 @@ impl Camera {
         if let Ok(images) = self.images() {
             let mut images = images.filter_map(|r| r.ok()).collect::<Vec<_>>();
             images.sort();
-            images.pop()
+            while let Some(image) = images.pop() {
+                if !self.skip_corrupt || !image.is_corrupt() {
+                    return Some(image);
+                }
+            }
+            None
         } else {
             None
         }
     }
 
+    /// Watches this camera's directory for new images.
+    ///
+    /// The returned iterator blocks until the next image arrives. Images that were already in the
+    /// directory are not returned. Only the camera's own directory is watched, even if the camera
+    /// searches subdirectories, so images that are moved into an archive aren't taken as new.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap();
+    /// let watch = camera.watch().unwrap();
+    /// ```
+    pub fn watch(&self) -> Result<Watch> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(WATCH_DELAY_MILLISECONDS))?;
+        watcher.watch(&self.path, RecursiveMode::NonRecursive)?;
+        Ok(Watch {
+            receiver: receiver,
+            extensions: self.extensions.clone(),
+            pattern: self.pattern.clone(),
+            datetime_policy: self.datetime_policy,
+            _watcher: watcher,
+        })
+    }
+
     /// Returns this camera's path.
     ///
     /// # Examples
@@ -228,25 +578,155 @@ impl Iterator for Images {
     type Item = Result<Image>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(result) = self.read_dir.next() {
-            match result {
-                Ok(dir_entry) => {
-                    if let Some(extension) = dir_entry.path().extension() {
+        loop {
+            let (result, depth) = match self.read_dirs.last_mut() {
+                Some(&mut (ref mut read_dir, depth)) => (read_dir.next(), depth),
+                None => return None,
+            };
+            let dir_entry = match result {
+                Some(Ok(dir_entry)) => dir_entry,
+                Some(Err(err)) => return Some(Err(err.into())),
+                None => {
+                    self.read_dirs.pop();
+                    continue;
+                }
+            };
+            let path = dir_entry.path();
+            let is_dir = match is_directory(&dir_entry, self.follow_symlinks) {
+                Ok(is_dir) => is_dir,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if is_dir {
+                if depth < self.max_depth {
+                    match self.visit(&path) {
+                        Ok(Some(read_dir)) => self.read_dirs.push((read_dir, depth + 1)),
+                        Ok(None) => {}
+                        Err(err) => return Some(Err(err)),
+                    }
+                }
+            } else if has_extension(&path, &self.extensions) {
+                match Image::with_policy(&path, &self.pattern, self.datetime_policy) {
+                    Ok(image) => {
+                        if self.contains(&image.datetime) {
+                            return Some(Ok(image));
+                        }
+                    }
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+        }
+    }
+}
+
+impl Images {
+    /// Only returns images taken at or after the start datetime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::Camera;
+    /// # fn main() {
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap();
+    /// let images = camera.images().unwrap().start(Utc.ymd(2017, 8, 7).and_hms(0, 0, 0));
+    /// assert_eq!(0, images.count());
+    /// # }
+    /// ```
+    pub fn start(mut self, start: DateTime<Utc>) -> Images {
+        self.start = Some(start);
+        self
+    }
+
+    /// Only returns images taken at or before the end datetime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::Camera;
+    /// # fn main() {
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap();
+    /// let images = camera.images().unwrap().end(Utc.ymd(2017, 8, 7).and_hms(0, 0, 0));
+    /// assert_eq!(1, images.count());
+    /// # }
+    /// ```
+    pub fn end(mut self, end: DateTime<Utc>) -> Images {
+        self.end = Some(end);
+        self
+    }
+
+    /// Collects the images, sorted by datetime.
+    ///
+    /// Returns the first error, if any image can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Order;
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap();
+    /// let images = camera.images().unwrap().sorted(Order::Descending).unwrap();
+    /// ```
+    pub fn sorted(self, order: Order) -> Result<Vec<Image>> {
+        let mut images = self.collect::<Result<Vec<_>>>()?;
+        match order {
+            Order::Ascending => images.sort(),
+            Order::Descending => images.sort_by(|a, b| b.cmp(a)),
+        }
+        Ok(images)
+    }
+
+    fn contains(&self, datetime: &DateTime<Utc>) -> bool {
+        self.start.map_or(true, |start| start <= *datetime) &&
+            self.end.map_or(true, |end| *datetime <= end)
+    }
+
+    /// Returns the directory's entries, or `None` if the directory has already been searched.
+    fn visit(&mut self, path: &Path) -> Result<Option<ReadDir>> {
+        if self.visited.insert(path.canonicalize()?) {
+            path.read_dir().map(Some).map_err(Error::from)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Iterator for Watch {
+    type Item = Result<Image>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.receiver.recv() {
+                Ok(DebouncedEvent::Create(path)) |
+                Ok(DebouncedEvent::Rename(_, path)) => {
+                    if let Some(extension) = path.extension() {
                         if self.extensions.iter().any(|lhs| lhs == extension) {
-                            return Some(Image::new(dir_entry.path()));
+                            return Some(Image::with_policy(
+                                path,
+                                &self.pattern,
+                                self.datetime_policy,
+                            ));
                         }
                     }
                 }
-                Err(err) => return Some(Err(err.into())),
+                Ok(DebouncedEvent::Error(err, _)) => return Some(Err(err.into())),
+                Ok(_) => {}
+                Err(_) => return None,
             }
         }
-        None
     }
 }
 
 impl Image {
     /// Creates a new image from the path, which is canonicalized.
     ///
+    /// The date and time are parsed from the filename with the default `Pattern`, falling back to
+    /// the image's EXIF metadata.
+    ///
     /// # Examples
     ///
     /// ```
@@ -259,24 +739,87 @@ impl Image {
     /// );
     /// ```
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Image> {
+        Image::with_pattern(path, &DEFAULT_PATTERN)
+    }
+
+    /// Creates a new image from the path, parsing the date and time from the filename with the
+    /// provided pattern and falling back to the image's EXIF metadata.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::Image;
+    /// # use glacio::camera::Pattern;
+    /// # fn main() {
+    /// let pattern = Pattern::new(r"_(?P<datetime>\d{8}_\d{4})\d{2}$", "%Y%m%d_%H%M").unwrap();
+    /// let image = Image::with_pattern("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg", &pattern)
+    ///     .unwrap();
+    /// assert_eq!(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0), image.datetime());
+    /// # }
+    /// ```
+    pub fn with_pattern<P: AsRef<Path>>(path: P, pattern: &Pattern) -> Result<Image> {
+        Image::with_policy(path, pattern, DatetimePolicy::default())
+    }
+
+    /// Creates a new image from the path, taking the date and time from the filename or the EXIF
+    /// metadata according to the policy.
+    ///
+    /// If both datetimes are available, the difference between the EXIF datetime and the filename
+    /// datetime is the image's clock drift. If neither is available, the error from parsing the
+    /// filename is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// # use glacio::camera::{DatetimePolicy, Pattern};
+    /// let image = Image::with_policy(
+    ///     "data/exif/STARDOT_20170806_152500.jpg",
+    ///     &Pattern::default(),
+    ///     DatetimePolicy::CrossCheck,
+    /// ).unwrap();
+    /// assert_eq!(150, image.clock_drift().unwrap().num_seconds());
+    /// ```
+    pub fn with_policy<P: AsRef<Path>>(
+        path: P,
+        pattern: &Pattern,
+        datetime_policy: DatetimePolicy,
+    ) -> Result<Image> {
         let path = path.as_ref().canonicalize()?;
-        if let Some(file_stem) = path.file_stem().and_then(|file_stem| file_stem.to_str()) {
-            if file_stem.len() <= 15 {
-                Err(Error::FileStemTooShort(file_stem.to_string()))
-            } else {
-                let (_, s) = file_stem.split_at(file_stem.len() - 15);
-                Utc.datetime_from_str(s, "%Y%m%d_%H%M%S")
-                    .map_err(Error::from)
-                    .map(|datetime| {
-                        Image {
-                            datetime: datetime,
-                            path: path.clone(),
-                        }
-                    })
-            }
+        let from_filename = match path.file_stem().and_then(|file_stem| file_stem.to_str()) {
+            Some(file_stem) => pattern.parse(file_stem),
+            None => Err(Error::NoFileStem(path.clone())),
+        };
+        let exif = if datetime_policy == DatetimePolicy::PreferFilename && from_filename.is_ok() {
+            None
         } else {
-            Err(Error::NoFileStem(path.clone()))
-        }
+            Exif::read(&path).ok()
+        };
+        let from_exif = exif.as_ref()
+            .and_then(|exif| exif.date_time_original)
+            .and_then(|datetime| pattern.localize(&datetime));
+        let clock_drift = match (&from_filename, from_exif) {
+            (&Ok(from_filename), Some(from_exif)) => Some(from_exif - from_filename),
+            _ => None,
+        };
+        let datetime = match (datetime_policy, from_exif) {
+            (DatetimePolicy::PreferExif, Some(from_exif)) => from_exif,
+            _ => {
+                match from_filename {
+                    Ok(from_filename) => from_filename,
+                    Err(err) => from_exif.ok_or(err)?,
+                }
+            }
+        };
+        Ok(Image {
+            datetime: datetime,
+            path: path,
+            exif: exif,
+            clock_drift: clock_drift,
+        })
     }
 
     /// Returns this image's local filesystem path.
@@ -312,70 +855,992 @@ impl Image {
     pub fn datetime(&self) -> DateTime<Utc> {
         self.datetime
     }
-}
 
-impl Ord for Image {
-    fn cmp(&self, other: &Image) -> Ordering {
-        self.datetime.cmp(&other.datetime)
+    /// Returns this image's EXIF metadata, if it was read.
+    ///
+    /// The EXIF metadata is only read if the datetime policy calls for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// # use glacio::camera::{DatetimePolicy, Pattern};
+    /// let path = "data/exif/STARDOT_20170806_152500.jpg";
+    /// assert!(Image::new(path).unwrap().exif().is_none());
+    /// let image = Image::with_policy(path, &Pattern::default(), DatetimePolicy::PreferExif)
+    ///     .unwrap();
+    /// assert_eq!("StarDot", image.exif().unwrap().make.as_ref().unwrap());
+    /// ```
+    pub fn exif(&self) -> Option<&Exif> {
+        self.exif.as_ref()
     }
-}
 
-impl Server {
-    /// Creates a new server, defaulting to our lidar.io url as the remote base url.
+    /// Returns how far ahead of the filename datetime the camera's clock was, according to the
+    /// EXIF metadata.
     ///
-    /// The server document root is canonicalized.
+    /// Only available if both the filename and the EXIF datetimes were read.
     ///
     /// # Examples
     ///
     /// ```
-    /// use std::path::Path;
-    /// # use glacio::camera::Server;
-    /// let server = Server::new("data").unwrap();
-    /// assert_eq!(Path::new("data").canonicalize().unwrap(), server.document_root());
+    /// # use glacio::Image;
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// assert_eq!(None, image.clock_drift());
     /// ```
-    pub fn new<P: AsRef<Path>>(document_root: P) -> Result<Server> {
-        Ok(Server {
-            document_root: document_root.as_ref().canonicalize()?,
-            base_url: Url::parse(DEFAULT_SERVER_BASE_URL).unwrap(),
-        })
+    pub fn clock_drift(&self) -> Option<chrono::Duration> {
+        self.clock_drift
     }
 
-    /// Returns the url for the provided image.
+    /// Returns the size of this image's file, in bytes.
+    ///
+    /// Like the other file properties, this is read from the filesystem on every call.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use glacio::camera::{Image, Server};
+    /// # use glacio::Image;
     /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
-    /// let server = Server::new("data").unwrap();
-    /// let url = server.url_for(&image).unwrap();
-    /// assert_eq!("http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
-    ///            url.as_str());
+    /// assert_eq!(98265, image.size().unwrap());
     /// ```
-    pub fn url_for(&self, image: &Image) -> Result<Url> {
-        let input = image.path().strip_prefix(&self.document_root)?;
-        self.base_url.join(&input.to_string_lossy()).map_err(
-            Error::from,
-        )
+    pub fn size(&self) -> Result<u64> {
+        fs::metadata(&self.path).map(|metadata| metadata.len()).map_err(Error::from)
     }
 
-    /// Returns this server's document root.
+    /// Returns this image's size in pixels, as read from its JPEG header.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use glacio::camera::Server;
-    /// let server = Server::new("data").unwrap();
-    /// let document_root = server.document_root();
-    /// assert!(document_root.is_absolute());
-    /// assert_eq!("data", document_root.file_name().unwrap());
+    /// # use glacio::Image;
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// let dimensions = image.dimensions().unwrap();
+    /// assert_eq!((1024, 768), (dimensions.width, dimensions.height));
     /// ```
-    pub fn document_root(&self) -> &Path {
-        &self.document_root
+    pub fn dimensions(&self) -> Result<Dimensions> {
+        jpeg::dimensions(&self.path).map_err(Error::from)
     }
-}
 
-#[cfg(test)]
+    /// Checks that this image's file is a complete JPEG that can be decoded.
+    ///
+    /// Returns the problem with the file if it isn't, e.g. if it was truncated in transmission.
+    /// The file is first checked with `glacio::jpeg::verify`, which is cheap, and then decoded to
+    /// catch garbled image data. Decoding reads the whole image, so this is slow for big images.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// image.verify().unwrap();
+    /// ```
+    pub fn verify(&self) -> Result<()> {
+        verify(&self.path)
+    }
+
+    /// Returns true if this image's file isn't a complete JPEG, or can't be read or decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// assert!(!image.is_corrupt());
+    /// ```
+    pub fn is_corrupt(&self) -> bool {
+        self.verify().is_err()
+    }
+
+    /// Returns the SHA-256 hash of this image's file, as a lowercase hex string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// let image = Image::new("data/exif/image.jpg").unwrap();
+    /// assert_eq!(64, image.checksum().unwrap().len());
+    /// ```
+    pub fn checksum(&self) -> Result<String> {
+        let mut file = File::open(&self.path)?;
+        let mut hasher = Sha256::default();
+        let mut buffer = [0; CHECKSUM_BUFFER_SIZE];
+        loop {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.input(&buffer[..n]);
+        }
+        Ok(
+            hasher
+                .result()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        )
+    }
+}
+
+impl Ord for Image {
+    fn cmp(&self, other: &Image) -> Ordering {
+        self.datetime.cmp(&other.datetime)
+    }
+}
+
+impl PartialOrd for Image {
+    fn partial_cmp(&self, other: &Image) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Default for DatetimePolicy {
+    fn default() -> DatetimePolicy {
+        DatetimePolicy::PreferFilename
+    }
+}
+
+impl Index {
+    /// Creates a new, empty index of the camera's images.
+    ///
+    /// Call `Index::refresh` to read the images.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// assert!(index.images().is_empty());
+    /// ```
+    pub fn new(camera: Camera) -> Index {
+        Index {
+            camera: camera,
+            directories: HashMap::new(),
+            images: Vec::new(),
+            properties: HashMap::new(),
+            corrupt_images: 0,
+        }
+    }
+
+    /// Brings the index up to date, reading any directories that have changed since the last
+    /// refresh.
+    ///
+    /// Directories that haven't changed cost one `stat` each. New and modified images are checked
+    /// with `glacio::jpeg::verify`, and images that were corrupt are checked again if their files
+    /// have changed, e.g. because they were still being written when they were first checked.
+    /// Images aren't decoded, see `Index::undecoded_latest_image`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// ```
+    pub fn refresh(&mut self) -> Result<()> {
+        let mut directories = HashMap::new();
+        let mut changed = false;
+        let mut scanned = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(self.camera.path.clone(), 0)];
+        while let Some((path, depth)) = stack.pop() {
+            let modified = match fs::metadata(&path) {
+                Ok(metadata) => metadata.modified()?,
+                // Subdirectories can be moved or removed out from under us.
+                Err(ref err) if depth > 0 && err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            if !visited.insert(path.canonicalize()?) {
+                continue;
+            }
+            let directory = match self.directories.remove(&path) {
+                Some(directory) => {
+                    if directory.is_fresh(modified) {
+                        directory
+                    } else {
+                        changed = true;
+                        scanned.insert(path.clone());
+                        self.scan(&path, depth, modified, Some(directory))?
+                    }
+                }
+                None => {
+                    changed = true;
+                    scanned.insert(path.clone());
+                    self.scan(&path, depth, modified, None)?
+                }
+            };
+            stack.extend(directory.subdirectories.iter().map(
+                |subdirectory| (subdirectory.clone(), depth + 1),
+            ));
+            directories.insert(path, directory);
+        }
+        // Anything left over is a directory that's gone.
+        changed = changed || !self.directories.is_empty();
+        self.directories = directories;
+        if changed {
+            self.images = self.directories
+                .values()
+                .flat_map(|directory| directory.images.iter().map(|&(_, ref image)| image.clone()))
+                .collect();
+            self.images.sort();
+            let mut old = mem::replace(&mut self.properties, HashMap::new());
+            for (path, directory) in &self.directories {
+                let was_scanned = scanned.contains(path);
+                for &(_, ref image) in &directory.images {
+                    let properties = match old.remove(image.path()) {
+                        Some(properties) => {
+                            if (!was_scanned && !properties.corrupt) ||
+                                properties.is_current(image.path())
+                            {
+                                properties
+                            } else {
+                                Properties::read(image.path())
+                            }
+                        }
+                        None => Properties::read(image.path()),
+                    };
+                    self.properties.insert(image.path().to_path_buf(), properties);
+                }
+            }
+        } else {
+            for (path, properties) in &mut self.properties {
+                if properties.corrupt && !properties.is_current(path) {
+                    *properties = Properties::read(path);
+                }
+            }
+        }
+        self.count_corrupt_images();
+        Ok(())
+    }
+
+    /// Returns all of the indexed images, oldest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let images = index.images();
+    /// ```
+    pub fn images(&self) -> &[Image] {
+        &self.images
+    }
+
+    /// Returns the indexed images taken within an inclusive range of datetimes, oldest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// # fn main() {
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let start = Utc.ymd(2017, 8, 7).and_hms(0, 0, 0);
+    /// assert!(index.range(Some(start), None).is_empty());
+    /// # }
+    /// ```
+    pub fn range(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> &[Image] {
+        let first = start.map_or(0, |start| self.partition(|datetime| datetime < start));
+        let last = end.map_or(self.images.len(), |end| {
+            self.partition(|datetime| datetime <= end)
+        });
+        if first < last {
+            &self.images[first..last]
+        } else {
+            &[]
+        }
+    }
+
+    /// Returns the indexed image taken at the datetime, if there is one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// # fn main() {
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// assert!(index.find(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0)).is_some());
+    /// # }
+    /// ```
+    pub fn find(&self, datetime: DateTime<Utc>) -> Option<&Image> {
+        self.images.get(self.partition(|d| d < datetime)).and_then(
+            |image| if image.datetime == datetime {
+                Some(image)
+            } else {
+                None
+            },
+        )
+    }
+
+    /// Returns the indexed image taken nearest to the datetime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{TimeZone, Utc};
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// # fn main() {
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let image = index.nearest(Utc.ymd(2017, 1, 1).and_hms(0, 0, 0)).unwrap();
+    /// assert_eq!(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0), image.datetime());
+    /// # }
+    /// ```
+    pub fn nearest(&self, datetime: DateTime<Utc>) -> Option<&Image> {
+        let i = self.partition(|d| d < datetime);
+        let before = if i > 0 { self.images.get(i - 1) } else { None };
+        match (before, self.images.get(i)) {
+            (Some(before), Some(after)) => {
+                if datetime.signed_duration_since(before.datetime) <=
+                    after.datetime.signed_duration_since(datetime)
+                {
+                    Some(before)
+                } else {
+                    Some(after)
+                }
+            }
+            (before, after) => before.or(after),
+        }
+    }
+
+    /// Returns the latest indexed image, skipping corrupt images if the camera is set to.
+    ///
+    /// Only images that have failed a check are skipped, so if the camera skips corrupt images the
+    /// latest image may have garbled data until it has been decoded. See
+    /// `Index::undecoded_latest_image`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let image = index.latest_image().unwrap();
+    /// ```
+    pub fn latest_image(&self) -> Option<&Image> {
+        self.images.iter().rev().find(|image| {
+            !self.camera.skip_corrupt || !self.is_corrupt(image)
+        })
+    }
+
+    /// Returns the latest image that needs decoding before `Index::latest_image` can be trusted.
+    ///
+    /// This is `None` if the camera doesn't skip corrupt images, or if the latest image that
+    /// isn't known to be corrupt has already been decoded. Otherwise, decode the returned image
+    /// with `Properties::decode`, record the result with `Index::update`, and ask again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::{Index, Properties};
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap().skip_corrupt(true);
+    /// let mut index = Index::new(camera);
+    /// index.refresh().unwrap();
+    /// let image = index.undecoded_latest_image().unwrap().clone();
+    /// index.update(&image, Properties::decode(image.path()));
+    /// assert!(index.undecoded_latest_image().is_none());
+    /// ```
+    pub fn undecoded_latest_image(&self) -> Option<&Image> {
+        if !self.camera.skip_corrupt {
+            return None;
+        }
+        self.latest_image().and_then(|image| {
+            if self.properties(image).map_or(
+                false,
+                |properties| properties.decoded,
+            )
+            {
+                None
+            } else {
+                Some(image)
+            }
+        })
+    }
+
+    /// Replaces the properties of an indexed image, e.g. with the result of `Properties::decode`.
+    ///
+    /// Returns false, and does nothing, if the image isn't in the index, e.g. because it was
+    /// removed by a refresh while it was being decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::{Index, Properties};
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let image = index.latest_image().unwrap().clone();
+    /// assert!(index.update(&image, Properties::decode(image.path())));
+    /// assert!(index.properties(&image).unwrap().is_decoded());
+    /// ```
+    pub fn update(&mut self, image: &Image, properties: Properties) -> bool {
+        let updated = match self.properties.get_mut(image.path()) {
+            Some(old) => {
+                *old = properties;
+                true
+            }
+            None => false,
+        };
+        self.count_corrupt_images();
+        updated
+    }
+
+    /// Returns the indexed image's properties, or `None` if the image isn't in the index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let image = index.latest_image().unwrap();
+    /// assert!(!index.properties(image).unwrap().is_corrupt());
+    /// ```
+    pub fn properties(&self, image: &Image) -> Option<&Properties> {
+        self.properties.get(image.path())
+    }
+
+    /// Returns true if the indexed image was corrupt when it was last checked.
+    ///
+    /// Images that aren't in the index aren't checked, and aren't corrupt.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// let image = index.latest_image().unwrap();
+    /// assert!(!index.is_corrupt(image));
+    /// ```
+    pub fn is_corrupt(&self, image: &Image) -> bool {
+        self.properties(image).map_or(false, |properties| properties.corrupt)
+    }
+
+    /// Returns the number of indexed images that were corrupt when they were last checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let mut index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// index.refresh().unwrap();
+    /// assert_eq!(0, index.corrupt_images());
+    /// ```
+    pub fn corrupt_images(&self) -> usize {
+        self.corrupt_images
+    }
+
+    /// Returns the indexed camera.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::Index;
+    /// let index = Index::new(Camera::new("data/ATLAS_CAM").unwrap());
+    /// let camera = index.camera();
+    /// ```
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn count_corrupt_images(&mut self) {
+        self.corrupt_images = self.properties
+            .values()
+            .filter(|properties| properties.corrupt)
+            .count();
+    }
+
+    /// Returns the number of images taken before the first datetime for which `is_before` is
+    /// false.
+    fn partition<F: Fn(DateTime<Utc>) -> bool>(&self, is_before: F) -> usize {
+        match self.images.binary_search_by(|image| if is_before(image.datetime) {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }) {
+            Ok(i) | Err(i) => i,
+        }
+    }
+
+    fn scan(
+        &self,
+        path: &Path,
+        depth: usize,
+        modified: SystemTime,
+        old: Option<Directory>,
+    ) -> Result<Directory> {
+        let scanned = SystemTime::now();
+        let mut old_images = old.map(|directory| {
+            directory.images.into_iter().collect::<HashMap<_, _>>()
+        }).unwrap_or_default();
+        let mut images = Vec::new();
+        let mut subdirectories = Vec::new();
+        for result in path.read_dir()? {
+            let dir_entry = result?;
+            let path = dir_entry.path();
+            if is_directory(&dir_entry, self.camera.follow_symlinks)? {
+                if depth < self.camera.max_depth {
+                    subdirectories.push(path);
+                }
+            } else if has_extension(&path, &self.camera.extensions) {
+                let image = match old_images.remove(&path) {
+                    Some(image) => image,
+                    None => {
+                        match Image::with_policy(
+                            &path,
+                            &self.camera.pattern,
+                            self.camera.datetime_policy,
+                        ) {
+                            Ok(image) => image,
+                            Err(_) => continue,
+                        }
+                    }
+                };
+                images.push((path, image));
+            }
+        }
+        Ok(Directory {
+            modified: modified,
+            scanned: scanned,
+            images: images,
+            subdirectories: subdirectories,
+        })
+    }
+}
+
+impl Directory {
+    /// Returns true if the directory hasn't changed since it was scanned.
+    fn is_fresh(&self, modified: SystemTime) -> bool {
+        is_unchanged(self.modified, self.scanned, modified)
+    }
+}
+
+impl Properties {
+    /// Reads the properties of an image file, without decoding it.
+    ///
+    /// A file that can't be read, or that fails `glacio::jpeg::verify`, is corrupt. Only the
+    /// file's headers and last two bytes are read, so this is cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Properties;
+    /// let properties = Properties::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg");
+    /// assert!(!properties.is_corrupt());
+    /// let properties = Properties::read("data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial");
+    /// assert!(properties.is_corrupt());
+    /// ```
+    pub fn read<P: AsRef<Path>>(path: P) -> Properties {
+        let path = path.as_ref();
+        let read = SystemTime::now();
+        let metadata = fs::metadata(path).ok();
+        Properties {
+            modified: metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+            read: read,
+            size: metadata.map(|metadata| metadata.len()),
+            dimensions: jpeg::dimensions(path).ok(),
+            corrupt: jpeg::verify(path).is_err(),
+            decoded: false,
+        }
+    }
+
+    /// Reads the properties of an image file and decodes it, to catch garbled image data.
+    ///
+    /// Decoding reads the whole image, so this is slow for big images. A file that can't be
+    /// decoded is corrupt.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Properties;
+    /// let properties = Properties::decode("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg");
+    /// assert!(properties.is_decoded());
+    /// assert!(!properties.is_corrupt());
+    /// ```
+    pub fn decode<P: AsRef<Path>>(path: P) -> Properties {
+        let path = path.as_ref();
+        let properties = Properties::read(path);
+        Properties {
+            corrupt: properties.corrupt || verify(path).is_err(),
+            decoded: true,
+            ..properties
+        }
+    }
+
+    /// Returns the size of the image file in bytes, or `None` if the file couldn't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Properties;
+    /// let properties = Properties::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg");
+    /// assert_eq!(Some(98265), properties.size());
+    /// ```
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Returns the image's size in pixels, or `None` if its JPEG header couldn't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Properties;
+    /// let properties = Properties::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg");
+    /// assert_eq!(1024, properties.dimensions().unwrap().width);
+    /// ```
+    pub fn dimensions(&self) -> Option<Dimensions> {
+        self.dimensions
+    }
+
+    /// Returns true if the image file was decoded when it was read, not just checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Properties;
+    /// let properties = Properties::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg");
+    /// assert!(!properties.is_decoded());
+    /// ```
+    pub fn is_decoded(&self) -> bool {
+        self.decoded
+    }
+
+    /// Returns true if the image file was corrupt when it was read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Properties;
+    /// let properties = Properties::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg");
+    /// assert!(!properties.is_corrupt());
+    /// ```
+    pub fn is_corrupt(&self) -> bool {
+        self.corrupt
+    }
+
+    /// Returns true if the file at the path hasn't changed since these properties were read.
+    fn is_current(&self, path: &Path) -> bool {
+        match (self.modified, fs::metadata(path).and_then(|metadata| metadata.modified())) {
+            (Some(recorded), Ok(modified)) => is_unchanged(recorded, self.read, modified),
+            _ => false,
+        }
+    }
+}
+
+impl Sampling {
+    /// Thins out images that are already sorted, in either order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Camera;
+    /// # use glacio::camera::{Order, Sampling};
+    /// let camera = Camera::new("data/ATLAS_CAM").unwrap();
+    /// let images = camera.images().unwrap().sorted(Order::Ascending).unwrap();
+    /// let images = Sampling::Every(2).sample(images);
+    /// assert_eq!(1, images.len());
+    /// ```
+    pub fn sample<B: Borrow<Image>>(&self, images: Vec<B>) -> Vec<B> {
+        match *self {
+            Sampling::Every(n) => {
+                images
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i % n.max(1) == 0)
+                    .map(|(_, image)| image)
+                    .collect()
+            }
+            Sampling::Interval(interval) => {
+                let mut sampled: Vec<B> = Vec::new();
+                for image in images {
+                    let keep = match sampled.last() {
+                        Some(last) => {
+                            let duration = image.borrow().datetime.signed_duration_since(
+                                last.borrow().datetime,
+                            );
+                            duration >= interval || -duration >= interval
+                        }
+                        None => true,
+                    };
+                    if keep {
+                        sampled.push(image);
+                    }
+                }
+                sampled
+            }
+        }
+    }
+}
+
+impl Pattern {
+    /// Creates a new pattern from a regular expression and a chrono format string.
+    ///
+    /// The regular expression must have a `datetime` capture group. The pattern is in UTC until
+    /// an offset is set with `Pattern::offset`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Pattern;
+    /// let pattern = Pattern::new(r"(?P<datetime>\d{4}-\d{2}-\d{2}_\d{4})", "%Y-%m-%d_%H%M")
+    ///     .unwrap();
+    /// assert!(Pattern::new(r"\d{4}-\d{2}-\d{2}_\d{4}", "%Y-%m-%d_%H%M").is_err());
+    /// ```
+    pub fn new(regex: &str, format: &str) -> Result<Pattern> {
+        let regex = Regex::new(regex)?;
+        if !regex.capture_names().any(|name| name == Some(DATETIME_GROUP)) {
+            return Err(Error::MissingDatetimeGroup(regex.as_str().to_string()));
+        }
+        Ok(Pattern {
+            regex: regex,
+            format: format.to_string(),
+            offset: FixedOffset::east(0),
+        })
+    }
+
+    /// Sets the offset from UTC of the filename datetimes, e.g. for cameras that are set to local
+    /// time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{FixedOffset, TimeZone, Utc};
+    /// # use glacio::camera::Pattern;
+    /// # fn main() {
+    /// let pattern = Pattern::new(r"(?P<datetime>\d{8}_\d{6})$", "%Y%m%d_%H%M%S")
+    ///     .unwrap()
+    ///     .offset(FixedOffset::west(3 * 3600));
+    /// assert_eq!(
+    ///     Utc.ymd(2017, 8, 6).and_hms(18, 25, 0),
+    ///     pattern.parse("ATLAS_CAM_20170806_152500").unwrap()
+    /// );
+    /// # }
+    /// ```
+    pub fn offset(mut self, offset: FixedOffset) -> Pattern {
+        self.offset = offset;
+        self
+    }
+
+    /// Parses the date and time out of a file stem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Pattern;
+    /// let pattern = Pattern::new(r"^(?P<datetime>\d+)$", "%s").unwrap();
+    /// let datetime = pattern.parse("1502033100").unwrap();
+    /// assert_eq!("2017-08-06T15:25:00+00:00", datetime.to_rfc3339());
+    /// ```
+    pub fn parse(&self, file_stem: &str) -> Result<DateTime<Utc>> {
+        let s = match self.regex.captures(file_stem).and_then(
+            |captures| captures.name(DATETIME_GROUP),
+        ) {
+            Some(m) => m.as_str(),
+            None => return Err(Error::PatternMismatch(file_stem.to_string())),
+        };
+        let datetime = NaiveDateTime::parse_from_str(s, &self.format)?;
+        self.localize(&datetime).ok_or_else(|| {
+            Error::PatternMismatch(file_stem.to_string())
+        })
+    }
+
+    fn localize(&self, datetime: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        self.offset.from_local_datetime(datetime).single().map(
+            |datetime| datetime.with_timezone(&Utc),
+        )
+    }
+}
+
+impl Default for Pattern {
+    fn default() -> Pattern {
+        DEFAULT_PATTERN.clone()
+    }
+}
+
+impl Server {
+    /// Creates a new server, defaulting to our lidar.io url as the remote base url.
+    ///
+    /// The server document root is canonicalized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// # use glacio::camera::Server;
+    /// let server = Server::new("data").unwrap();
+    /// assert_eq!(Path::new("data").canonicalize().unwrap(), server.document_root());
+    /// ```
+    pub fn new<P: AsRef<Path>>(document_root: P) -> Result<Server> {
+        Server::with_base_url(document_root, DEFAULT_SERVER_BASE_URL)
+    }
+
+    /// Creates a new server with the provided remote base url.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::{Image, Server};
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// let server = Server::with_base_url("data", "https://example.com/images").unwrap();
+    /// let url = server.url_for(&image).unwrap();
+    /// assert_eq!("https://example.com/images/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+    ///            url.as_str());
+    /// ```
+    pub fn with_base_url<P: AsRef<Path>>(document_root: P, base_url: &str) -> Result<Server> {
+        let mut server = Server { roots: Vec::new() };
+        server.add_document_root(document_root, base_url)?;
+        Ok(server)
+    }
+
+    /// Adds another document root, with its own remote base url.
+    ///
+    /// When document roots are nested, images are served from the deepest root that holds them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::{Image, Server};
+    /// let path = "data/HEL_BERGCAM3/StarDot1/HEL_BERGCAM3_StarDot1_20170825_120000.jpg";
+    /// let image = Image::new(path).unwrap();
+    /// let mut server = Server::new("data").unwrap();
+    /// server.add_document_root("data/HEL_BERGCAM3", "https://example.com").unwrap();
+    /// let url = server.url_for(&image).unwrap();
+    /// assert_eq!("https://example.com/StarDot1/HEL_BERGCAM3_StarDot1_20170825_120000.jpg",
+    ///            url.as_str());
+    /// ```
+    pub fn add_document_root<P: AsRef<Path>>(
+        &mut self,
+        document_root: P,
+        base_url: &str,
+    ) -> Result<()> {
+        let mut base_url = Url::parse(base_url)?;
+        // Without a trailing slash, joining would replace the last segment of the base url.
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        self.roots.push(DocumentRoot {
+            path: document_root.as_ref().canonicalize()?,
+            base_url: base_url,
+        });
+        Ok(())
+    }
+
+    /// Returns the url for the provided image.
+    ///
+    /// Returns an error if the image isn't under any of the server's document roots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::{Image, Server};
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// let server = Server::new("data").unwrap();
+    /// let url = server.url_for(&image).unwrap();
+    /// assert_eq!("http://iridiumcam.lidar.io/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+    ///            url.as_str());
+    /// ```
+    pub fn url_for(&self, image: &Image) -> Result<Url> {
+        let path = image.path();
+        let root = self.roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count());
+        match root {
+            Some(root) => {
+                let input = path.strip_prefix(&root.path).unwrap();
+                root.base_url.join(&input.to_string_lossy()).map_err(
+                    Error::from,
+                )
+            }
+            None => Err(Error::OutsideDocumentRoots(path.to_path_buf())),
+        }
+    }
+
+    /// Returns this server's first document root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::camera::Server;
+    /// let server = Server::new("data").unwrap();
+    /// let document_root = server.document_root();
+    /// assert!(document_root.is_absolute());
+    /// assert_eq!("data", document_root.file_name().unwrap());
+    /// ```
+    pub fn document_root(&self) -> &Path {
+        &self.roots[0].path
+    }
+}
+
+/// Returns true if a file or directory that had the `recorded` modification time when it was read
+/// at `read` still has it.
+///
+/// If it was read within the resolution of its modification time, it could have changed again
+/// without its modification time changing, so it's taken to have changed.
+fn is_unchanged(recorded: SystemTime, read: SystemTime, modified: SystemTime) -> bool {
+    recorded == modified && is_settled(read, modified)
+}
+
+/// Returns true if `read` is far enough after `modified` that a later write would have changed the
+/// modification time.
+pub(crate) fn is_settled(read: SystemTime, modified: SystemTime) -> bool {
+    read.duration_since(modified)
+        .map(|duration| {
+            duration >= Duration::from_secs(MODIFIED_RESOLUTION_SECONDS)
+        })
+        .unwrap_or(false)
+}
+
+fn verify(path: &Path) -> Result<()> {
+    jpeg::verify(path)?;
+    image::load(BufReader::new(File::open(path)?), ImageFormat::JPEG)?;
+    Ok(())
+}
+
+fn is_directory(dir_entry: &DirEntry, follow_symlinks: bool) -> io::Result<bool> {
+    let file_type = dir_entry.file_type()?;
+    Ok(if file_type.is_symlink() {
+        follow_symlinks &&
+            fs::metadata(dir_entry.path())
+                .map(|metadata| metadata.is_dir())
+                .unwrap_or(false)
+    } else {
+        file_type.is_dir()
+    })
+}
+
+fn has_extension(path: &Path, extensions: &[OsString]) -> bool {
+    path.extension().map_or(false, |extension| {
+        extensions.iter().any(|lhs| lhs == extension)
+    })
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -395,6 +1860,427 @@ mod tests {
         assert_eq!(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0), image.datetime);
     }
 
+    #[test]
+    fn filtered_images() {
+        use chrono::Timelike;
+        use std::env;
+
+        let path = env::temp_dir().join("glacio-filtered-images");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        for hour in 0..6 {
+            File::create(path.join(format!("ATLAS_CAM_20170806_{:02}0000.jpg", hour))).unwrap();
+        }
+        let camera = Camera::new(&path).unwrap();
+        let hours = |images: Vec<Image>| -> Vec<u32> {
+            images.iter().map(|image| image.datetime().hour()).collect()
+        };
+
+        let images = camera
+            .images()
+            .unwrap()
+            .start(Utc.ymd(2017, 8, 6).and_hms(1, 0, 0))
+            .end(Utc.ymd(2017, 8, 6).and_hms(4, 0, 0))
+            .sorted(Order::Ascending)
+            .unwrap();
+        assert_eq!(vec![1, 2, 3, 4], hours(images));
+
+        let images = camera.images().unwrap().sorted(Order::Descending).unwrap();
+        assert_eq!(vec![5, 4, 3, 2, 1, 0], hours(images));
+
+        let images = camera.images().unwrap().sorted(Order::Descending).unwrap();
+        assert_eq!(vec![5, 3, 1], hours(Sampling::Every(2).sample(images)));
+
+        let images = camera.images().unwrap().sorted(Order::Ascending).unwrap();
+        let sampling = Sampling::Interval(chrono::Duration::minutes(150));
+        assert_eq!(vec![0, 3], hours(sampling.sample(images)));
+
+        let images = camera.images().unwrap().sorted(Order::Ascending).unwrap();
+        assert_eq!(6, Sampling::Every(0).sample(images).len());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn index() {
+        use std::env;
+
+        let path = env::temp_dir().join("glacio-camera-index");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("2017")).unwrap();
+        for hour in &[3, 1, 2] {
+            File::create(path.join(format!("ATLAS_CAM_20170806_{:02}0000.jpg", hour))).unwrap();
+        }
+        File::create(path.join("ATLAS_CAM_latest.jpg")).unwrap();
+        File::create(path.join("2017/ATLAS_CAM_20170805_000000.jpg")).unwrap();
+        let mut index = Index::new(Camera::new(&path).unwrap().max_depth(1));
+        index.refresh().unwrap();
+        let datetimes = |images: &[Image]| -> Vec<DateTime<Utc>> {
+            images.iter().map(|image| image.datetime()).collect()
+        };
+        let datetime = |day, hour, minute| Utc.ymd(2017, 8, day).and_hms(hour, minute, 0);
+        assert_eq!(
+            vec![
+                datetime(5, 0, 0),
+                datetime(6, 1, 0),
+                datetime(6, 2, 0),
+                datetime(6, 3, 0),
+            ],
+            datetimes(index.images())
+        );
+        assert_eq!(
+            vec![datetime(6, 1, 0), datetime(6, 2, 0)],
+            datetimes(index.range(Some(datetime(6, 0, 30)), Some(datetime(6, 2, 0))))
+        );
+        assert!(index.range(Some(datetime(7, 0, 0)), None).is_empty());
+        assert!(index.range(Some(datetime(6, 3, 0)), Some(datetime(6, 1, 0))).is_empty());
+        assert!(index.find(datetime(6, 2, 0)).is_some());
+        assert!(index.find(datetime(6, 2, 30)).is_none());
+        assert_eq!(datetime(6, 2, 0), index.nearest(datetime(6, 2, 30)).unwrap().datetime());
+        assert_eq!(datetime(6, 3, 0), index.nearest(datetime(6, 2, 31)).unwrap().datetime());
+        assert_eq!(datetime(6, 3, 0), index.nearest(datetime(9, 0, 0)).unwrap().datetime());
+        assert_eq!(datetime(6, 3, 0), index.latest_image().unwrap().datetime());
+
+        File::create(path.join("ATLAS_CAM_20170806_040000.jpg")).unwrap();
+        fs::remove_dir_all(path.join("2017")).unwrap();
+        index.refresh().unwrap();
+        assert_eq!(
+            vec![
+                datetime(6, 1, 0),
+                datetime(6, 2, 0),
+                datetime(6, 3, 0),
+                datetime(6, 4, 0),
+            ],
+            datetimes(index.images())
+        );
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn index_corrupt_images() {
+        use std::env;
+
+        let path = env::temp_dir().join("glacio-camera-index-corrupt-images");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let complete = "data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg";
+        fs::copy(complete, path.join("ATLAS_CAM_20170806_152500.jpg")).unwrap();
+        let latest = path.join("ATLAS_CAM_20170806_182500.jpg");
+        fs::copy("data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial", &latest).unwrap();
+        let mut index = Index::new(Camera::new(&path).unwrap().skip_corrupt(true));
+        index.refresh().unwrap();
+        assert_eq!(1, index.corrupt_images());
+        let datetime = |hour| Utc.ymd(2017, 8, 6).and_hms(hour, 25, 0);
+        assert_eq!(datetime(15), index.latest_image().unwrap().datetime());
+        assert!(index.is_corrupt(index.find(datetime(18)).unwrap()));
+
+        // The rest of the image arrives without a new file in the directory.
+        fs::copy(complete, &latest).unwrap();
+        index.refresh().unwrap();
+        assert_eq!(0, index.corrupt_images());
+        assert_eq!(datetime(18), index.latest_image().unwrap().datetime());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn fresh_directory() {
+        let modified = SystemTime::now();
+        let directory = |scanned| {
+            Directory {
+                modified: modified,
+                scanned: scanned,
+                images: Vec::new(),
+                subdirectories: Vec::new(),
+            }
+        };
+        let later = modified + Duration::from_secs(MODIFIED_RESOLUTION_SECONDS);
+        assert!(directory(later).is_fresh(modified));
+        assert!(!directory(later).is_fresh(later));
+        assert!(!directory(modified).is_fresh(modified));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recursive_images() {
+        use std::{env, fs};
+        use std::os::unix::fs::symlink;
+
+        let path = env::temp_dir().join("glacio-camera-recursive");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("camera/2017/08")).unwrap();
+        fs::create_dir_all(path.join("elsewhere")).unwrap();
+        for name in &[
+            "camera/ATLAS_CAM_20170806_152500.jpg",
+            "camera/2017/ATLAS_CAM_20170701_000000.jpg",
+            "camera/2017/08/ATLAS_CAM_20170801_000000.jpg",
+            "camera/2017/08/ATLAS_CAM_20170802_000000.jpg",
+            "elsewhere/ATLAS_CAM_20160101_000000.jpg",
+        ]
+        {
+            fs::copy(
+                "data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+                path.join(name),
+            ).unwrap();
+        }
+        symlink(path.join("elsewhere"), path.join("camera/elsewhere")).unwrap();
+        symlink(path.join("camera/2017"), path.join("camera/2017/again")).unwrap();
+
+        let count = |max_depth, follow_symlinks| {
+            Camera::new(path.join("camera"))
+                .unwrap()
+                .max_depth(max_depth)
+                .follow_symlinks(follow_symlinks)
+                .images()
+                .unwrap()
+                .map(|result| result.unwrap())
+                .count()
+        };
+        assert_eq!(1, count(0, false));
+        assert_eq!(2, count(1, false));
+        assert_eq!(4, count(2, false));
+        assert_eq!(4, count(10, false));
+        assert_eq!(3, count(1, true));
+        assert_eq!(5, count(2, true));
+        assert_eq!(5, count(10, true));
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn pattern() {
+        use chrono::FixedOffset;
+
+        let pattern = Pattern::default();
+        assert_eq!(
+            Utc.ymd(2017, 8, 6).and_hms(15, 25, 0),
+            pattern.parse("20170806_152500").unwrap()
+        );
+        assert!(pattern.parse("ATLAS_CAM").is_err());
+
+        let pattern = Pattern::new(r"^CAM_(?P<datetime>\d{4}-\d{2}-\d{2}_\d{4})$", "%Y-%m-%d_%H%M")
+            .unwrap()
+            .offset(FixedOffset::west(2 * 3600));
+        assert_eq!(
+            Utc.ymd(2017, 8, 6).and_hms(17, 25, 0),
+            pattern.parse("CAM_2017-08-06_1525").unwrap()
+        );
+        assert!(pattern.parse("CAM_20170806_152500").is_err());
+        assert!(pattern.parse("CAM_2017-13-06_1525").is_err());
+
+        assert!(Pattern::new(r"(?P<time>\d+)", "%s").is_err());
+        assert!(Pattern::new(r"(?P<datetime>\d+", "%s").is_err());
+    }
+
+    #[test]
+    fn camera_images_with_pattern() {
+        use std::{env, fs};
+
+        let path = env::temp_dir().join("glacio-camera-pattern");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        for name in &["1502033100.jpg", "1502036700.jpg"] {
+            fs::copy(
+                "data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+                path.join(name),
+            ).unwrap();
+        }
+        let camera = Camera::new(&path).unwrap().pattern(
+            Pattern::new(r"^(?P<datetime>\d+)$", "%s").unwrap(),
+        );
+        let mut images = camera
+            .images()
+            .unwrap()
+            .map(|result| result.unwrap().datetime())
+            .collect::<Vec<_>>();
+        images.sort();
+        assert_eq!(
+            vec![
+                Utc.ymd(2017, 8, 6).and_hms(15, 25, 0),
+                Utc.ymd(2017, 8, 6).and_hms(16, 25, 0),
+            ],
+            images
+        );
+        assert!(Camera::new(&path).unwrap().latest_image().is_none());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn datetime_policy() {
+        let pattern = Pattern::default();
+        let image = |path, datetime_policy| {
+            Image::with_policy(path, &pattern, datetime_policy).unwrap()
+        };
+
+        let path = "data/exif/STARDOT_20170806_152500.jpg";
+        let from_filename = Utc.ymd(2017, 8, 6).and_hms(15, 25, 0);
+        let from_exif = Utc.ymd(2017, 8, 6).and_hms(15, 27, 30);
+        let prefer_filename = image(path, DatetimePolicy::PreferFilename);
+        assert_eq!(from_filename, prefer_filename.datetime());
+        assert_eq!(None, prefer_filename.exif());
+        assert_eq!(None, prefer_filename.clock_drift());
+        let prefer_exif = image(path, DatetimePolicy::PreferExif);
+        assert_eq!(from_exif, prefer_exif.datetime());
+        assert_eq!(
+            "SD0123456",
+            prefer_exif.exif().unwrap().serial_number.as_ref().unwrap()
+        );
+        assert_eq!(Some(chrono::Duration::seconds(150)), prefer_exif.clock_drift());
+        let cross_check = image(path, DatetimePolicy::CrossCheck);
+        assert_eq!(from_filename, cross_check.datetime());
+        assert_eq!(Some(chrono::Duration::seconds(150)), cross_check.clock_drift());
+
+        let path = "data/exif/image.jpg";
+        let from_exif = Utc.ymd(2017, 8, 6).and_hms(16, 25, 0);
+        for &datetime_policy in &[
+            DatetimePolicy::PreferFilename,
+            DatetimePolicy::PreferExif,
+            DatetimePolicy::CrossCheck,
+        ]
+        {
+            let image = image(path, datetime_policy);
+            assert_eq!(from_exif, image.datetime());
+            assert_eq!(None, image.clock_drift());
+        }
+        let pattern = Pattern::default().offset(FixedOffset::west(2 * 3600));
+        assert_eq!(
+            Utc.ymd(2017, 8, 6).and_hms(18, 25, 0),
+            Image::with_pattern(path, &pattern).unwrap().datetime()
+        );
+
+        assert!(Image::new("data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial").is_err());
+        assert!(
+            Image::with_policy(
+                "data/exif/notafile.jpg",
+                &Pattern::default(),
+                DatetimePolicy::PreferExif,
+            ).is_err()
+        );
+    }
+
+    #[test]
+    fn file_properties() {
+        let image = Image::new("data/exif/image.jpg").unwrap();
+        assert_eq!(245, image.size().unwrap());
+        assert_eq!(
+            Dimensions {
+                width: 8,
+                height: 8,
+            },
+            image.dimensions().unwrap()
+        );
+        assert_eq!(
+            "350b1bb2ca8d4009107e27b36668c60db5c353d9ebd3397c35a45101caa63986",
+            image.checksum().unwrap()
+        );
+    }
+
+    #[test]
+    fn skip_corrupt() {
+        use std::{env, fs};
+
+        let path = env::temp_dir().join("glacio-camera-skip-corrupt");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        fs::copy(
+            "data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            path.join("ATLAS_CAM_20170806_152500.jpg"),
+        ).unwrap();
+        fs::copy(
+            "data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial",
+            path.join("ATLAS_CAM_20170806_182500.jpg"),
+        ).unwrap();
+        let camera = Camera::new(&path).unwrap();
+        let image = camera.latest_image().unwrap();
+        assert_eq!(Utc.ymd(2017, 8, 6).and_hms(18, 25, 0), image.datetime());
+        assert!(image.is_corrupt());
+        assert!(image.verify().is_err());
+        let image = camera.skip_corrupt(true).latest_image().unwrap();
+        assert_eq!(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0), image.datetime());
+        assert!(!image.is_corrupt());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn garbled_image() {
+        use std::{env, fs};
+        use std::io::Write;
+
+        let path = env::temp_dir().join("glacio-camera-garbled-image");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let file = path.join("ATLAS_CAM_20170806_152500.jpg");
+        File::create(&file).unwrap().write_all(&garbled()).unwrap();
+        assert!(jpeg::verify(&file).is_ok());
+        let image = Image::new(&file).unwrap();
+        assert!(image.verify().is_err());
+        assert!(image.is_corrupt());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn index_decodes_latest_image() {
+        use filetime::{self, FileTime};
+        use std::{env, fs};
+        use std::io::Write;
+
+        let path = env::temp_dir().join("glacio-camera-index-decodes-latest-image");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        fs::copy(
+            "data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            path.join("ATLAS_CAM_20170806_152500.jpg"),
+        ).unwrap();
+        File::create(path.join("ATLAS_CAM_20170806_182500.jpg"))
+            .unwrap()
+            .write_all(&garbled())
+            .unwrap();
+        // Files that were just written might still be changing, and are read again on refresh.
+        for result in path.read_dir().unwrap() {
+            let time = FileTime::from_seconds_since_1970(1_500_000_000, 0);
+            filetime::set_file_times(result.unwrap().path(), time, time).unwrap();
+        }
+        let datetime = |hour| Utc.ymd(2017, 8, 6).and_hms(hour, 25, 0);
+
+        let mut index = Index::new(Camera::new(&path).unwrap());
+        index.refresh().unwrap();
+        assert!(index.undecoded_latest_image().is_none());
+
+        let mut index = Index::new(Camera::new(&path).unwrap().skip_corrupt(true));
+        index.refresh().unwrap();
+        // Refreshing only checks the structure of each file, which the garbled image passes.
+        assert_eq!(0, index.corrupt_images());
+        assert!(index.properties.values().all(|properties| !properties.decoded));
+        assert_eq!(datetime(18), index.latest_image().unwrap().datetime());
+        let mut decoded = Vec::new();
+        while let Some(image) = index.undecoded_latest_image().cloned() {
+            decoded.push(image.datetime());
+            assert!(index.update(&image, Properties::decode(image.path())));
+        }
+        assert_eq!(vec![datetime(18), datetime(15)], decoded);
+        assert_eq!(1, index.corrupt_images());
+        assert_eq!(datetime(15), index.latest_image().unwrap().datetime());
+
+        // Decoded properties are kept across refreshes.
+        index.refresh().unwrap();
+        assert!(index.undecoded_latest_image().is_none());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn watch() {
+        use std::{env, fs};
+
+        let path = env::temp_dir().join("glacio-camera-watch");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let camera = Camera::new(&path).unwrap();
+        let mut watch = camera.watch().unwrap();
+        fs::copy(
+            "data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg",
+            path.join("ATLAS_CAM_20170806_152500.jpg"),
+        ).unwrap();
+        let image = watch.next().unwrap().unwrap();
+        assert_eq!(Utc.ymd(2017, 8, 6).and_hms(15, 25, 0), image.datetime);
+    }
+
     #[test]
     fn server_url() {
         let server = Server::new("data").unwrap();
@@ -419,6 +2305,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn server_document_roots() {
+        let mut server = Server::with_base_url("data/ATLAS_CAM", "https://example.com/atlas")
+            .unwrap();
+        let camera = Camera::new("data/ATLAS_CAM").unwrap();
+        let image = camera.images().unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            "https://example.com/atlas/ATLAS_CAM_20170806_152500.jpg",
+            server.url_for(&image).unwrap().as_str()
+        );
+
+        let camera = Camera::new("data/HEL_BERGCAM3/StarDot1").unwrap();
+        let image = camera.images().unwrap().next().unwrap().unwrap();
+        match server.url_for(&image).unwrap_err() {
+            Error::OutsideDocumentRoots(path) => assert_eq!(image.path(), path),
+            err => panic!("Unexpected error: {}", err),
+        }
+        server
+            .add_document_root("data/HEL_BERGCAM3", "https://example.com/bergcam/")
+            .unwrap();
+        assert_eq!(
+            "https://example.com/bergcam/StarDot1/HEL_BERGCAM3_StarDot1_20170825_120000.jpg",
+            server.url_for(&image).unwrap().as_str()
+        );
+
+        assert!(server.add_document_root("data", "not a url").is_err());
+        assert!(server.add_document_root("notadirectory", "https://example.com").is_err());
+    }
+
     #[test]
     fn server_url_mixing_absolute_and_relative() {
         let server = Server::new("data").unwrap();
@@ -430,4 +2345,26 @@ mod tests {
             url.as_str()
         );
     }
+
+    fn garbled() -> Vec<u8> {
+        // All-ones bits aren't a valid Huffman code, so overwriting the start of the image data
+        // with stuffed 0xFF bytes leaves a complete file that only fails once it's decoded.
+        let mut bytes = Vec::new();
+        File::open("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        let start_of_scan = bytes
+            .windows(2)
+            .position(|window| window == [0xFF, 0xDA])
+            .unwrap();
+        let high = bytes[start_of_scan + 2] as usize;
+        let low = bytes[start_of_scan + 3] as usize;
+        let data = start_of_scan + 2 + ((high << 8) | low);
+        for i in 0..500 {
+            bytes[data + 2 * i] = 0xFF;
+            bytes[data + 2 * i + 1] = 0x00;
+        }
+        bytes
+    }
 }
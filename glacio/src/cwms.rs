@@ -0,0 +1,786 @@
+//! Import timeseries exported from the CWMS server.
+//!
+//! Greg Hanlon's CWMS server holds the weather station timeseries that don't come to us as
+//! station data files. CWMS exports them as text, in one of two layouts. CSV exports have a
+//! column per timeseries (named by its CWMS timeseries id), each optionally followed by a
+//! `Quality` column:
+//!
+//! ```text
+//! # Time zone: EST
+//! Date/Time,HEL_WX.Temp-Air.Inst.1Hour.0.Raw,Quality,HEL_WX.%-Humidity.Inst.1Hour.0.Raw
+//! 2017-08-25 11:00:00,5.41,3,73.0
+//! ```
+//!
+//! DSS-style dumps have a block for each timeseries, headed by its DSS pathname:
+//!
+//! ```text
+//! /HELHEIM/HEL_WX/SPEED-WIND/01AUG2017/1HOUR/RAW/
+//! Time zone: GMT
+//! 25Aug2017 1600, 3.20
+//! 25Aug2017 2400, 2.75
+//! ```
+//!
+//! Times are in the export's time zone (UTC if none is given), and DSS's `2400` is midnight at the
+//! end of the day. Values of -901 or -902, empty values, and values with the missing or rejected
+//! quality flags are all treated as missing. Values are taken to already be in the units of
+//! `weather::Observation`.
+//!
+//! The `Importer` maps each timeseries' location to a station and its parameter to an observation
+//! field, and appends the observations to a TOA5 file in the station's data directory, so that
+//! they can be read with `weather::DatSource` (the table name is `CWMS`). Imports are merged into
+//! the file: new observations are added, and values that are missing from observations already in
+//! the file are filled in, so parameters that arrive in separate exports aren't lost, and the same
+//! (or overlapping) exports can be imported over and over.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::{env, fs};
+//! use glacio::cwms::Importer;
+//! # let path = env::temp_dir().join("glacio-cwms-doctest");
+//! # let _ = fs::remove_dir_all(&path);
+//! let mut importer = Importer::new();
+//! importer.station("HEL_WX", "HEL_WX", &path);
+//! let summary = importer.import("data/cwms/HEL_WX.csv").unwrap();
+//! assert_eq!(3, summary.imported);
+//! ```
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use std::{error, io, result};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::Entry;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::num::{ParseFloatError, ParseIntError};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use weather::{self, DatSource, Observation};
+
+/// The datetime formats found in CWMS exports.
+const DATETIME_FORMATS: &'static [&'static str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%d%b%Y %H%M",
+    "%d%b%Y %H:%M",
+];
+/// The values that CWMS and DSS use for missing data.
+const MISSING_VALUES: &'static [f64] = &[-901., -902.];
+/// Every parameter, in the order of the imported observation files' columns.
+const PARAMETERS: &'static [Parameter] = &[
+    Parameter::AirTemperature,
+    Parameter::RelativeHumidity,
+    Parameter::Pressure,
+    Parameter::WindSpeed,
+    Parameter::WindDirection,
+    Parameter::SolarRadiation,
+    Parameter::BatteryVoltage,
+];
+/// The quality flags for missing and rejected values.
+const MISSING_QUALITY: u32 = 0b00100;
+const REJECTED_QUALITY: u32 = 0b10000;
+/// The logger table name used for imported observations.
+pub const TABLE: &'static str = "CWMS";
+/// The TOA5 header lines (after the first) of the imported observation files.
+const HEADER: &'static str = "\"TIMESTAMP\",\"RECORD\",\"AirTC_Avg\",\"RH\",\"BP_mbar\",\
+                              \"WS_ms_Avg\",\"WindDir\",\"SlrW_Avg\",\"BattV_Min\"\r\n\
+                              \"TS\",\"RN\",\"Deg C\",\"%\",\"mbar\",\"meters/second\",\
+                              \"degrees\",\"W/m^2\",\"Volts\"\r\n\
+                              \"\",\"\",\"Avg\",\"Smp\",\"Smp\",\
+                              \"Avg\",\"Smp\",\"Avg\",\"Min\"\r\n";
+
+/// A custom error enum for CWMS imports.
+#[derive(Debug)]
+pub enum Error {
+    /// The export isn't in a format we understand.
+    Format(String),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+    /// A station's path isn't a directory, so there's nowhere to put its CWMS file.
+    NotADirectory(PathBuf),
+    /// Wrapper around `std::num::ParseFloatError`.
+    ParseFloat(ParseFloatError),
+    /// Wrapper around `std::num::ParseIntError`.
+    ParseInt(ParseIntError),
+    /// Wrapper around `glacio::weather::Error`.
+    Weather(weather::Error),
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The parts of a CWMS timeseries id or DSS pathname that we use.
+///
+/// A CWMS timeseries id is `Location.Parameter.Type.Interval.Duration.Version`, and a DSS pathname
+/// is `/A/B/C/D/E/F/`, where B is the location, C the parameter, E the interval, and F the
+/// version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pathname {
+    /// The location, which is usually the station name.
+    pub location: String,
+    /// The parameter, e.g. `Temp-Air`.
+    pub parameter: String,
+    /// The interval, e.g. `1Hour`.
+    pub interval: String,
+    /// The version, e.g. `Raw`.
+    pub version: String,
+}
+
+/// A timeseries from a CWMS export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timeseries {
+    /// The timeseries' pathname.
+    pub pathname: Pathname,
+    /// The timeseries' values. Missing values are `None`.
+    pub values: Vec<(DateTime<Utc>, Option<f64>)>,
+}
+
+/// The observation fields that CWMS parameters can be mapped to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parameter {
+    /// `Temp-Air`.
+    AirTemperature,
+    /// `%-Humidity` or `%-RH`.
+    RelativeHumidity,
+    /// `Pres-Air`, `Pres-Atmospheric`, or `Pres-Barometric`.
+    Pressure,
+    /// `Speed-Wind`.
+    WindSpeed,
+    /// `Dir-Wind`.
+    WindDirection,
+    /// `Irrad-Solar`.
+    SolarRadiation,
+    /// `Volt-Battery`.
+    BatteryVoltage,
+}
+
+/// Imports CWMS exports into station data directories.
+#[derive(Debug, Default)]
+pub struct Importer {
+    stations: HashMap<String, (String, PathBuf)>,
+}
+
+/// The counts from an import.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// The number of observations that were added to station files or filled in missing values.
+    pub imported: usize,
+    /// The number of observations that had nothing new, e.g. because they were already imported.
+    pub skipped: usize,
+    /// The number of missing values.
+    pub missing: usize,
+    /// The number of timeseries whose location or parameter isn't mapped.
+    pub unmapped: usize,
+}
+
+/// Reads all of the timeseries in a CWMS export.
+///
+/// # Examples
+///
+/// ```
+/// let timeseries = glacio::cwms::read("data/cwms/HEL_WX.dss.txt").unwrap();
+/// assert_eq!("SPEED-WIND", timeseries[0].pathname.parameter);
+/// ```
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<Timeseries>> {
+    let mut timeseries = Vec::new();
+    // The timeseries that the values in each column belong to, and the column of its quality.
+    let mut columns: Vec<Option<(usize, Option<usize>)>> = Vec::new();
+    let mut offset = FixedOffset::east(0);
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(time_zone) = header_value(line, "time zone") {
+            offset = parse_time_zone(time_zone)?;
+            continue;
+        }
+        if line.starts_with('#') || header_value(line, "units").is_some() ||
+            header_value(line, "type").is_some()
+        {
+            continue;
+        }
+        if line.starts_with('/') {
+            timeseries.push(Timeseries {
+                pathname: line.parse()?,
+                values: Vec::new(),
+            });
+            columns = vec![None, Some((timeseries.len() - 1, Some(2)))];
+            continue;
+        }
+        let values = line.split(',').map(|value| value.trim()).collect::<Vec<_>>();
+        if columns.is_empty() {
+            columns.push(None);
+            for value in values.iter().skip(1) {
+                let value = value.trim_matches('"');
+                if value.to_lowercase() == "quality" {
+                    let index = columns.len() - 1;
+                    match columns[index] {
+                        Some((_, ref mut quality)) => *quality = Some(index + 1),
+                        None => {
+                            return Err(Error::Format(
+                                "quality column without values".to_string(),
+                            ))
+                        }
+                    }
+                    columns.push(None);
+                } else {
+                    timeseries.push(Timeseries {
+                        pathname: value.parse()?,
+                        values: Vec::new(),
+                    });
+                    columns.push(Some((timeseries.len() - 1, None)));
+                }
+            }
+            continue;
+        }
+        let datetime = parse_datetime(values[0].trim_matches('"'), &offset)?;
+        for (index, column) in columns.iter().enumerate() {
+            if let Some((i, quality)) = *column {
+                let value = parse_value(
+                    values.get(index).map(|s| *s).unwrap_or(""),
+                    quality.and_then(|quality| values.get(quality)).map(|s| *s),
+                )?;
+                timeseries[i].values.push((datetime, value));
+            }
+        }
+    }
+    Ok(timeseries)
+}
+
+impl FromStr for Pathname {
+    type Err = Error;
+
+    /// Parses a CWMS timeseries id or a DSS pathname.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::cwms::Pathname;
+    /// let id: Pathname = "HEL_WX.Temp-Air.Inst.1Hour.0.Raw".parse().unwrap();
+    /// let pathname: Pathname = "/HELHEIM/HEL_WX/TEMP-AIR/01AUG2017/1HOUR/RAW/".parse().unwrap();
+    /// assert_eq!(id.location, pathname.location);
+    /// ```
+    fn from_str(s: &str) -> Result<Pathname> {
+        if s.starts_with('/') {
+            let parts = s.split('/').collect::<Vec<_>>();
+            if parts.len() != 8 {
+                return Err(Error::Format(format!("invalid DSS pathname: {}", s)));
+            }
+            Ok(Pathname {
+                location: parts[2].to_string(),
+                parameter: parts[3].to_string(),
+                interval: parts[5].to_string(),
+                version: parts[6].to_string(),
+            })
+        } else {
+            let parts = s.split('.').collect::<Vec<_>>();
+            if parts.len() != 6 {
+                return Err(Error::Format(format!("invalid CWMS timeseries id: {}", s)));
+            }
+            Ok(Pathname {
+                location: parts[0].to_string(),
+                parameter: parts[1].to_string(),
+                interval: parts[3].to_string(),
+                version: parts[5].to_string(),
+            })
+        }
+    }
+}
+
+impl Pathname {
+    /// Returns the observation field for this pathname's parameter, if we know it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::cwms::{Parameter, Pathname};
+    /// let pathname: Pathname = "HEL_WX.Temp-Air.Inst.1Hour.0.Raw".parse().unwrap();
+    /// assert_eq!(Some(Parameter::AirTemperature), pathname.to_parameter());
+    /// ```
+    pub fn to_parameter(&self) -> Option<Parameter> {
+        match self.parameter.to_uppercase().as_str() {
+            "TEMP-AIR" => Some(Parameter::AirTemperature),
+            "%-HUMIDITY" | "%-RH" => Some(Parameter::RelativeHumidity),
+            "PRES-AIR" | "PRES-ATMOSPHERIC" | "PRES-BAROMETRIC" => Some(Parameter::Pressure),
+            "SPEED-WIND" => Some(Parameter::WindSpeed),
+            "DIR-WIND" => Some(Parameter::WindDirection),
+            "IRRAD-SOLAR" => Some(Parameter::SolarRadiation),
+            "VOLT-BATTERY" => Some(Parameter::BatteryVoltage),
+            _ => None,
+        }
+    }
+}
+
+impl Parameter {
+    /// Returns this parameter's field from an observation.
+    pub fn get(&self, observation: &Observation) -> Option<f64> {
+        match *self {
+            Parameter::AirTemperature => observation.air_temperature,
+            Parameter::RelativeHumidity => observation.relative_humidity,
+            Parameter::Pressure => observation.pressure,
+            Parameter::WindSpeed => observation.wind_speed,
+            Parameter::WindDirection => observation.wind_direction,
+            Parameter::SolarRadiation => observation.solar_radiation,
+            Parameter::BatteryVoltage => observation.battery_voltage,
+        }
+    }
+
+    /// Sets this parameter's field on an observation.
+    pub fn set(&self, observation: &mut Observation, value: Option<f64>) {
+        let field = match *self {
+            Parameter::AirTemperature => &mut observation.air_temperature,
+            Parameter::RelativeHumidity => &mut observation.relative_humidity,
+            Parameter::Pressure => &mut observation.pressure,
+            Parameter::WindSpeed => &mut observation.wind_speed,
+            Parameter::WindDirection => &mut observation.wind_direction,
+            Parameter::SolarRadiation => &mut observation.solar_radiation,
+            Parameter::BatteryVoltage => &mut observation.battery_voltage,
+        };
+        *field = value;
+    }
+}
+
+impl Importer {
+    /// Creates a new importer with no stations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::cwms::Importer;
+    /// let importer = Importer::new();
+    /// ```
+    pub fn new() -> Importer {
+        Default::default()
+    }
+
+    /// Maps a CWMS location to a station, whose observations go in this directory.
+    ///
+    /// Locations are matched case-insensitively, since DSS pathnames are all upper case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::cwms::Importer;
+    /// let mut importer = Importer::new();
+    /// importer.station("HEL_WX", "HEL_WX", "data/HEL_WX");
+    /// ```
+    pub fn station<P: AsRef<Path>>(&mut self, location: &str, name: &str, path: P) {
+        self.stations.insert(
+            location.to_uppercase(),
+            (name.to_string(), path.as_ref().to_path_buf()),
+        );
+    }
+
+    /// Imports a CWMS export.
+    ///
+    /// Returns an error if the export can't be read, if a station's path exists but isn't a
+    /// directory, or if a station's file can't be read or written.
+    pub fn import<P: AsRef<Path>>(&self, path: P) -> Result<Summary> {
+        let mut summary = Summary::default();
+        let mut observations: HashMap<&str, BTreeMap<DateTime<Utc>, Observation>> =
+            HashMap::new();
+        for timeseries in read(path)? {
+            let station = self.stations.get(&timeseries.pathname.location.to_uppercase());
+            let (name, parameter) = match (station, timeseries.pathname.to_parameter()) {
+                (Some(&(ref name, _)), Some(parameter)) => (name.as_str(), parameter),
+                _ => {
+                    summary.unmapped += 1;
+                    continue;
+                }
+            };
+            let observations = observations.entry(name).or_insert_with(BTreeMap::new);
+            for (datetime, value) in timeseries.values {
+                if value.is_none() {
+                    summary.missing += 1;
+                }
+                let observation = observations.entry(datetime).or_insert_with(
+                    || Observation::new(datetime),
+                );
+                if value.is_some() {
+                    parameter.set(observation, value);
+                }
+            }
+        }
+        for &(ref name, ref path) in self.stations.values() {
+            if observations.contains_key(name.as_str()) && path.exists() && !path.is_dir() {
+                return Err(Error::NotADirectory(path.clone()));
+            }
+        }
+        for &(ref name, ref path) in self.stations.values() {
+            if let Some(observations) = observations.get(name.as_str()) {
+                let (imported, skipped) = merge(name, path, observations.values())?;
+                summary.imported += imported;
+                summary.skipped += skipped;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(err: ParseFloatError) -> Error {
+        Error::ParseFloat(err)
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(err: ParseIntError) -> Error {
+        Error::ParseInt(err)
+    }
+}
+
+impl From<weather::Error> for Error {
+    fn from(err: weather::Error) -> Error {
+        Error::Weather(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Format(_) => "invalid CWMS export",
+            Error::Io(ref err) => err.description(),
+            Error::NotADirectory(_) => "station path is not a directory",
+            Error::ParseFloat(ref err) => err.description(),
+            Error::ParseInt(ref err) => err.description(),
+            Error::Weather(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Format(_) => None,
+            Error::Io(ref err) => Some(err),
+            Error::NotADirectory(_) => None,
+            Error::ParseFloat(ref err) => Some(err),
+            Error::ParseInt(ref err) => Some(err),
+            Error::Weather(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Format(ref msg) => write!(f, "invalid CWMS export: {}", msg),
+            Error::Io(ref err) => err.fmt(f),
+            Error::NotADirectory(ref path) => {
+                write!(f, "station path is not a directory: {}", path.display())
+            }
+            Error::ParseFloat(ref err) => err.fmt(f),
+            Error::ParseInt(ref err) => err.fmt(f),
+            Error::Weather(ref err) => err.fmt(f),
+        }
+    }
+}
+
+/// Merges observations into a station's CWMS file.
+///
+/// Observations at new datetimes are added, and values that are missing from the file's
+/// observations are filled in. Values that are already in the file are kept. If anything changed,
+/// the whole file is rewritten in datetime order, to a temporary file that's then moved into place.
+///
+/// Returns the number of observations that changed the file, and the number that didn't.
+fn merge<'a, I>(name: &str, path: &Path, observations: I) -> Result<(usize, usize)>
+where
+    I: Iterator<Item = &'a Observation>,
+{
+    let file_path = path.join(format!("{}_{}.dat", name, TABLE));
+    let mut merged = BTreeMap::new();
+    if file_path.exists() {
+        for observation in DatSource::new(&file_path).iter()? {
+            let observation = observation?;
+            merged.insert(observation.datetime, observation);
+        }
+    }
+    let mut imported = 0;
+    let mut skipped = 0;
+    for observation in observations {
+        let changed = match merged.entry(observation.datetime) {
+            Entry::Occupied(mut entry) => fill(entry.get_mut(), observation),
+            Entry::Vacant(entry) => {
+                entry.insert(*observation);
+                true
+            }
+        };
+        if changed {
+            imported += 1;
+        } else {
+            skipped += 1;
+        }
+    }
+    if imported == 0 {
+        return Ok((0, skipped));
+    }
+    fs::create_dir_all(path)?;
+    let partial_path = file_path.with_extension("dat.partial");
+    {
+        let mut file = BufWriter::new(File::create(&partial_path)?);
+        write!(
+            file,
+            "\"TOA5\",\"{}\",\"{}\",\"\",\"\",\"\",\"\",\"{}\"\r\n{}",
+            name,
+            TABLE,
+            TABLE,
+            HEADER
+        )?;
+        for (record, observation) in merged.values().enumerate() {
+            write!(
+                file,
+                "\"{}\",{}",
+                observation.datetime.format("%Y-%m-%d %H:%M:%S"),
+                record
+            )?;
+            for parameter in PARAMETERS {
+                match parameter.get(observation) {
+                    Some(value) => write!(file, ",{}", value)?,
+                    None => write!(file, ",\"NAN\"")?,
+                }
+            }
+            write!(file, "\r\n")?;
+        }
+        file.flush()?;
+    }
+    fs::rename(&partial_path, &file_path)?;
+    Ok((imported, skipped))
+}
+
+/// Fills in the values that are missing from an observation, returning true if any were.
+fn fill(observation: &mut Observation, other: &Observation) -> bool {
+    let mut filled = false;
+    for parameter in PARAMETERS {
+        if parameter.get(observation).is_none() && parameter.get(other).is_some() {
+            parameter.set(observation, parameter.get(other));
+            filled = true;
+        }
+    }
+    filled
+}
+
+/// Returns the value of a `Key: value` header line, with or without a leading `#`.
+fn header_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let line = line.trim_left_matches('#').trim_left();
+    let mut split = line.splitn(2, ':');
+    match (split.next(), split.next()) {
+        (Some(name), Some(value)) if name.trim().to_lowercase() == key => Some(value.trim()),
+        _ => None,
+    }
+}
+
+/// Parses a time zone name (`UTC`, `GMT`, or a US time zone abbreviation) or offset from UTC
+/// (e.g. `GMT-05:00` or `-0500`).
+fn parse_time_zone(s: &str) -> Result<FixedOffset> {
+    let upper = s.to_uppercase();
+    let hours = match upper.as_str() {
+        "UTC" | "GMT" | "Z" => 0,
+        "EST" | "CDT" => -5,
+        "EDT" => -4,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" | "AKDT" => -8,
+        "AKST" => -9,
+        _ => {
+            let offset = upper.trim_left_matches("UTC").trim_left_matches("GMT");
+            let sign = match offset.chars().next() {
+                Some('+') => 1,
+                Some('-') => -1,
+                _ => return Err(Error::Format(format!("unknown time zone: {}", s))),
+            };
+            let digits = offset[1..].replace(':', "");
+            let (hours, minutes) = match digits.len() {
+                1 | 2 => (digits.parse::<i32>()?, 0),
+                4 => (digits[..2].parse::<i32>()?, digits[2..].parse::<i32>()?),
+                _ => return Err(Error::Format(format!("unknown time zone: {}", s))),
+            };
+            if hours >= 24 || minutes >= 60 {
+                return Err(Error::Format(format!("time zone offset out of range: {}", s)));
+            }
+            return Ok(FixedOffset::east(sign * (hours * 3600 + minutes * 60)));
+        }
+    };
+    Ok(FixedOffset::east(hours * 3600))
+}
+
+/// Parses a datetime in the export's time zone.
+fn parse_datetime(s: &str, offset: &FixedOffset) -> Result<DateTime<Utc>> {
+    // DSS writes midnight as 2400 of the day before.
+    let (s, days) = if s.ends_with(" 2400") || s.ends_with(" 24:00") {
+        (format!("{} 0000", &s[..s.rfind(' ').unwrap()]), 1)
+    } else {
+        (s.to_string(), 0)
+    };
+    for format in DATETIME_FORMATS {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(&s, format) {
+            return offset
+                .from_local_datetime(&(datetime + Duration::days(days)))
+                .single()
+                .map(|datetime| datetime.with_timezone(&Utc))
+                .ok_or_else(|| Error::Format(format!("invalid datetime: {}", s)));
+        }
+    }
+    Err(Error::Format(format!("invalid datetime: {}", s)))
+}
+
+/// Parses a value and its (optional) quality code.
+fn parse_value(value: &str, quality: Option<&str>) -> Result<Option<f64>> {
+    let value = value.trim_matches('"');
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match quality.map(|s| s.trim_matches('"')) {
+        Some("") | None => {}
+        Some(quality) => {
+            if quality.parse::<u32>()? & (MISSING_QUALITY | REJECTED_QUALITY) != 0 {
+                return Ok(None);
+            }
+        }
+    }
+    let value = value.parse::<f64>()?;
+    if MISSING_VALUES.contains(&value) {
+        Ok(None)
+    } else {
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn importer(name: &str) -> (Importer, PathBuf) {
+        let path = env::temp_dir().join(format!("glacio-cwms-{}", name));
+        let _ = fs::remove_dir_all(&path);
+        let mut importer = Importer::new();
+        importer.station("HEL_WX", "HEL_WX", &path);
+        (importer, path)
+    }
+
+    #[test]
+    fn csv() {
+        let timeseries = read("data/cwms/HEL_WX.csv").unwrap();
+        assert_eq!(3, timeseries.len());
+        let temperature = &timeseries[0];
+        assert_eq!("HEL_WX", temperature.pathname.location);
+        assert_eq!(Some(Parameter::AirTemperature), temperature.pathname.to_parameter());
+        assert_eq!(
+            vec![
+                (Utc.ymd(2017, 8, 25).and_hms(16, 0, 0), Some(5.41)),
+                (Utc.ymd(2017, 8, 25).and_hms(17, 0, 0), Some(5.12)),
+                (Utc.ymd(2017, 8, 25).and_hms(18, 0, 0), None),
+            ],
+            temperature.values
+        );
+        assert_eq!(None, timeseries[1].values[1].1);
+        assert_eq!(None, timeseries[2].pathname.to_parameter());
+    }
+
+    #[test]
+    fn dss() {
+        let timeseries = read("data/cwms/HEL_WX.dss.txt").unwrap();
+        assert_eq!(2, timeseries.len());
+        assert_eq!("1HOUR", timeseries[0].pathname.interval);
+        assert_eq!("RAW", timeseries[0].pathname.version);
+        assert_eq!(
+            vec![
+                (Utc.ymd(2017, 8, 25).and_hms(16, 0, 0), Some(3.2)),
+                (Utc.ymd(2017, 8, 25).and_hms(17, 0, 0), None),
+                (Utc.ymd(2017, 8, 26).and_hms(0, 0, 0), Some(2.75)),
+            ],
+            timeseries[0].values
+        );
+    }
+
+    #[test]
+    fn time_zones() {
+        assert_eq!(FixedOffset::east(0), parse_time_zone("gmt").unwrap());
+        assert_eq!(FixedOffset::east(-4 * 3600), parse_time_zone("EDT").unwrap());
+        assert_eq!(
+            FixedOffset::east(-(3 * 3600 + 30 * 60)),
+            parse_time_zone("GMT-03:30").unwrap()
+        );
+        assert_eq!(FixedOffset::east(2 * 3600), parse_time_zone("+02").unwrap());
+        assert!(parse_time_zone("Mars/Olympus_Mons").is_err());
+        assert!(parse_time_zone("GMT+24").is_err());
+        assert!(parse_time_zone("-99:00").is_err());
+        assert!(parse_time_zone("+05:60").is_err());
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(Some(1.5), parse_value("1.5", None).unwrap());
+        assert_eq!(Some(1.5), parse_value("1.5", Some("3")).unwrap());
+        assert_eq!(None, parse_value("1.5", Some("5")).unwrap());
+        assert_eq!(None, parse_value("1.5", Some("17")).unwrap());
+        assert_eq!(None, parse_value("-901.0", None).unwrap());
+        assert_eq!(None, parse_value("", None).unwrap());
+        assert!(parse_value("M", None).is_err());
+    }
+
+    #[test]
+    fn import() {
+        let (importer, path) = importer("import");
+        assert_eq!(
+            Summary {
+                imported: 3,
+                skipped: 0,
+                missing: 2,
+                unmapped: 1,
+            },
+            importer.import("data/cwms/HEL_WX.csv").unwrap()
+        );
+        assert_eq!(
+            Summary {
+                imported: 2,
+                skipped: 1,
+                missing: 1,
+                unmapped: 1,
+            },
+            importer.import("data/cwms/HEL_WX.dss.txt").unwrap()
+        );
+        assert_eq!(0, importer.import("data/cwms/HEL_WX.csv").unwrap().imported);
+
+        let observations = DatSource::new(&path)
+            .tables(&["CWMS"])
+            .iter()
+            .unwrap()
+            .collect::<weather::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(4, observations.len());
+        assert_eq!(Some(5.41), observations[0].air_temperature);
+        assert_eq!(Some(73.), observations[0].relative_humidity);
+        assert_eq!(Some(3.2), observations[0].wind_speed);
+        assert_eq!(None, observations[2].air_temperature);
+        assert_eq!(Utc.ymd(2017, 8, 26).and_hms(0, 0, 0), observations[3].datetime);
+        assert_eq!(Some(2.75), observations[3].wind_speed);
+    }
+
+    #[test]
+    fn not_a_directory() {
+        let path = env::temp_dir().join("glacio-cwms-not-a-directory.dat");
+        File::create(&path).unwrap();
+        let mut importer = Importer::new();
+        importer.station("HEL_WX", "HEL_WX", &path);
+        match importer.import("data/cwms/HEL_WX.csv") {
+            Err(Error::NotADirectory(ref err_path)) => assert_eq!(&path, err_path),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unmapped() {
+        let (mut importer, path) = importer("unmapped");
+        importer.stations.clear();
+        assert_eq!(3, importer.import("data/cwms/HEL_WX.csv").unwrap().unmapped);
+        assert!(!path.exists());
+    }
+}
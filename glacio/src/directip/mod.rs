@@ -0,0 +1,382 @@
+//! Send and receive Iridium SBD messages over DirectIP.
+//!
+//! Iridium delivers mobile-originated (MO) SBD messages by opening a TCP connection to our server
+//! and sending the message in the DirectIP format. This module listens for those connections,
+//! decodes each message, writes it into a `sbd` filesystem storage (the same layout that
+//! `atlas::SbdSource` reads), and sends back an MO confirmation. Each connection is handled on its
+//! own thread, so a slow or stuck client doesn't hold up the others.
+//!
+//! Anything watching the storage, e.g. `atlas::SbdSource::watch`, sees the new file as soon as it
+//! is written.
+//!
+//! Sending mobile-terminated (MT) messages to a modem lives in the `mt` module.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use glacio::directip::Server;
+//! let mut server = Server::bind("0.0.0.0:10800", "/var/iridium").unwrap();
+//! for result in server.incoming() {
+//!     match result {
+//!         Ok(message) => println!("Received message from {}", message.imei()),
+//!         Err(err) => println!("Problem receiving message: {}", err),
+//!     }
+//! }
+//! ```
+
+pub mod mt;
+
+use chrono::{DateTime, Utc};
+use sbd;
+use sbd::mo::Message;
+use sbd::storage::{FilesystemStorage, Storage};
+use serde_json;
+use std::{error, io, result};
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// The DirectIP protocol revision number.
+const PROTOCOL_REVISION_NUMBER: u8 = 1;
+/// The information element id of an MO header.
+const MO_HEADER_IEI: u8 = 0x01;
+/// The information element id of an MO payload.
+const MO_PAYLOAD_IEI: u8 = 0x02;
+/// The information element id of an MO confirmation.
+const CONFIRMATION_IEI: u8 = 0x05;
+/// How long to wait for a slow or stuck client before giving up on it.
+const READ_TIMEOUT_SECONDS: u64 = 60;
+
+/// A custom error enum for DirectIP.
+#[derive(Debug)]
+pub enum Error {
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+    /// Wrapper around `serde_json::Error`.
+    Json(serde_json::Error),
+    /// A saved MT queue stayed locked by someone else for too long.
+    Locked(PathBuf),
+    /// The other end of the connection broke the DirectIP protocol.
+    Protocol(String),
+    /// Wrapper around `sbd::Error`.
+    Sbd(sbd::Error),
+    /// A saved MT queue is for a modem with a different IMEI.
+    WrongImei(String),
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// A DirectIP server that stores the messages it receives.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    path: PathBuf,
+    storage: FilesystemStorage,
+}
+
+/// An iterator over the messages received by a server.
+///
+/// The first call to `next` starts a thread that accepts connections, and each connection is
+/// handled on a thread of its own. Each call to `next` blocks until a message has been handled,
+/// so messages are returned in the order they finish arriving, not the order their connections
+/// were opened.
+#[derive(Debug)]
+pub struct Incoming<'a> {
+    server: &'a mut Server,
+    receiver: Option<Receiver<Result<Message>>>,
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
+impl From<sbd::Error> for Error {
+    fn from(err: sbd::Error) -> Error {
+        Error::Sbd(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => err.description(),
+            Error::Json(ref err) => err.description(),
+            Error::Locked(_) => "the MT queue is locked",
+            Error::Protocol(_) => "DirectIP protocol error",
+            Error::Sbd(ref err) => err.description(),
+            Error::WrongImei(_) => "the MT queue is for a different IMEI",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            Error::Locked(_) => None,
+            Error::Protocol(_) => None,
+            Error::Sbd(ref err) => Some(err),
+            Error::WrongImei(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => err.fmt(f),
+            Error::Json(ref err) => err.fmt(f),
+            Error::Locked(ref path) => {
+                write!(
+                    f,
+                    "the MT queue is locked, remove {} if nothing else is using the queue",
+                    path.display()
+                )
+            }
+            Error::Protocol(ref msg) => write!(f, "DirectIP protocol error: {}", msg),
+            Error::Sbd(ref err) => err.fmt(f),
+            Error::WrongImei(ref imei) => write!(f, "the MT queue is for IMEI {}", imei),
+        }
+    }
+}
+
+impl Server {
+    /// Binds a new server to an address, storing messages in the storage at `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::Server;
+    /// let server = Server::bind("127.0.0.1:0", "data").unwrap();
+    /// ```
+    pub fn bind<A: ToSocketAddrs, P: AsRef<Path>>(addr: A, path: P) -> Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            path: path.as_ref().to_path_buf(),
+            storage: FilesystemStorage::open(path)?,
+        })
+    }
+
+    /// Returns the local address that this server is listening on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::Server;
+    /// let server = Server::bind("127.0.0.1:0", "data").unwrap();
+    /// let addr = server.local_addr().unwrap();
+    /// ```
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr().map_err(Error::from)
+    }
+
+    /// Returns an iterator over received messages.
+    pub fn incoming(&mut self) -> Incoming {
+        Incoming {
+            server: self,
+            receiver: None,
+        }
+    }
+
+    /// Reads one message from the stream, stores it, and confirms receipt.
+    ///
+    /// If the message can't be read or stored, we tell Iridium that it failed so it will be
+    /// re-sent.
+    pub fn handle(&mut self, stream: TcpStream) -> Result<Message> {
+        handle(stream, &mut self.storage)
+    }
+
+    /// Starts a thread that accepts connections and handles each one on a new thread, sending
+    /// the results back through the returned receiver.
+    fn accept(&self) -> Result<Receiver<Result<Message>>> {
+        let listener = self.listener.try_clone()?;
+        let path = self.path.clone();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || for stream in listener.incoming() {
+            let sender = sender.clone();
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    if sender.send(Err(err.into())).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            let path = path.clone();
+            thread::spawn(move || {
+                let result = FilesystemStorage::open(&path).map_err(Error::from).and_then(
+                    |mut storage| handle(stream, &mut storage),
+                );
+                let _ = sender.send(result);
+            });
+        });
+        Ok(receiver)
+    }
+}
+
+impl<'a> Iterator for Incoming<'a> {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Result<Message>> {
+        if self.receiver.is_none() {
+            match self.server.accept() {
+                Ok(receiver) => self.receiver = Some(receiver),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        self.receiver.as_ref().and_then(|receiver| receiver.recv().ok())
+    }
+}
+
+fn handle(mut stream: TcpStream, storage: &mut FilesystemStorage) -> Result<Message> {
+    stream.set_read_timeout(
+        Some(Duration::from_secs(READ_TIMEOUT_SECONDS)),
+    )?;
+    let result = Message::read_from(&mut stream).and_then(|message| {
+        storage.store(message.clone()).map(|()| message)
+    });
+    let _ = confirm(&mut stream, result.is_ok());
+    result.map_err(Error::from)
+}
+
+/// Builds an MO message by encoding it in the DirectIP format and reading it back.
+///
+/// This is how we make messages for SBD data that didn't arrive over DirectIP, e.g. in an email.
+/// The IMEI must be fifteen characters long.
+pub(crate) fn mo_message(
+    imei: &str,
+    momsn: u16,
+    mtmsn: u16,
+    time_of_session: DateTime<Utc>,
+    payload: &[u8],
+) -> sbd::Result<Message> {
+    let mut bytes = vec![PROTOCOL_REVISION_NUMBER, 0, 0, MO_HEADER_IEI, 0, 28, 0, 0, 0, 0];
+    bytes.extend_from_slice(imei.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(&u16_to_bytes(momsn));
+    bytes.extend_from_slice(&u16_to_bytes(mtmsn));
+    let timestamp = time_of_session.timestamp() as u32;
+    bytes.extend_from_slice(&u16_to_bytes((timestamp >> 16) as u16));
+    bytes.extend_from_slice(&u16_to_bytes(timestamp as u16));
+    bytes.push(MO_PAYLOAD_IEI);
+    bytes.extend_from_slice(&u16_to_bytes(payload.len() as u16));
+    bytes.extend_from_slice(payload);
+    let length = u16_to_bytes(bytes.len() as u16 - 3);
+    bytes[1] = length[0];
+    bytes[2] = length[1];
+    Message::read_from(&bytes[..])
+}
+
+fn u16_to_bytes(n: u16) -> [u8; 2] {
+    [(n >> 8) as u8, n as u8]
+}
+
+fn confirm<W: Write>(write: &mut W, success: bool) -> io::Result<()> {
+    write.write_all(
+        &[
+            PROTOCOL_REVISION_NUMBER,
+            0,
+            4,
+            CONFIRMATION_IEI,
+            0,
+            1,
+            success as u8,
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use std::io::Read;
+    use std::net::Shutdown;
+    use std::thread;
+
+    #[test]
+    fn receive() {
+        let path = env::temp_dir().join("glacio-directip-receive");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let mut server = Server::bind("127.0.0.1:0", &path).unwrap();
+        let addr = server.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut bytes = Vec::new();
+            fs::File::open("data/300234063556840/2017/08/170825_150106.sbd")
+                .unwrap()
+                .read_to_end(&mut bytes)
+                .unwrap();
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&bytes).unwrap();
+            let mut confirmation = Vec::new();
+            stream.read_to_end(&mut confirmation).unwrap();
+            confirmation
+        });
+
+        let message = server.incoming().next().unwrap().unwrap();
+        assert_eq!("300234063556840", message.imei());
+        assert_eq!(vec![1, 0, 4, 5, 0, 1, 1], client.join().unwrap());
+        let messages = FilesystemStorage::open(&path)
+            .unwrap()
+            .messages_from_imei("300234063556840")
+            .unwrap();
+        assert_eq!(vec![message], messages);
+    }
+
+    #[test]
+    fn truncated_message() {
+        let path = env::temp_dir().join("glacio-directip-truncated-message");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let mut server = Server::bind("127.0.0.1:0", &path).unwrap();
+        let addr = server.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&[1, 0, 10, 1, 0]).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut confirmation = Vec::new();
+            stream.read_to_end(&mut confirmation).unwrap();
+            confirmation
+        });
+
+        assert!(server.incoming().next().unwrap().is_err());
+        assert_eq!(vec![1, 0, 4, 5, 0, 1, 0], client.join().unwrap());
+    }
+
+    #[test]
+    fn stuck_client() {
+        let path = env::temp_dir().join("glacio-directip-stuck-client");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let mut server = Server::bind("127.0.0.1:0", &path).unwrap();
+        let addr = server.local_addr().unwrap();
+        // Connects but never sends anything, so it's only given up on after the read timeout.
+        let stuck = TcpStream::connect(addr).unwrap();
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            fs::File::open("data/300234063556840/2017/08/170825_150106.sbd")
+                .unwrap()
+                .read_to_end(&mut bytes)
+                .unwrap();
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&bytes).unwrap();
+        });
+
+        let message = server.incoming().next().unwrap().unwrap();
+        assert_eq!("300234063556840", message.imei());
+        drop(stuck);
+    }
+}
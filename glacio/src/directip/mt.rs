@@ -0,0 +1,687 @@
+//! Send mobile-terminated (MT) messages to an Iridium modem.
+//!
+//! MT messages go the other way from MO ones: we connect to Iridium's DirectIP gateway, send one
+//! message, and the gateway replies with a confirmation saying whether the message was queued for
+//! the modem. The modem picks up queued messages the next time it opens an SBD session. The MO
+//! message from that session carries the MTMSN of the MT message the modem received, which is how
+//! a `Queue` knows that a message was delivered.
+//!
+//! A queue can be saved to a JSON file and opened again, so that MTMSNs keep counting up from one
+//! command to the next and deliveries can be confirmed by whatever is receiving MO messages. When
+//! more than one process uses the same saved queue, they should change it with `Queue::update`,
+//! which locks the file, and send its messages with `Queue::send_saved`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use glacio::directip::mt::Queue;
+//! use glacio::sutron::Command;
+//! let mut queue = Queue::new("300234063556840");
+//! queue.push(Command::ForceHeartbeat.to_string()).unwrap();
+//! queue.send("12.47.179.12:10800").unwrap();
+//! ```
+
+use chrono::{DateTime, Utc};
+use directip::{Error, PROTOCOL_REVISION_NUMBER, READ_TIMEOUT_SECONDS, Result, u16_to_bytes};
+use sbd::mo;
+use serde_json;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The information element id of an MT header.
+const HEADER_IEI: u8 = 0x41;
+/// The information element id of an MT payload.
+const PAYLOAD_IEI: u8 = 0x42;
+/// The information element id of an MT confirmation.
+const CONFIRMATION_IEI: u8 = 0x44;
+/// The disposition flag that tells the gateway to use our message id as the MTMSN.
+const ASSIGN_MTMSN: u16 = 0x0020;
+/// The longest payload that the gateway accepts.
+const MAX_PAYLOAD_LENGTH: usize = 1890;
+/// How long to wait for someone else to unlock a saved queue.
+const LOCK_TIMEOUT_SECONDS: u64 = 10;
+/// How long to wait between tries to lock a saved queue.
+const LOCK_RETRY_MILLISECONDS: u64 = 20;
+
+/// A mobile-terminated message.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Message {
+    mtmsn: u16,
+    imei: String,
+    payload: Vec<u8>,
+}
+
+/// The gateway's response to an MT message.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Confirmation {
+    /// The id we sent with the message, which is also its MTMSN.
+    pub client_message_id: u32,
+    /// The IMEI of the modem.
+    pub imei: String,
+    /// The gateway's own id for the message.
+    pub auto_id_reference: u32,
+    /// The status of the message.
+    ///
+    /// Positive numbers are the message's position in the modem's queue, zero means there was no
+    /// payload, and negative numbers are errors (e.g. -1 is an invalid IMEI).
+    pub status: i16,
+}
+
+/// A queue of MT messages for one modem, which tracks each message until it is delivered.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Queue {
+    imei: String,
+    last_mtmsn: u16,
+    entries: Vec<Entry>,
+}
+
+/// A message in a queue.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
+    /// The message.
+    pub message: Message,
+    /// Where the message is on its way to the modem.
+    pub status: Status,
+}
+
+/// A lock on a saved queue, which is a `.lock` file next to it that is removed when the lock is
+/// dropped.
+#[derive(Debug)]
+struct Lock {
+    path: PathBuf,
+}
+
+/// The delivery status of a queued message.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The message hasn't been sent to the gateway yet.
+    Pending,
+    /// The gateway has queued the message for the modem.
+    Queued(Confirmation),
+    /// The gateway refused the message.
+    Rejected(Confirmation),
+    /// The modem received the message in the SBD session at this time.
+    Delivered(DateTime<Utc>),
+}
+
+impl Message {
+    /// Creates a new MT message.
+    ///
+    /// The MTMSN must not be zero, the IMEI must be fifteen digits, and the payload can be at most
+    /// 1890 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Message;
+    /// let message = Message::new(1, "300234063556840", b"ATCM HEARTBEAT".to_vec()).unwrap();
+    /// ```
+    pub fn new(mtmsn: u16, imei: &str, payload: Vec<u8>) -> Result<Message> {
+        if mtmsn == 0 {
+            return Err(Error::Protocol("the MTMSN can't be zero".to_string()));
+        }
+        if imei.len() != 15 || !imei.chars().all(|c| c.is_digit(10)) {
+            return Err(Error::Protocol(format!("invalid IMEI: {}", imei)));
+        }
+        if payload.len() > MAX_PAYLOAD_LENGTH {
+            return Err(Error::Protocol(
+                format!("payload is {} bytes long", payload.len()),
+            ));
+        }
+        Ok(Message {
+            mtmsn: mtmsn,
+            imei: imei.to_string(),
+            payload: payload,
+        })
+    }
+
+    /// Returns this message's MTMSN.
+    pub fn mtmsn(&self) -> u16 {
+        self.mtmsn
+    }
+
+    /// Returns the IMEI of the modem this message is for.
+    pub fn imei(&self) -> &str {
+        &self.imei
+    }
+
+    /// Returns this message's payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Writes this message in the DirectIP format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Message;
+    /// let message = Message::new(1, "300234063556840", b"ATCM HEARTBEAT".to_vec()).unwrap();
+    /// let mut bytes = Vec::new();
+    /// message.write_to(&mut bytes).unwrap();
+    /// assert_eq!(44, bytes.len());
+    /// ```
+    pub fn write_to<W: Write>(&self, write: &mut W) -> Result<()> {
+        let mut bytes = vec![PROTOCOL_REVISION_NUMBER, 0, 0, HEADER_IEI, 0, 21, 0, 0];
+        bytes.extend_from_slice(&u16_to_bytes(self.mtmsn));
+        bytes.extend_from_slice(self.imei.as_bytes());
+        bytes.extend_from_slice(&u16_to_bytes(ASSIGN_MTMSN));
+        bytes.push(PAYLOAD_IEI);
+        bytes.extend_from_slice(&u16_to_bytes(self.payload.len() as u16));
+        bytes.extend_from_slice(&self.payload);
+        let length = u16_to_bytes(bytes.len() as u16 - 3);
+        bytes[1] = length[0];
+        bytes[2] = length[1];
+        write.write_all(&bytes).map_err(Error::from)
+    }
+
+    /// Sends this message to a DirectIP gateway and returns the gateway's confirmation.
+    pub fn send<A: ToSocketAddrs>(&self, addr: A) -> Result<Confirmation> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(
+            Some(Duration::from_secs(READ_TIMEOUT_SECONDS)),
+        )?;
+        self.write_to(&mut stream)?;
+        Confirmation::read_from(&mut stream)
+    }
+}
+
+impl Confirmation {
+    /// Reads a confirmation in the DirectIP format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Confirmation;
+    /// let mut bytes = vec![1, 0, 28, 0x44, 0, 25, 0, 0, 0, 1];
+    /// bytes.extend_from_slice(b"300234063556840");
+    /// bytes.extend_from_slice(&[0, 0, 0, 42, 0, 1]);
+    /// let confirmation = Confirmation::read_from(&bytes[..]).unwrap();
+    /// assert_eq!(1, confirmation.status);
+    /// ```
+    pub fn read_from<R: Read>(mut read: R) -> Result<Confirmation> {
+        let mut header = [0; 3];
+        read.read_exact(&mut header)?;
+        if header[0] != PROTOCOL_REVISION_NUMBER {
+            return Err(Error::Protocol(
+                format!("invalid protocol revision number: {}", header[0]),
+            ));
+        }
+        let mut bytes = vec![0; u16_from_bytes(&header[1..]) as usize];
+        read.read_exact(&mut bytes)?;
+        let mut i = 0;
+        while i + 3 <= bytes.len() {
+            let length = u16_from_bytes(&bytes[i + 1..]) as usize;
+            let data = match bytes.get(i + 3..i + 3 + length) {
+                Some(data) => data,
+                None => break,
+            };
+            if bytes[i] == CONFIRMATION_IEI && length == 25 {
+                return Ok(Confirmation {
+                    client_message_id: u32_from_bytes(&data[0..]),
+                    imei: String::from_utf8_lossy(&data[4..19]).into_owned(),
+                    auto_id_reference: u32_from_bytes(&data[19..]),
+                    status: u16_from_bytes(&data[23..]) as i16,
+                });
+            }
+            i += 3 + length;
+        }
+        Err(Error::Protocol(
+            "no MT confirmation in the response".to_string(),
+        ))
+    }
+
+    /// Returns true if the gateway accepted the message.
+    pub fn is_success(&self) -> bool {
+        self.status >= 0
+    }
+}
+
+impl Queue {
+    /// Creates a new, empty queue for the modem with the given IMEI.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Queue;
+    /// let queue = Queue::new("300234063556840");
+    /// ```
+    pub fn new(imei: &str) -> Queue {
+        Queue {
+            imei: imei.to_string(),
+            last_mtmsn: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Opens the queue saved at `path`, or creates a new, empty one if there's no file there yet.
+    ///
+    /// Returns an error if the saved queue is for a different modem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Queue;
+    /// let queue = Queue::open("/tmp/glacio-mt-queue-example.json", "300234063556840").unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P, imei: &str) -> Result<Queue> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(Queue::new(imei)),
+            Err(err) => return Err(err.into()),
+        };
+        let queue: Queue = serde_json::from_reader(file)?;
+        if queue.imei != imei {
+            return Err(Error::WrongImei(queue.imei));
+        }
+        Ok(queue)
+    }
+
+    /// Saves this queue to `path`, replacing any queue that was saved there.
+    ///
+    /// The queue is written to a temporary file that is then moved into place, so a crash while
+    /// saving doesn't lose the queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Queue;
+    /// let queue = Queue::new("300234063556840");
+    /// queue.save("/tmp/glacio-mt-queue-example.json").unwrap();
+    /// ```
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        {
+            let mut file = File::create(&partial)?;
+            serde_json::to_writer(&mut file, self)?;
+            file.write_all(b"\n")?;
+        }
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Opens the queue saved at `path`, changes it with `f`, and saves it, all while holding a lock
+    /// on the saved queue.
+    ///
+    /// Use this instead of `open` and `save` if anything else might change the same saved queue,
+    /// e.g. a DirectIP server that confirms deliveries. If `f` returns an error, the queue isn't
+    /// saved. Returns `Error::Locked` if the queue stays locked by someone else for ten seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Queue;
+    /// # use glacio::sutron::Command;
+    /// # let path = "/tmp/glacio-mt-queue-update-example.json";
+    /// # let _ = std::fs::remove_file(path);
+    /// let mtmsn = Queue::update(path, "300234063556840", |queue| {
+    ///     queue.push(Command::ForceHeartbeat.to_string())
+    /// }).unwrap();
+    /// assert_eq!(1, mtmsn);
+    /// ```
+    pub fn update<P, F, T>(path: P, imei: &str, f: F) -> Result<T>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&mut Queue) -> Result<T>,
+    {
+        let path = path.as_ref();
+        let _lock = Lock::acquire(path)?;
+        let mut queue = Queue::open(path, imei)?;
+        let value = f(&mut queue)?;
+        queue.save(path)?;
+        Ok(value)
+    }
+
+    /// Sends every pending message in the queue saved at `path` to the gateway, returning the
+    /// number that were queued.
+    ///
+    /// The saved queue isn't locked while we wait on the gateway, which can take a while. Instead,
+    /// each confirmation is recorded with `Queue::update`, so changes made to the saved queue in
+    /// the meantime (e.g. delivery confirmations) aren't lost. If a message can't be sent, it stays
+    /// pending and the error is returned.
+    pub fn send_saved<P, A>(path: P, imei: &str, addr: A) -> Result<usize>
+    where
+        P: AsRef<Path>,
+        A: ToSocketAddrs,
+    {
+        let path = path.as_ref();
+        let mut count = 0;
+        for message in Queue::open(path, imei)?.pending() {
+            let confirmation = message.send(&addr)?;
+            if Queue::update(path, imei, |queue| {
+                Ok(queue.record(message.mtmsn, confirmation))
+            })?
+            {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Adds a payload to the queue, returning the MTMSN of its message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::directip::mt::Queue;
+    /// # use glacio::sutron::Command;
+    /// let mut queue = Queue::new("300234063556840");
+    /// assert_eq!(1, queue.push(Command::ForceHeartbeat.to_string()).unwrap());
+    /// assert_eq!(2, queue.push(Command::RieglSwitch(true).to_string()).unwrap());
+    /// ```
+    pub fn push<P: Into<Vec<u8>>>(&mut self, payload: P) -> Result<u16> {
+        let mtmsn = self.last_mtmsn.checked_add(1).unwrap_or(1);
+        let message = Message::new(mtmsn, &self.imei, payload.into())?;
+        self.last_mtmsn = mtmsn;
+        self.entries.push(Entry {
+            message: message,
+            status: Status::Pending,
+        });
+        Ok(mtmsn)
+    }
+
+    /// Sends every pending message to the gateway, returning the number that were queued.
+    ///
+    /// If a message can't be sent, e.g. because the gateway is down, it stays pending and the
+    /// error is returned.
+    pub fn send<A: ToSocketAddrs>(&mut self, addr: A) -> Result<usize> {
+        let mut count = 0;
+        for message in self.pending() {
+            let confirmation = message.send(&addr)?;
+            if self.record(message.mtmsn, confirmation) {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Marks the message that the modem received in this MO message's session as delivered.
+    ///
+    /// Returns the MTMSN of the delivered message, if any. Feed every MO message from the modem,
+    /// e.g. from a `directip::Server`, through this method.
+    pub fn confirm(&mut self, message: &mo::Message) -> Option<u16> {
+        if message.imei() != self.imei {
+            return None;
+        }
+        for entry in &mut self.entries {
+            if entry.message.mtmsn == message.mtmsn() {
+                if let Status::Queued(_) = entry.status {
+                    entry.status = Status::Delivered(message.time_of_session());
+                    return Some(entry.message.mtmsn);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns every message in this queue, in the order they were pushed.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn pending(&self) -> Vec<Message> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == Status::Pending)
+            .map(|entry| entry.message.clone())
+            .collect()
+    }
+
+    /// Records the gateway's confirmation of a pending message, returning true if the message was
+    /// queued.
+    fn record(&mut self, mtmsn: u16, confirmation: Confirmation) -> bool {
+        let is_success = confirmation.is_success();
+        if let Some(entry) = self.entries.iter_mut().find(|entry| {
+            entry.message.mtmsn == mtmsn && entry.status == Status::Pending
+        })
+        {
+            entry.status = if is_success {
+                Status::Queued(confirmation)
+            } else {
+                Status::Rejected(confirmation)
+            };
+        }
+        is_success
+    }
+}
+
+impl Lock {
+    fn acquire(path: &Path) -> Result<Lock> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+                Ok(_) => return Ok(Lock { path: lock_path }),
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {
+                    if start.elapsed() >= Duration::from_secs(LOCK_TIMEOUT_SECONDS) {
+                        return Err(Error::Locked(lock_path));
+                    }
+                    thread::sleep(Duration::from_millis(LOCK_RETRY_MILLISECONDS));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn u16_from_bytes(bytes: &[u8]) -> u16 {
+    (bytes[0] as u16) << 8 | bytes[1] as u16
+}
+
+fn u32_from_bytes(bytes: &[u8]) -> u32 {
+    (u16_from_bytes(bytes) as u32) << 16 | u16_from_bytes(&bytes[2..]) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+    use sutron::Command;
+
+    const IMEI: &'static str = "300234063556840";
+
+    /// A stand-in for the DirectIP gateway that records the messages it receives and replies with
+    /// the given statuses.
+    fn gateway(statuses: Vec<i16>) -> (String, Receiver<Vec<u8>>) {
+        gateway_with(statuses, || {})
+    }
+
+    /// A gateway that calls `before_reply` after reading each message, while the sender waits.
+    fn gateway_with<F>(statuses: Vec<i16>, before_reply: F) -> (String, Receiver<Vec<u8>>)
+    where
+        F: Fn() + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || for (i, status) in statuses.into_iter().enumerate() {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0; 3];
+            stream.read_exact(&mut header).unwrap();
+            let mut bytes = vec![0; u16_from_bytes(&header[1..]) as usize];
+            stream.read_exact(&mut bytes).unwrap();
+            before_reply();
+            let mut response = vec![1, 0, 28, CONFIRMATION_IEI, 0, 25];
+            response.extend_from_slice(&bytes[3..7]);
+            response.extend_from_slice(&bytes[7..22]);
+            response.extend_from_slice(&[0, 0, 0, i as u8]);
+            response.extend_from_slice(&u16_to_bytes(status as u16));
+            stream.write_all(&response).unwrap();
+            let mut message = header.to_vec();
+            message.extend(bytes);
+            sender.send(message).unwrap();
+        });
+        (addr, receiver)
+    }
+
+    fn mo_message(mtmsn: u16) -> mo::Message {
+        let mut bytes = vec![1, 0, 31, 1, 0, 28, 0, 0, 0, 0];
+        bytes.extend_from_slice(IMEI.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&[0, 1]);
+        bytes.extend_from_slice(&u16_to_bytes(mtmsn));
+        bytes.extend_from_slice(&[0x59, 0xa0, 0x3b, 0xb2]);
+        mo::Message::read_from(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn write() {
+        let message = Message::new(258, IMEI, b"ATCM RIEGL ON".to_vec()).unwrap();
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        let mut expected = vec![1, 0, 40, 0x41, 0, 21, 0, 0, 1, 2];
+        expected.extend_from_slice(IMEI.as_bytes());
+        expected.extend_from_slice(&[0, 0x20, 0x42, 0, 13]);
+        expected.extend_from_slice(b"ATCM RIEGL ON");
+        assert_eq!(expected, bytes);
+    }
+
+    #[test]
+    fn invalid_message() {
+        assert!(Message::new(0, IMEI, Vec::new()).is_err());
+        assert!(Message::new(1, "3002340635568", Vec::new()).is_err());
+        assert!(Message::new(1, IMEI, vec![0; 1891]).is_err());
+    }
+
+    #[test]
+    fn queue() {
+        let (addr, receiver) = gateway(vec![1, -4]);
+        let mut queue = Queue::new(IMEI);
+        queue.push(Command::ForceHeartbeat.to_string()).unwrap();
+        queue.push(Command::RieglSwitch(false).to_string()).unwrap();
+        assert_eq!(1, queue.send(&addr).unwrap());
+
+        let sent = receiver.recv().unwrap();
+        assert_eq!(b"ATCM HEARTBEAT", &sent[sent.len() - 14..]);
+        assert_eq!(
+            Status::Queued(Confirmation {
+                client_message_id: 1,
+                imei: IMEI.to_string(),
+                auto_id_reference: 0,
+                status: 1,
+            }),
+            queue.entries()[0].status
+        );
+        match queue.entries()[1].status {
+            Status::Rejected(ref confirmation) => assert_eq!(-4, confirmation.status),
+            ref status => panic!("Unexpected status: {:?}", status),
+        }
+
+        assert_eq!(None, queue.confirm(&mo_message(2)));
+        assert_eq!(Some(1), queue.confirm(&mo_message(1)));
+        assert_eq!(
+            Status::Delivered(Utc.ymd(2017, 8, 25).and_hms(15, 1, 6)),
+            queue.entries()[0].status
+        );
+        assert_eq!(None, queue.confirm(&mo_message(1)));
+    }
+
+    #[test]
+    fn gateway_down() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut queue = Queue::new(IMEI);
+        queue.push(Command::ForceHeartbeat.to_string()).unwrap();
+        assert!(queue.send(addr).is_err());
+        assert_eq!(Status::Pending, queue.entries()[0].status);
+    }
+
+    #[test]
+    fn save_and_open() {
+        use std::env;
+
+        let path = env::temp_dir().join("glacio-directip-mt-queue.json");
+        let _ = fs::remove_file(&path);
+        let mut queue = Queue::open(&path, IMEI).unwrap();
+        assert!(queue.entries().is_empty());
+        queue.push(Command::ForceHeartbeat.to_string()).unwrap();
+        queue.save(&path).unwrap();
+
+        let mut queue = Queue::open(&path, IMEI).unwrap();
+        assert_eq!(Status::Pending, queue.entries()[0].status);
+        assert_eq!(2, queue.push(Command::RieglSwitch(true).to_string()).unwrap());
+        assert!(Queue::open(&path, "300234063556841").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        use std::env;
+
+        let path = env::temp_dir().join("glacio-directip-mt-queue-concurrent.json");
+        let _ = fs::remove_file(&path);
+        let threads = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    Queue::update(&path, IMEI, |queue| {
+                        queue.push(Command::ForceHeartbeat.to_string())
+                    }).unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut mtmsns = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+        mtmsns.sort();
+        assert_eq!(vec![1, 2, 3, 4], mtmsns);
+        assert_eq!(4, Queue::open(&path, IMEI).unwrap().entries().len());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn send_saved() {
+        use std::env;
+
+        let path = env::temp_dir().join("glacio-directip-mt-queue-send-saved.json");
+        let _ = fs::remove_file(&path);
+        Queue::update(&path, IMEI, |queue| {
+            queue.push(Command::ForceHeartbeat.to_string())
+        }).unwrap();
+        let (addr, _receiver) = gateway(vec![1]);
+        assert_eq!(1, Queue::send_saved(&path, IMEI, &addr).unwrap());
+
+        // The modem picks up the first command while the second one is being sent.
+        Queue::update(&path, IMEI, |queue| {
+            queue.push(Command::RieglSwitch(true).to_string())
+        }).unwrap();
+        let (addr, _receiver) = gateway_with(vec![2], {
+            let path = path.clone();
+            move || {
+                Queue::update(&path, IMEI, |queue| Ok(queue.confirm(&mo_message(1)))).unwrap();
+            }
+        });
+        assert_eq!(1, Queue::send_saved(&path, IMEI, &addr).unwrap());
+        let queue = Queue::open(&path, IMEI).unwrap();
+        assert_eq!(
+            Status::Delivered(Utc.ymd(2017, 8, 25).and_hms(15, 1, 6)),
+            queue.entries()[0].status
+        );
+        match queue.entries()[1].status {
+            Status::Queued(ref confirmation) => assert_eq!(2, confirmation.client_message_id),
+            ref status => panic!("Unexpected status: {:?}", status),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+//! Read EXIF metadata from JPEG images.
+//!
+//! Our cameras write EXIF metadata into their pictures, including the camera's own idea of when
+//! the picture was taken. We only need a handful of tags, so rather than pulling in a full EXIF
+//! library this module walks the JPEG segments to the `Exif` APP1 segment and reads those tags out
+//! of its TIFF structure:
+//!
+//! - `DateTimeOriginal`, when the picture was taken, in the camera clock's time zone.
+//! - `Make` and `Model`, the camera's manufacturer and model.
+//! - `BodySerialNumber`, the camera's serial number.
+
+use chrono::NaiveDateTime;
+use jpeg::{self, Segments};
+use std::{error, io, result};
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+const APP1: u8 = 0xe1;
+const EXIF_HEADER: &'static [u8] = b"Exif\0\0";
+const DATETIME_FORMAT: &'static str = "%Y:%m:%d %H:%M:%S";
+
+const ASCII: u16 = 2;
+const LONG: u16 = 4;
+
+const MAKE: u16 = 0x010f;
+const MODEL: u16 = 0x0110;
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const BODY_SERIAL_NUMBER: u16 = 0xa431;
+
+/// Our custom error enum for EXIF reading.
+#[derive(Debug)]
+pub enum Error {
+    /// The file isn't a JPEG with valid EXIF data.
+    Format(String),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+    /// Wrapper around `glacio::jpeg::Error`.
+    Jpeg(jpeg::Error),
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The EXIF metadata that we care about.
+///
+/// Tags that are missing, or that can't be read, are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Exif {
+    /// When the picture was taken, according to the camera's clock.
+    ///
+    /// EXIF datetimes don't have a time zone; it's whatever the camera's clock is set to.
+    pub date_time_original: Option<NaiveDateTime>,
+    /// The camera's manufacturer.
+    pub make: Option<String>,
+    /// The camera's model.
+    pub model: Option<String>,
+    /// The camera's serial number.
+    pub serial_number: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ByteOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+#[derive(Debug)]
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    byte_order: ByteOrder,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value_offset: usize,
+}
+
+impl Exif {
+    /// Reads the EXIF metadata from the JPEG file at the given path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::exif::Exif;
+    /// let exif = Exif::read("data/exif/STARDOT_20170806_152500.jpg").unwrap();
+    /// assert_eq!(Some("NetCam SC 5MP".to_string()), exif.model);
+    /// assert!(Exif::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").is_err());
+    /// ```
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Exif> {
+        let file = File::open(path)?;
+        Exif::from_jpeg(BufReader::new(file))
+    }
+
+    /// Reads the EXIF metadata out of JPEG data.
+    ///
+    /// Only the segments before the image data are read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::exif::Exif;
+    /// assert!(Exif::from_jpeg(&b"not a jpeg"[..]).is_err());
+    /// ```
+    pub fn from_jpeg<R: Read>(read: R) -> Result<Exif> {
+        for result in Segments::new(read)? {
+            let (marker, data) = result?;
+            if marker == APP1 && data.starts_with(EXIF_HEADER) {
+                return Exif::from_tiff(&data[EXIF_HEADER.len()..]);
+            }
+        }
+        Err(Error::Format("no EXIF segment".to_string()))
+    }
+
+    fn from_tiff(bytes: &[u8]) -> Result<Exif> {
+        let tiff = Tiff::new(bytes)?;
+        let mut exif = Exif::default();
+        let offset = tiff.u32(4)? as usize;
+        for entry in tiff.entries(offset)? {
+            match entry.tag {
+                MAKE => exif.make = tiff.ascii(&entry),
+                MODEL => exif.model = tiff.ascii(&entry),
+                EXIF_IFD_POINTER if entry.field_type == LONG => {
+                    let offset = tiff.u32(entry.value_offset)? as usize;
+                    for entry in tiff.entries(offset)? {
+                        match entry.tag {
+                            DATE_TIME_ORIGINAL => {
+                                exif.date_time_original = tiff.ascii(&entry).and_then(|s| {
+                                    NaiveDateTime::parse_from_str(&s, DATETIME_FORMAT).ok()
+                                })
+                            }
+                            BODY_SERIAL_NUMBER => exif.serial_number = tiff.ascii(&entry),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(exif)
+    }
+}
+
+impl<'a> Tiff<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Tiff<'a>> {
+        let byte_order = if bytes.starts_with(b"II") {
+            ByteOrder::LittleEndian
+        } else if bytes.starts_with(b"MM") {
+            ByteOrder::BigEndian
+        } else {
+            return Err(Error::Format("invalid TIFF byte order".to_string()));
+        };
+        let tiff = Tiff {
+            bytes: bytes,
+            byte_order: byte_order,
+        };
+        if tiff.u16(2)? != 42 {
+            return Err(Error::Format("invalid TIFF header".to_string()));
+        }
+        Ok(tiff)
+    }
+
+    fn entries(&self, offset: usize) -> Result<Vec<Entry>> {
+        let count = self.u16(offset)? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let offset = offset + 2 + i * 12;
+            entries.push(Entry {
+                tag: self.u16(offset)?,
+                field_type: self.u16(offset + 2)?,
+                count: self.u32(offset + 4)?,
+                value_offset: offset + 8,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn ascii(&self, entry: &Entry) -> Option<String> {
+        if entry.field_type != ASCII {
+            return None;
+        }
+        let count = entry.count as usize;
+        let offset = if count <= 4 {
+            entry.value_offset
+        } else {
+            match self.u32(entry.value_offset) {
+                Ok(offset) => offset as usize,
+                Err(_) => return None,
+            }
+        };
+        self.bytes.get(offset..offset + count).and_then(|bytes| {
+            let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+            let s = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+            if s.is_empty() { None } else { Some(s) }
+        })
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.get(offset, 2)?;
+        let (a, b) = (u16::from(bytes[0]), u16::from(bytes[1]));
+        Ok(match self.byte_order {
+            ByteOrder::BigEndian => a << 8 | b,
+            ByteOrder::LittleEndian => b << 8 | a,
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.get(offset, 4)?;
+        Ok(bytes.iter().enumerate().fold(0, |n, (i, &byte)| {
+            let shift = match self.byte_order {
+                ByteOrder::BigEndian => 8 * (3 - i),
+                ByteOrder::LittleEndian => 8 * i,
+            };
+            n | u32::from(byte) << shift
+        }))
+    }
+
+    fn get(&self, offset: usize, length: usize) -> Result<&[u8]> {
+        self.bytes.get(offset..offset + length).ok_or_else(|| {
+            Error::Format(format!("TIFF offset out of bounds: {}", offset))
+        })
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<jpeg::Error> for Error {
+    fn from(err: jpeg::Error) -> Error {
+        Error::Jpeg(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Format(_) => "invalid EXIF data",
+            Error::Io(ref err) => err.description(),
+            Error::Jpeg(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Format(_) => None,
+            Error::Io(ref err) => Some(err),
+            Error::Jpeg(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Format(ref msg) => write!(f, "invalid EXIF data: {}", msg),
+            Error::Io(ref err) => err.fmt(f),
+            Error::Jpeg(ref err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(s: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok()
+    }
+
+    #[test]
+    fn read() {
+        let exif = Exif::read("data/exif/STARDOT_20170806_152500.jpg").unwrap();
+        assert_eq!(datetime("2017-08-06 15:27:30"), exif.date_time_original);
+        assert_eq!(Some("StarDot".to_string()), exif.make);
+        assert_eq!(Some("NetCam SC 5MP".to_string()), exif.model);
+        assert_eq!(Some("SD0123456".to_string()), exif.serial_number);
+
+        let exif = Exif::read("data/exif/image.jpg").unwrap();
+        assert_eq!(datetime("2017-08-06 16:25:00"), exif.date_time_original);
+        assert_eq!(Some("GE".to_string()), exif.make);
+        assert_eq!(None, exif.model);
+        assert_eq!(None, exif.serial_number);
+    }
+
+    #[test]
+    fn no_exif() {
+        assert!(Exif::read("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").is_err());
+        assert!(Exif::read("data/exif/notafile.jpg").is_err());
+        assert!(Exif::from_jpeg(&[0xff, 0xd8, 0xff, 0xe1, 0x00][..]).is_err());
+    }
+}
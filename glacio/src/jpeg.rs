@@ -0,0 +1,306 @@
+//! Read JPEG headers without decoding the image.
+//!
+//! A JPEG file is a sequence of segments, each starting with a marker. Everything we want to know
+//! about an image without decoding it (its size in pixels, its EXIF metadata) lives in the
+//! segments before the compressed image data, so this module only reads up to the start of scan.
+//!
+//! Images sent over a satellite link are sometimes cut short. `verify` catches those without
+//! decoding the image, by checking that the headers are complete and that the file ends with the
+//! end of image marker.
+
+use std::{error, io, result};
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The start of image marker.
+pub const START_OF_IMAGE: u8 = 0xd8;
+/// The end of image marker.
+pub const END_OF_IMAGE: u8 = 0xd9;
+/// The start of scan marker, after which comes the compressed image data.
+pub const START_OF_SCAN: u8 = 0xda;
+
+const DEFINE_HUFFMAN_TABLE: u8 = 0xc4;
+const JPEG_EXTENSION: u8 = 0xc8;
+const DEFINE_ARITHMETIC_CODING: u8 = 0xcc;
+
+/// Our custom error enum for JPEG reading.
+#[derive(Debug)]
+pub enum Error {
+    /// The file isn't a valid JPEG.
+    Format(String),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The size of an image, in pixels.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Dimensions {
+    /// The width of the image.
+    pub width: u16,
+    /// The height of the image.
+    pub height: u16,
+}
+
+/// An iterator over the segments before a JPEG's image data.
+///
+/// Each segment is its marker (the byte after `0xff`) and its data, not including the length.
+/// Iteration stops at the start of scan or the end of image.
+///
+/// # Examples
+///
+/// ```
+/// # use glacio::jpeg::Segments;
+/// let file = std::fs::File::open("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+/// for result in Segments::new(file).unwrap() {
+///     let (marker, data) = result.unwrap();
+///     println!("{:x}: {} bytes", marker, data.len());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Segments<R: Read> {
+    read: R,
+    done: bool,
+    end_marker: Option<u8>,
+}
+
+impl<R: Read> Segments<R> {
+    /// Starts reading segments, after checking for the start of image marker.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::jpeg::Segments;
+    /// assert!(Segments::new(&b"not a jpeg"[..]).is_err());
+    /// ```
+    pub fn new(mut read: R) -> Result<Segments<R>> {
+        let mut marker = [0; 2];
+        read.read_exact(&mut marker)?;
+        if marker != [0xff, START_OF_IMAGE] {
+            return Err(Error::Format("not a JPEG".to_string()));
+        }
+        Ok(Segments {
+            read: read,
+            done: false,
+            end_marker: None,
+        })
+    }
+
+    /// Returns the marker that ended the segments, either the start of scan or the end of image.
+    ///
+    /// Returns `None` if iteration hasn't finished, or if it ended with an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::jpeg::{self, Segments};
+    /// let file = std::fs::File::open("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// let mut segments = Segments::new(file).unwrap();
+    /// while let Some(result) = segments.next() {
+    ///     result.unwrap();
+    /// }
+    /// assert_eq!(Some(jpeg::START_OF_SCAN), segments.end_marker());
+    /// ```
+    pub fn end_marker(&self) -> Option<u8> {
+        self.end_marker
+    }
+
+    fn read_segment(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        let mut marker = [0; 2];
+        self.read.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(Error::Format(format!("invalid marker: {:?}", marker)));
+        }
+        if marker[1] == START_OF_SCAN || marker[1] == END_OF_IMAGE {
+            self.end_marker = Some(marker[1]);
+            return Ok(None);
+        }
+        let mut length = [0; 2];
+        self.read.read_exact(&mut length)?;
+        let length = (u16::from(length[0]) << 8 | u16::from(length[1])) as usize;
+        if length < 2 {
+            return Err(Error::Format(format!("invalid segment length: {}", length)));
+        }
+        let mut data = vec![0; length - 2];
+        self.read.read_exact(&mut data)?;
+        Ok(Some((marker[1], data)))
+    }
+}
+
+impl<R: Read> Iterator for Segments<R> {
+    type Item = Result<(u8, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_segment() {
+            Ok(Some(segment)) => Some(Ok(segment)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Reads the dimensions of the JPEG file at the given path from its start of frame header.
+///
+/// # Examples
+///
+/// ```
+/// # use glacio::jpeg;
+/// let dimensions = jpeg::dimensions("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+/// assert_eq!(1024, dimensions.width);
+/// assert_eq!(768, dimensions.height);
+/// ```
+pub fn dimensions<P: AsRef<Path>>(path: P) -> Result<Dimensions> {
+    let file = File::open(path)?;
+    for result in Segments::new(BufReader::new(file))? {
+        let (marker, data) = result?;
+        if is_start_of_frame(marker) {
+            return frame_dimensions(&data);
+        }
+    }
+    Err(Error::Format("no start of frame segment".to_string()))
+}
+
+/// Checks that the JPEG file at the given path is complete.
+///
+/// The file must start with the start of image marker, have a start of frame segment with a
+/// non-zero size, have image data after its headers, and end with the end of image marker. The
+/// image data themselves aren't decoded, so a complete file with garbled data still passes.
+///
+/// # Examples
+///
+/// ```
+/// # use glacio::jpeg;
+/// assert!(jpeg::verify("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").is_ok());
+/// assert!(jpeg::verify("data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial").is_err());
+/// ```
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<()> {
+    let mut file = File::open(path)?;
+    {
+        let mut segments = Segments::new(BufReader::new(&mut file))?;
+        let mut dimensions = None;
+        while let Some(result) = segments.next() {
+            let (marker, data) = result?;
+            if is_start_of_frame(marker) {
+                dimensions = Some(frame_dimensions(&data)?);
+            }
+        }
+        match dimensions {
+            Some(dimensions) => {
+                if dimensions.width == 0 || dimensions.height == 0 {
+                    return Err(Error::Format("image has no pixels".to_string()));
+                }
+            }
+            None => return Err(Error::Format("no start of frame segment".to_string())),
+        }
+        if segments.end_marker() != Some(START_OF_SCAN) {
+            return Err(Error::Format("no image data".to_string()));
+        }
+    }
+    file.seek(SeekFrom::End(-2))?;
+    let mut marker = [0; 2];
+    file.read_exact(&mut marker)?;
+    if marker == [0xff, END_OF_IMAGE] {
+        Ok(())
+    } else {
+        Err(Error::Format(
+            "no end of image marker, the file may be truncated".to_string(),
+        ))
+    }
+}
+
+fn frame_dimensions(data: &[u8]) -> Result<Dimensions> {
+    if data.len() < 5 {
+        return Err(Error::Format("start of frame segment is too short".to_string()));
+    }
+    Ok(Dimensions {
+        height: u16::from(data[1]) << 8 | u16::from(data[2]),
+        width: u16::from(data[3]) << 8 | u16::from(data[4]),
+    })
+}
+
+fn is_start_of_frame(marker: u8) -> bool {
+    marker >= 0xc0 && marker <= 0xcf && marker != DEFINE_HUFFMAN_TABLE &&
+        marker != JPEG_EXTENSION && marker != DEFINE_ARITHMETIC_CODING
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Format(_) => "invalid JPEG",
+            Error::Io(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Format(_) => None,
+            Error::Io(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Format(ref msg) => write!(f, "invalid JPEG: {}", msg),
+            Error::Io(ref err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        let file = File::open("data/exif/image.jpg").unwrap();
+        let markers = Segments::new(file)
+            .unwrap()
+            .map(|result| result.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0xe0, 0xe1, 0xdb, 0xc0, 0xc4, 0xc4], markers);
+    }
+
+    #[test]
+    fn read_dimensions() {
+        assert_eq!(
+            Dimensions {
+                width: 8,
+                height: 8,
+            },
+            dimensions("data/exif/image.jpg").unwrap()
+        );
+        assert!(dimensions("data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial").is_ok());
+        assert!(dimensions("data/HEL_WX/HEL_WX_Hourly.dat").is_err());
+        assert!(dimensions("data/notafile.jpg").is_err());
+    }
+
+    #[test]
+    fn verify_images() {
+        assert!(verify("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").is_ok());
+        assert!(verify("data/exif/image.jpg").is_ok());
+        assert!(verify("data/ATLAS_CAM/ATLAS_CAM_20160603_101500.jpg.partial").is_err());
+        assert!(verify("data/HEL_WX/HEL_WX_Hourly.dat").is_err());
+        assert!(verify("data/notafile.jpg").is_err());
+    }
+}
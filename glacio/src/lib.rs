@@ -9,21 +9,28 @@
 //!     - In Iridium Short Burst Data (SBD) messages in `/var/iridium`
 //!     - As images in `/home/iridiumcam/StarDot`
 //!
-//! This crate brings together these disparate data sources into a single Rust API. For now, we
-//! don't have any of the weather station data, only cameras and ATLAS status information.
+//! This crate brings together these disparate data sources into a single Rust API. Weather
+//! station data are read from the stations' own data files, as copied off of the CWMS server.
 
 #![deny(missing_docs, missing_debug_implementations, missing_copy_implementations, trivial_casts,
         trivial_numeric_casts, unsafe_code, unstable_features, unused_import_braces,
         unused_qualifications)]
 
+extern crate base64;
 extern crate chrono;
+#[cfg(test)]
+extern crate filetime;
+extern crate image;
 #[macro_use]
 extern crate lazy_static;
+extern crate notify;
 extern crate regex;
 extern crate sbd;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
 extern crate url;
 
 #[macro_use]
@@ -31,6 +38,15 @@ mod macros;
 
 pub mod atlas;
 pub mod camera;
+pub mod cwms;
+pub mod directip;
+pub mod exif;
+pub mod jpeg;
+pub mod mail;
+pub mod station;
 pub mod sutron;
+pub mod thumbnail;
+pub mod weather;
 
 pub use camera::{Camera, Image};
+pub use station::Station;
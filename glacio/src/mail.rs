@@ -0,0 +1,584 @@
+//! Import SBD messages from Iridium's email deliveries.
+//!
+//! Besides DirectIP, Iridium can deliver each mobile-originated message as an email with the
+//! payload attached as a `.sbd` file, which is how our backup mailbox receives them. The
+//! attachment is only the payload, so the IMEI, MOMSN, and time of session come from the email
+//! itself. The subject names the unit and the body lists the session details:
+//!
+//! ```text
+//! Subject: SBD Msg From Unit: 300234063556840
+//!
+//! MOMSN: 1234
+//! MTMSN: 0
+//! Time of Session (UTC): Fri Aug 25 15:01:06 2017
+//! Session Status: 00 - Transfer OK
+//! ```
+//!
+//! If the body doesn't have them, the IMEI and MOMSN are taken from the attachment's file name
+//! (`300234063556840_001234.sbd`) and the time of session from the `Date` header. The `Date`
+//! header is when the email was sent, not when the session happened, so it won't match the time
+//! of session of the same message received over DirectIP.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::{env, fs};
+//! use glacio::mail::Importer;
+//! # let storage = env::temp_dir().join("glacio-mail-doctest");
+//! # let _ = fs::remove_dir_all(&storage);
+//! # fs::create_dir_all(&storage).unwrap();
+//! let mut importer = Importer::open(&storage).unwrap();
+//! let summary = importer.import("data/mail/iridium.mbox").unwrap();
+//! assert_eq!(2, summary.imported);
+//! ```
+
+use base64;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use directip;
+use regex::Regex;
+use sbd;
+use sbd::mo::Message;
+use sbd::storage::{FilesystemStorage, Storage};
+use std::{error, io, result};
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The format of the time of session in the email body, e.g. `Fri Aug 25 15:01:06 2017`.
+const TIME_OF_SESSION_FORMAT: &'static str = "%a %b %e %H:%M:%S %Y";
+/// The longest payload that fits in a single DirectIP message.
+const MAX_PAYLOAD_LENGTH: usize = 1960;
+
+lazy_static! {
+    static ref SUBJECT_REGEX: Regex = Regex::new(r"Unit: (?P<imei>\d{15})").unwrap();
+    static ref FILE_NAME_REGEX: Regex = Regex::new(r"^(?P<imei>\d{15})_(?P<momsn>\d+)\.sbd$")
+        .unwrap();
+}
+
+/// A custom error enum for email imports.
+#[derive(Debug)]
+pub enum Error {
+    /// Wrapper around `base64::DecodeError`.
+    Base64(base64::DecodeError),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+    /// The email doesn't describe its attachment well enough to make an SBD message.
+    Mail(String),
+    /// Wrapper around `sbd::Error`.
+    Sbd(sbd::Error),
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Imports SBD attachments from mailboxes into an SBD storage.
+///
+/// Messages already in the storage, or already imported by this importer, are skipped. Two
+/// messages are the same if they have the same IMEI, MOMSN, and payload. The time of session isn't
+/// compared, since it may only be known from the email's `Date` header.
+#[derive(Debug)]
+pub struct Importer {
+    path: PathBuf,
+    storage: FilesystemStorage,
+    existing: HashSet<(String, u16, Vec<u8>)>,
+    loaded: HashSet<String>,
+}
+
+/// The counts of attachments found in an import.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// The number of messages written to the storage.
+    pub imported: usize,
+    /// The number of messages that were already in the storage.
+    pub duplicates: usize,
+    /// The number of `.sbd` attachments that couldn't be turned into messages.
+    pub invalid: usize,
+}
+
+#[derive(Debug, Default)]
+struct Email {
+    imei: Option<String>,
+    momsn: Option<u16>,
+    mtmsn: Option<u16>,
+    time_of_session: Option<DateTime<Utc>>,
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Debug)]
+struct Attachment {
+    file_name: String,
+    payload: Result<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct Part<'a> {
+    headers: Vec<(String, String)>,
+    body: &'a str,
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(err: base64::DecodeError) -> Error {
+        Error::Base64(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<sbd::Error> for Error {
+    fn from(err: sbd::Error) -> Error {
+        Error::Sbd(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Base64(ref err) => err.description(),
+            Error::Io(ref err) => err.description(),
+            Error::Mail(_) => "email error",
+            Error::Sbd(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Base64(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            Error::Mail(_) => None,
+            Error::Sbd(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Base64(ref err) => err.fmt(f),
+            Error::Io(ref err) => err.fmt(f),
+            Error::Mail(ref msg) => write!(f, "email error: {}", msg),
+            Error::Sbd(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl Importer {
+    /// Opens an importer that writes into the SBD storage at `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::mail::Importer;
+    /// let importer = Importer::open("data").unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Importer> {
+        Ok(Importer {
+            path: path.as_ref().to_path_buf(),
+            storage: FilesystemStorage::open(path)?,
+            existing: HashSet::new(),
+            loaded: HashSet::new(),
+        })
+    }
+
+    /// Imports every SBD attachment in a mailbox.
+    ///
+    /// If `path` is a directory it is read as a Maildir (both `new` and `cur`), otherwise as an
+    /// mbox file.
+    pub fn import<P: AsRef<Path>>(&mut self, path: P) -> Result<Summary> {
+        let path = path.as_ref();
+        let mut summary = Summary::default();
+        if path.is_dir() {
+            for subdirectory in &["new", "cur"] {
+                let subdirectory = path.join(subdirectory);
+                if !subdirectory.is_dir() {
+                    continue;
+                }
+                let mut paths = fs::read_dir(subdirectory)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<io::Result<Vec<_>>>()?;
+                paths.sort();
+                for path in paths.into_iter().filter(|path| path.is_file()) {
+                    self.import_email(&read_to_string(&path)?, &mut summary)?;
+                }
+            }
+        } else {
+            for email in split_mbox(&read_to_string(path)?) {
+                self.import_email(&email, &mut summary)?;
+            }
+        }
+        Ok(summary)
+    }
+
+    fn import_email(&mut self, text: &str, summary: &mut Summary) -> Result<()> {
+        for result in Email::parse(text).messages() {
+            let message = match result {
+                Ok(message) => message,
+                Err(_) => {
+                    summary.invalid += 1;
+                    continue;
+                }
+            };
+            if self.contains(&message)? {
+                summary.duplicates += 1;
+            } else {
+                self.existing.insert(key(&message));
+                self.storage.store(message)?;
+                summary.imported += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn contains(&mut self, message: &Message) -> Result<bool> {
+        if !self.loaded.contains(message.imei()) {
+            if self.path.join(message.imei()).is_dir() {
+                for message in self.storage.messages_from_imei(message.imei())? {
+                    self.existing.insert(key(&message));
+                }
+            }
+            self.loaded.insert(message.imei().to_string());
+        }
+        Ok(self.existing.contains(&key(message)))
+    }
+}
+
+impl Email {
+    fn parse(text: &str) -> Email {
+        let text = text.replace("\r\n", "\n");
+        let part = Part::parse(&text);
+        let mut email = Email::default();
+        email.imei = part.header("Subject").and_then(|subject| {
+            SUBJECT_REGEX.captures(subject).map(
+                |captures| captures["imei"].to_string(),
+            )
+        });
+        email.add(&part);
+        if email.time_of_session.is_none() {
+            email.time_of_session = part.header("Date").and_then(|date| {
+                DateTime::parse_from_rfc2822(date).ok().map(|datetime| {
+                    datetime.with_timezone(&Utc)
+                })
+            });
+        }
+        email
+    }
+
+    fn add(&mut self, part: &Part) {
+        if let Some(boundary) = part.parameter("Content-Type", "boundary") {
+            for text in split_multipart(part.body, &boundary) {
+                self.add(&Part::parse(text));
+            }
+        } else if let Some(file_name) = part.file_name() {
+            if file_name.to_lowercase().ends_with(".sbd") {
+                self.attachments.push(Attachment {
+                    file_name: file_name,
+                    payload: part.decode(),
+                });
+            }
+        } else if part.content_type().map_or(true, |content_type| {
+            content_type.eq_ignore_ascii_case("text/plain")
+        })
+        {
+            for line in part.body.lines() {
+                let mut split = line.splitn(2, ':');
+                let (name, value) = match (split.next(), split.next()) {
+                    (Some(name), Some(value)) => (name.trim(), value.trim()),
+                    _ => continue,
+                };
+                match name {
+                    "MOMSN" => self.momsn = value.parse().ok(),
+                    "MTMSN" => self.mtmsn = value.parse().ok(),
+                    "Time of Session (UTC)" => {
+                        self.time_of_session =
+                            NaiveDateTime::parse_from_str(value, TIME_OF_SESSION_FORMAT)
+                                .ok()
+                                .map(|datetime| DateTime::from_utc(datetime, Utc))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn messages(self) -> Vec<Result<Message>> {
+        let mut messages = Vec::new();
+        for attachment in &self.attachments {
+            messages.push(self.message(attachment));
+        }
+        messages
+    }
+
+    fn message(&self, attachment: &Attachment) -> Result<Message> {
+        let payload = match attachment.payload {
+            Ok(ref payload) => payload,
+            Err(ref err) => return Err(Error::Mail(err.to_string())),
+        };
+        let captures = FILE_NAME_REGEX.captures(&attachment.file_name);
+        let imei = match self.imei.as_ref().map(|imei| imei.as_str()).or_else(|| {
+            captures.as_ref().map(|captures| {
+                captures.name("imei").unwrap().as_str()
+            })
+        }) {
+            Some(imei) => imei,
+            None => return Err(Error::Mail("no IMEI".to_string())),
+        };
+        let momsn = match self.momsn.or_else(|| {
+            captures.as_ref().and_then(|captures| captures["momsn"].parse().ok())
+        }) {
+            Some(momsn) => momsn,
+            None => return Err(Error::Mail("no MOMSN".to_string())),
+        };
+        let time_of_session = match self.time_of_session {
+            Some(time_of_session) => time_of_session,
+            None => return Err(Error::Mail("no time of session".to_string())),
+        };
+        if payload.len() > MAX_PAYLOAD_LENGTH {
+            return Err(Error::Mail(
+                format!("payload is {} bytes long", payload.len()),
+            ));
+        }
+        if imei.len() != 15 {
+            return Err(Error::Mail(format!("invalid IMEI: {}", imei)));
+        }
+        directip::mo_message(
+            imei,
+            momsn,
+            self.mtmsn.unwrap_or(0),
+            time_of_session,
+            payload,
+        ).map_err(Error::from)
+    }
+}
+
+impl<'a> Part<'a> {
+    fn parse(text: &'a str) -> Part<'a> {
+        let (head, body) = match text.find("\n\n") {
+            Some(i) => (&text[..i], &text[i + 2..]),
+            None => (text, ""),
+        };
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in head.lines() {
+            if line.starts_with(' ') || line.starts_with('\t') {
+                if let Some(header) = headers.last_mut() {
+                    header.1.push(' ');
+                    header.1.push_str(line.trim());
+                }
+            } else {
+                let mut split = line.splitn(2, ':');
+                if let (Some(name), Some(value)) = (split.next(), split.next()) {
+                    headers.push((name.trim().to_string(), value.trim().to_string()));
+                }
+            }
+        }
+        Part {
+            headers: headers,
+            body: body,
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|header| header.0.eq_ignore_ascii_case(name))
+            .map(|header| header.1.as_str())
+    }
+
+    fn parameter(&self, header: &str, name: &str) -> Option<String> {
+        self.header(header).and_then(|value| {
+            value.split(';').skip(1).filter_map(|parameter| {
+                let mut split = parameter.splitn(2, '=');
+                match (split.next(), split.next()) {
+                    (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case(name) => {
+                        Some(value.trim().trim_matches('"').to_string())
+                    }
+                    _ => None,
+                }
+            }).next()
+        })
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type").map(|value| {
+            value.split(';').next().unwrap().trim()
+        })
+    }
+
+    fn file_name(&self) -> Option<String> {
+        self.parameter("Content-Disposition", "filename").or_else(|| {
+            self.parameter("Content-Type", "name")
+        })
+    }
+
+    fn decode(&self) -> Result<Vec<u8>> {
+        match self.header("Content-Transfer-Encoding") {
+            Some(encoding) if encoding.eq_ignore_ascii_case("base64") => {
+                let encoded = self.body
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>();
+                base64::decode(&encoded).map_err(Error::from)
+            }
+            _ => Ok(self.body.as_bytes().to_vec()),
+        }
+    }
+}
+
+fn key(message: &Message) -> (String, u16, Vec<u8>) {
+    (
+        message.imei().to_string(),
+        message.momsn(),
+        message.payload().to_vec(),
+    )
+}
+
+fn read_to_string(path: &Path) -> Result<String> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Splits an mbox into its emails, unescaping `>From ` lines.
+fn split_mbox(text: &str) -> Vec<String> {
+    let mut emails = Vec::new();
+    let mut email: Option<String> = None;
+    let mut previous_line_is_blank = true;
+    for line in text.lines() {
+        if previous_line_is_blank && line.starts_with("From ") {
+            emails.extend(email.take());
+            email = Some(String::new());
+        } else if let Some(ref mut email) = email {
+            if line.starts_with('>') && line.trim_left_matches('>').starts_with("From ") {
+                email.push_str(&line[1..]);
+            } else {
+                email.push_str(line);
+            }
+            email.push('\n');
+        }
+        previous_line_is_blank = line.trim().is_empty();
+    }
+    emails.extend(email);
+    emails
+}
+
+/// Returns the body parts of a multipart body.
+fn split_multipart<'a>(body: &'a str, boundary: &str) -> Vec<&'a str> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut position = 0;
+    for line in body.split('\n') {
+        let trimmed = line.trim_right();
+        if trimmed == delimiter || trimmed == format!("{}--", delimiter) {
+            if let Some(start) = start {
+                parts.push(&body[start..position]);
+            }
+            if trimmed != delimiter {
+                break;
+            }
+            start = Some(position + line.len() + 1);
+        }
+        position += line.len() + 1;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::env;
+
+    fn storage(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("glacio-mail-{}", name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn mbox() {
+        let path = storage("mbox");
+        let mut importer = Importer::open(&path).unwrap();
+        let summary = importer.import("data/mail/iridium.mbox").unwrap();
+        assert_eq!(
+            Summary {
+                imported: 2,
+                duplicates: 1,
+                invalid: 1,
+            },
+            summary
+        );
+        let mut messages = FilesystemStorage::open(&path)
+            .unwrap()
+            .messages_from_imei("300234063556840")
+            .unwrap();
+        messages.sort_by_key(|message| message.time_of_session());
+        let expected = Message::from_path("data/300234063556840/2017/08/170801_000055.sbd")
+            .unwrap();
+        assert_eq!(expected.payload(), messages[0].payload());
+        assert_eq!(expected.time_of_session(), messages[0].time_of_session());
+        assert_eq!(
+            Utc.ymd(2017, 8, 25).and_hms(15, 1, 6),
+            messages[1].time_of_session()
+        );
+    }
+
+    #[test]
+    fn maildir() {
+        let path = storage("maildir");
+        let mut importer = Importer::open(&path).unwrap();
+        let summary = importer.import("data/mail/Maildir").unwrap();
+        assert_eq!(2, summary.imported);
+        let summary = Importer::open(&path)
+            .unwrap()
+            .import("data/mail/Maildir")
+            .unwrap();
+        assert_eq!(0, summary.imported);
+        assert_eq!(2, summary.duplicates);
+    }
+
+    #[test]
+    fn existing_messages() {
+        let path = storage("existing-messages");
+        let mut storage = FilesystemStorage::open(&path).unwrap();
+        storage
+            .store(
+                Message::from_path("data/300234063556840/2017/08/170825_150106.sbd").unwrap(),
+            )
+            .unwrap();
+        let summary = Importer::open(&path)
+            .unwrap()
+            .import("data/mail/iridium.mbox")
+            .unwrap();
+        assert_eq!(1, summary.imported);
+        assert_eq!(2, summary.duplicates);
+    }
+
+    #[test]
+    fn date_header_duplicate() {
+        let path = storage("date-header-duplicate");
+        let mut storage = FilesystemStorage::open(&path).unwrap();
+        storage
+            .store(
+                Message::from_path("data/300234063556840/2017/08/170825_150106.sbd").unwrap(),
+            )
+            .unwrap();
+        let email = split_mbox(&read_to_string(Path::new("data/mail/iridium.mbox")).unwrap())
+            .into_iter()
+            .find(|email| !email.contains("Time of Session (UTC)"))
+            .unwrap()
+            .replace("15:01:06 +0000", "15:03:12 +0000");
+        let mut summary = Summary::default();
+        Importer::open(&path)
+            .unwrap()
+            .import_email(&email, &mut summary)
+            .unwrap();
+        assert_eq!(0, summary.imported);
+        assert_eq!(1, summary.duplicates);
+    }
+}
@@ -0,0 +1,209 @@
+//! Remote stations of every kind.
+//!
+//! ATLAS, our remote cameras, and our weather stations are all remote stations: they sit out on
+//! the ice and report in on a schedule, each with its own kind of observation (heartbeats, images,
+//! and weather observations). The `Station` trait describes what they have in common, so that
+//! they can be listed and checked together instead of one subsystem at a time.
+//!
+//! Where a station is, what it's called, and how often it should report usually come from
+//! configuration rather than from the station's data, so implementors are often configuration
+//! objects.
+
+use atlas::Heartbeat;
+use camera::Image;
+use chrono::{DateTime, Duration, Utc};
+use weather;
+
+/// The number of reporting intervals that a station can miss before it's considered silent.
+pub const SILENT_INTERVALS: i32 = 3;
+
+/// A remote station.
+pub trait Station {
+    /// The type of this station's observations.
+    type Observation: Timestamped;
+    /// The type of error returned when the station's observations can't be read.
+    type Error;
+
+    /// Returns the station's name.
+    fn name(&self) -> &str;
+
+    /// Returns the station's location, if we know it.
+    fn location(&self) -> Option<Location> {
+        None
+    }
+
+    /// Returns how often the station is expected to report.
+    fn interval(&self) -> Duration;
+
+    /// Returns an iterator over the station's observations, in no particular order.
+    ///
+    /// Returns an error if the station's data can't be read at all. Observations that can't be
+    /// read are skipped.
+    fn observations<'a>(
+        &'a self,
+    ) -> Result<Box<Iterator<Item = Self::Observation> + 'a>, Self::Error>;
+
+    /// Returns the date and time of the station's most recent observation.
+    fn last_report(&self) -> Result<Option<DateTime<Utc>>, Self::Error> {
+        Ok(self.observations()?.map(|observation| observation.datetime()).max())
+    }
+
+    /// Returns the station's health as of `now`.
+    fn health(&self, now: DateTime<Utc>) -> Health {
+        match self.last_report() {
+            Ok(last_report) => Health::new(last_report, self.interval(), now),
+            Err(_) => Health::Unknown,
+        }
+    }
+}
+
+/// Something that happened at a specific date and time.
+pub trait Timestamped {
+    /// Returns the date and time.
+    fn datetime(&self) -> DateTime<Utc>;
+}
+
+/// Where a station is.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Location {
+    /// Latitude, in decimal degrees.
+    pub latitude: f64,
+    /// Longitude, in decimal degrees.
+    pub longitude: f64,
+    /// Elevation, in meters above sea level.
+    pub elevation: Option<f64>,
+}
+
+/// How a station is doing, judging by when it last reported.
+///
+/// The variants are ordered from best to worst.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    /// The station has reported within its reporting interval.
+    Ok,
+    /// The station has missed a report, but fewer than `SILENT_INTERVALS` of them.
+    Late,
+    /// The station has missed `SILENT_INTERVALS` reports or more.
+    Silent,
+    /// The station has never reported, or its data can't be read.
+    Unknown,
+}
+
+impl Health {
+    /// Returns the health of a station that last reported at `last_report`, as of `now`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::{Duration, Utc};
+    /// # use glacio::station::Health;
+    /// # fn main() {
+    /// let now = Utc::now();
+    /// let last_report = Some(now - Duration::minutes(90));
+    /// assert_eq!(Health::Late, Health::new(last_report, Duration::hours(1), now));
+    /// # }
+    /// ```
+    pub fn new(
+        last_report: Option<DateTime<Utc>>,
+        interval: Duration,
+        now: DateTime<Utc>,
+    ) -> Health {
+        match last_report {
+            Some(last_report) => {
+                let age = now.signed_duration_since(last_report);
+                if age <= interval {
+                    Health::Ok
+                } else if age < interval * SILENT_INTERVALS {
+                    Health::Late
+                } else {
+                    Health::Silent
+                }
+            }
+            None => Health::Unknown,
+        }
+    }
+}
+
+impl Timestamped for Heartbeat {
+    fn datetime(&self) -> DateTime<Utc> {
+        self.datetime
+    }
+}
+
+impl Timestamped for Image {
+    fn datetime(&self) -> DateTime<Utc> {
+        Image::datetime(self)
+    }
+}
+
+impl Timestamped for weather::Observation {
+    fn datetime(&self) -> DateTime<Utc> {
+        self.datetime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use weather::{DatSource, Observation};
+
+    #[derive(Debug)]
+    struct Weather(DatSource);
+
+    impl Station for Weather {
+        type Observation = Observation;
+        type Error = weather::Error;
+
+        fn name(&self) -> &str {
+            "HEL_WX"
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::hours(1)
+        }
+
+        fn observations<'a>(&'a self) -> weather::Result<Box<Iterator<Item = Observation> + 'a>> {
+            Ok(Box::new(self.0.iter()?.filter_map(|result| result.ok())))
+        }
+    }
+
+    #[test]
+    fn health() {
+        let now = Utc.ymd(2017, 8, 25).and_hms(12, 0, 0);
+        let interval = Duration::hours(3);
+        let health = |hours| Health::new(Some(now - Duration::hours(hours)), interval, now);
+        assert_eq!(Health::Ok, health(3));
+        assert_eq!(Health::Late, health(4));
+        assert_eq!(Health::Silent, health(9));
+        assert_eq!(Health::Unknown, Health::new(None, interval, now));
+        assert!(Health::Ok < Health::Late);
+        assert!(Health::Silent < Health::Unknown);
+    }
+
+    #[test]
+    fn station() {
+        let station = Weather(DatSource::new("data/HEL_WX").tables(&["Hourly"]));
+        assert_eq!(6, station.observations().unwrap().count());
+        assert_eq!(
+            Some(Utc.ymd(2017, 8, 25).and_hms(15, 0, 0)),
+            station.last_report().unwrap()
+        );
+        assert_eq!(
+            Health::Ok,
+            station.health(Utc.ymd(2017, 8, 25).and_hms(15, 30, 0))
+        );
+        assert_eq!(
+            Health::Late,
+            station.health(Utc.ymd(2017, 8, 25).and_hms(17, 0, 0))
+        );
+        assert_eq!(Health::Silent, station.health(Utc::now()));
+
+        let station = Weather(DatSource::new("data/notastation"));
+        assert!(station.last_report().is_err());
+        assert_eq!(Health::Unknown, station.health(Utc::now()));
+    }
+}
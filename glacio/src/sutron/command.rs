@@ -0,0 +1,193 @@
+//! Commands for the ATLAS data logger.
+//!
+//! Commands are sent to the Sutron data logger as mobile-terminated SBD messages. The logger's
+//! program reads each message as one line of text that starts with the `ATCM` tag (matching the
+//! `ATHB` tag on heartbeats) followed by the command and its arguments:
+//!
+//! ```text
+//! ATCM HEARTBEAT
+//! ATCM SCHEDULE 06:00 6
+//! ATCM RIEGL ON
+//! ```
+
+use chrono::{NaiveTime, ParseError};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::num::ParseIntError;
+use std::result;
+use std::str::FromStr;
+
+/// The tag that starts every command.
+pub const TAG: &'static str = "ATCM";
+
+/// The format of the scan schedule start time.
+const TIME_FORMAT: &'static str = "%H:%M";
+
+/// A command for the ATLAS data logger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Send a heartbeat now, instead of waiting for the next scheduled one.
+    ForceHeartbeat,
+    /// Change when scans happen.
+    ScanSchedule {
+        /// The time of day (UTC) of the first scan.
+        start: NaiveTime,
+        /// The number of hours between scans.
+        interval: u8,
+    },
+    /// Turn the power switch to the Riegl scanner on (`true`) or off (`false`).
+    RieglSwitch(bool),
+}
+
+/// A custom error enum for commands.
+#[derive(Debug)]
+pub enum Error {
+    /// Wrapper around `chrono::ParseError`.
+    ChronoParse(ParseError),
+    /// The command is not in a format we understand.
+    InvalidCommand(String),
+    /// Wrapper around `std::num::ParseIntError`.
+    ParseInt(ParseIntError),
+}
+
+/// Custom result type for commands.
+pub type Result<T> = result::Result<T, Error>;
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Command::ForceHeartbeat => write!(f, "{} HEARTBEAT", TAG),
+            Command::ScanSchedule { start, interval } => {
+                write!(
+                    f,
+                    "{} SCHEDULE {} {}",
+                    TAG,
+                    start.format(TIME_FORMAT),
+                    interval
+                )
+            }
+            Command::RieglSwitch(on) => {
+                write!(f, "{} RIEGL {}", TAG, if on { "ON" } else { "OFF" })
+            }
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    /// Parses a command, with or without the leading tag.
+    ///
+    /// Parsing is case-insensitive, so this can be used for user input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::sutron::Command;
+    /// let command: Command = "riegl off".parse().unwrap();
+    /// assert_eq!(Command::RieglSwitch(false), command);
+    /// assert_eq!("ATCM RIEGL OFF", command.to_string());
+    /// ```
+    fn from_str(s: &str) -> Result<Command> {
+        let upper = s.to_uppercase();
+        let mut words = upper.split_whitespace().peekable();
+        if words.peek() == Some(&TAG) {
+            words.next();
+        }
+        let words = words.collect::<Vec<_>>();
+        match (words.first().map(|word| *word), words.len()) {
+            (Some("HEARTBEAT"), 1) => Ok(Command::ForceHeartbeat),
+            (Some("SCHEDULE"), 3) => {
+                let interval = words[2].parse()?;
+                if interval == 0 {
+                    return Err(Error::InvalidCommand(s.to_string()));
+                }
+                Ok(Command::ScanSchedule {
+                    start: NaiveTime::parse_from_str(words[1], TIME_FORMAT)?,
+                    interval: interval,
+                })
+            }
+            (Some("RIEGL"), 2) if words[1] == "ON" => Ok(Command::RieglSwitch(true)),
+            (Some("RIEGL"), 2) if words[1] == "OFF" => Ok(Command::RieglSwitch(false)),
+            _ => Err(Error::InvalidCommand(s.to_string())),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Error {
+        Error::ChronoParse(err)
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(err: ParseIntError) -> Error {
+        Error::ParseInt(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::ChronoParse(ref err) => err.description(),
+            Error::InvalidCommand(_) => "invalid command",
+            Error::ParseInt(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::ChronoParse(ref err) => Some(err),
+            Error::InvalidCommand(_) => None,
+            Error::ParseInt(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::ChronoParse(ref err) => err.fmt(f),
+            Error::InvalidCommand(ref s) => write!(f, "invalid command: {}", s),
+            Error::ParseInt(ref err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for command in &[
+            Command::ForceHeartbeat,
+            Command::ScanSchedule {
+                start: NaiveTime::from_hms(6, 0, 0),
+                interval: 6,
+            },
+            Command::RieglSwitch(true),
+            Command::RieglSwitch(false),
+        ]
+        {
+            assert_eq!(*command, command.to_string().parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn schedule() {
+        assert_eq!(
+            "ATCM SCHEDULE 06:30 12",
+            "schedule 6:30 12".parse::<Command>().unwrap().to_string()
+        );
+        assert!("schedule 06:00 0".parse::<Command>().is_err());
+        assert!("schedule 25:00 6".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("".parse::<Command>().is_err());
+        assert!("ATCM".parse::<Command>().is_err());
+        assert!("riegl maybe".parse::<Command>().is_err());
+    }
+}
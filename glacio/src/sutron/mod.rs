@@ -2,8 +2,10 @@
 //!
 //! This includes stuff like datetime parsing and SBD message reconstruction.
 
+pub mod command;
 pub mod message;
 
+pub use self::command::Command;
 pub use self::message::Message;
 use chrono::{DateTime, ParseError, TimeZone, Utc};
 
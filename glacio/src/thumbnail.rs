@@ -0,0 +1,325 @@
+//! Resized copies of camera images.
+//!
+//! Our cameras take big pictures, and downloading them all just to show a grid of small ones is
+//! slow, especially from the field. A thumbnail `Cache` makes smaller JPEG copies of images on
+//! demand and keeps them in a cache directory, so each thumbnail is only made once. A cached
+//! thumbnail is remade if its image file has been modified since the thumbnail was made, or so
+//! shortly before that the image might have still been being written.
+//!
+//! Thumbnails are always JPEGs. WebP thumbnails would be smaller, but the `image` crate can only
+//! decode WebP, not encode it, so we don't make them.
+
+use camera::{self, Image};
+use image::{self, FilterType, GenericImage, ImageError, ImageFormat};
+use image::jpeg::JPEGEncoder;
+use sha2::{Digest, Sha256};
+use std::{error, io, result};
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_QUALITY: u8 = 80;
+/// The number of hex characters of the image path's hash used in thumbnail file names.
+const PATH_HASH_LENGTH: usize = 16;
+
+/// Counts the partial files that this process has written, to keep their names unique.
+static PARTIAL_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Our custom error enum for thumbnails.
+#[derive(Debug)]
+pub enum Error {
+    /// Wrapper around `image::ImageError`.
+    Image(ImageError),
+    /// Thumbnails must be at least one pixel wide.
+    InvalidWidth(u32),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// A directory of cached thumbnails.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    path: PathBuf,
+    quality: u8,
+}
+
+impl Cache {
+    /// Creates a new thumbnail cache in the given directory.
+    ///
+    /// The directory is created when the first thumbnail is made.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::thumbnail::Cache;
+    /// let cache = Cache::new("/tmp/glacio-thumbnails");
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P) -> Cache {
+        Cache {
+            path: path.as_ref().to_path_buf(),
+            quality: DEFAULT_QUALITY,
+        }
+    }
+
+    /// Sets the JPEG quality of the thumbnails, from 1 to 100.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::thumbnail::Cache;
+    /// let cache = Cache::new("/tmp/glacio-thumbnails").quality(60);
+    /// ```
+    pub fn quality(mut self, quality: u8) -> Cache {
+        self.quality = quality;
+        self
+    }
+
+    /// Returns the path to a thumbnail of the image, making it if needed.
+    ///
+    /// The thumbnail is `width` pixels wide, keeping the image's aspect ratio. Images are never
+    /// enlarged, so if the image is narrower than `width` the thumbnail is the same size as the
+    /// image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// # use glacio::thumbnail::Cache;
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// let cache = Cache::new(std::env::temp_dir().join("glacio-thumbnail-doctest"));
+    /// let path = cache.thumbnail(&image, 100).unwrap();
+    /// assert!(path.exists());
+    /// ```
+    pub fn thumbnail(&self, image: &Image, width: u32) -> Result<PathBuf> {
+        if width == 0 {
+            return Err(Error::InvalidWidth(width));
+        }
+        let path = self.path_for(image, width);
+        if self.is_fresh(&path, image.path())? {
+            return Ok(path);
+        }
+        let source = image::load(BufReader::new(File::open(image.path())?), ImageFormat::JPEG)?;
+        let (source_width, source_height) = source.dimensions();
+        let (width, height) = if width < source_width {
+            let height = u64::from(source_height) * u64::from(width) / u64::from(source_width);
+            (width, height.max(1) as u32)
+        } else {
+            (source_width, source_height)
+        };
+        let thumbnail = source.resize_exact(width, height, FilterType::Triangle);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a partial file first so that readers never see a half-written thumbnail. Each
+        // partial file gets its own name, so requests for the same thumbnail don't write to the
+        // same file, and the last one to finish wins.
+        let partial = path.with_extension(format!("jpg.{}.partial", partial_id()));
+        let result = File::create(&partial).map_err(Error::from).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            JPEGEncoder::new_with_quality(&mut writer, self.quality).encode(
+                &thumbnail.raw_pixels(),
+                width,
+                height,
+                thumbnail.color(),
+            )?;
+            writer.flush()?;
+            Ok(())
+        });
+        match result.and_then(|()| fs::rename(&partial, &path).map_err(Error::from)) {
+            Ok(()) => Ok(path),
+            Err(err) => {
+                let _ = fs::remove_file(&partial);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the path of the thumbnail for an image, whether or not it exists.
+    ///
+    /// Thumbnails are grouped by width, and named by the image's file stem and a hash of the
+    /// image's path, so images with the same name in different directories don't collide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::Image;
+    /// # use glacio::thumbnail::Cache;
+    /// let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+    /// let path = Cache::new("/tmp/glacio-thumbnails").path_for(&image, 100);
+    /// assert!(path.starts_with("/tmp/glacio-thumbnails/100"));
+    /// ```
+    pub fn path_for(&self, image: &Image, width: u32) -> PathBuf {
+        let mut hasher = Sha256::default();
+        hasher.input(image.path().to_string_lossy().as_bytes());
+        let hash = hasher
+            .result()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        let file_stem = image
+            .path()
+            .file_stem()
+            .map(|file_stem| file_stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.path.join(width.to_string()).join(format!(
+            "{}-{}.jpg",
+            file_stem,
+            &hash[..PATH_HASH_LENGTH]
+        ))
+    }
+
+    fn is_fresh(&self, path: &Path, source: &Path) -> Result<bool> {
+        let modified = match fs::metadata(path) {
+            Ok(metadata) => metadata.modified()?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(camera::is_settled(modified, fs::metadata(source)?.modified()?))
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(err: ImageError) -> Error {
+        Error::Image(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Image(ref err) => err.description(),
+            Error::InvalidWidth(_) => "invalid thumbnail width",
+            Error::Io(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Image(ref err) => Some(err),
+            Error::InvalidWidth(_) => None,
+            Error::Io(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Image(ref err) => err.fmt(f),
+            Error::InvalidWidth(width) => write!(f, "invalid thumbnail width: {}", width),
+            Error::Io(ref err) => err.fmt(f),
+        }
+    }
+}
+
+/// Returns a name for a partial file that's unique within this process, and unlikely to be used
+/// by another one.
+fn partial_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or(0);
+    format!("{}-{}", nanos, PARTIAL_COUNT.fetch_add(1, Ordering::SeqCst))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::{self, FileTime};
+    use jpeg;
+    use std::{env, thread};
+
+    /// A modification time for test images, well in the past.
+    const MODIFIED: u64 = 1_500_000_000;
+
+    fn set_modified<P: AsRef<Path>>(path: P, seconds: u64) {
+        let time = FileTime::from_seconds_since_1970(seconds, 0);
+        filetime::set_file_times(path, time, time).unwrap();
+    }
+
+    #[test]
+    fn thumbnail() {
+        let path = env::temp_dir().join("glacio-thumbnail");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("images")).unwrap();
+        let source = path.join("images/ATLAS_CAM_20170806_152500.jpg");
+        fs::copy("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg", &source).unwrap();
+        set_modified(&source, MODIFIED);
+        let image = Image::new(&source).unwrap();
+        let cache = Cache::new(path.join("cache"));
+
+        let thumbnail = cache.thumbnail(&image, 256).unwrap();
+        assert_eq!(cache.path_for(&image, 256), thumbnail);
+        let dimensions = jpeg::dimensions(&thumbnail).unwrap();
+        assert_eq!((256, 192), (dimensions.width, dimensions.height));
+        assert!(jpeg::verify(&thumbnail).is_ok());
+
+        let modified = fs::metadata(&thumbnail).unwrap().modified().unwrap();
+        assert_eq!(thumbnail, cache.thumbnail(&image, 256).unwrap());
+        assert_eq!(modified, fs::metadata(&thumbnail).unwrap().modified().unwrap());
+
+        let dimensions = jpeg::dimensions(cache.thumbnail(&image, 4096).unwrap()).unwrap();
+        assert_eq!((1024, 768), (dimensions.width, dimensions.height));
+
+        assert!(cache.thumbnail(&image, 0).is_err());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn stale_thumbnail() {
+        let path = env::temp_dir().join("glacio-thumbnail-stale");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("images")).unwrap();
+        let source = path.join("images/ATLAS_CAM_20170806_152500.jpg");
+        fs::copy("data/exif/image.jpg", &source).unwrap();
+        let image = Image::new(&source).unwrap();
+        let cache = Cache::new(path.join("cache"));
+        let thumbnail = cache.thumbnail(&image, 100).unwrap();
+        assert_eq!(8, jpeg::dimensions(&thumbnail).unwrap().width);
+
+        fs::copy("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg", &source).unwrap();
+        set_modified(&source, MODIFIED);
+        set_modified(&thumbnail, MODIFIED + camera::MODIFIED_RESOLUTION_SECONDS);
+        let thumbnail = cache.thumbnail(&image, 100).unwrap();
+        assert_eq!(8, jpeg::dimensions(&thumbnail).unwrap().width);
+
+        // The source might have still been being written when this thumbnail was made.
+        set_modified(&thumbnail, MODIFIED + camera::MODIFIED_RESOLUTION_SECONDS - 1);
+        let thumbnail = cache.thumbnail(&image, 100).unwrap();
+        assert_eq!(100, jpeg::dimensions(&thumbnail).unwrap().width);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn concurrent_thumbnails() {
+        let path = env::temp_dir().join("glacio-thumbnail-concurrent");
+        let _ = fs::remove_dir_all(&path);
+        let image = Image::new("data/ATLAS_CAM/ATLAS_CAM_20170806_152500.jpg").unwrap();
+        let cache = Cache::new(&path);
+        let threads = (0..4)
+            .map(|_| {
+                let image = image.clone();
+                let cache = cache.clone();
+                thread::spawn(move || cache.thumbnail(&image, 128).unwrap())
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            assert!(jpeg::verify(thread.join().unwrap()).is_ok());
+        }
+        let parent = cache.path_for(&image, 128).parent().unwrap().to_path_buf();
+        assert_eq!(1, fs::read_dir(parent).unwrap().count());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,478 @@
+//! Weather stations.
+//!
+//! Our weather stations run Campbell Scientific data loggers, which write their tables as TOA5
+//! `.dat` files. The files are collected onto Greg Hanlon's CWMS server. A TOA5 file is a CSV file
+//! with four header lines: the station and table names, the column names, the units, and the
+//! processing (e.g. `Avg` or `Min`):
+//!
+//! ```text
+//! "TOA5","HEL_WX","CR1000","45678","CR1000.Std.31","CPU:HEL_WX.CR1","12345","Hourly"
+//! "TIMESTAMP","RECORD","AirTC_Avg","RH","BP_mbar","WS_ms_Avg","WindDir","SlrW_Avg","BattV_Min"
+//! "TS","RN","Deg C","%","mbar","meters/second","degrees","W/m^2","Volts"
+//! "","","Avg","Smp","Smp","Avg","Smp","Avg","Min"
+//! "2017-08-25 12:00:00",1204,6.21,71.3,1002.4,3.71,244,412.5,12.91
+//! ```
+//!
+//! Each station's program names its columns a little differently, so each observation field is
+//! read from the first column that matches one of several known names. Missing columns and `NAN`
+//! values leave the field empty. Our loggers keep UTC time.
+//!
+//! # Examples
+//!
+//! ```
+//! use glacio::weather::DatSource;
+//! let observations = DatSource::new("data/HEL_WX")
+//!     .tables(&["Hourly"])
+//!     .iter()
+//!     .unwrap()
+//!     .filter_map(|result| result.ok())
+//!     .collect::<Vec<_>>();
+//! ```
+
+use chrono::{self, DateTime, TimeZone, Utc};
+use std::{error, io, result};
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines};
+use std::num::ParseFloatError;
+use std::path::{Path, PathBuf};
+use std::vec::IntoIter;
+
+/// The format of TOA5 timestamps.
+const TIMESTAMP_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+/// The column names we recognize for each observation field, in order of preference.
+const AIR_TEMPERATURE_COLUMNS: &'static [&'static str] = &["AirTC_Avg", "AirTC", "AirTemp_Avg"];
+const RELATIVE_HUMIDITY_COLUMNS: &'static [&'static str] = &["RH", "RH_Avg"];
+const PRESSURE_COLUMNS: &'static [&'static str] = &["BP_mbar", "BP_mbar_Avg", "BP_Avg"];
+const WIND_SPEED_COLUMNS: &'static [&'static str] = &["WS_ms_Avg", "WS_ms", "WS_ms_S_WVT"];
+const WIND_DIRECTION_COLUMNS: &'static [&'static str] = &["WindDir", "WindDir_D1_WVT"];
+const SOLAR_RADIATION_COLUMNS: &'static [&'static str] = &["SlrW_Avg", "SlrW", "Incoming_SW_Avg"];
+const BATTERY_VOLTAGE_COLUMNS: &'static [&'static str] = &["BattV_Min", "BattV", "BattV_Avg"];
+
+/// A custom error enum for weather stations.
+#[derive(Debug)]
+pub enum Error {
+    /// Wrapper around `chrono::ParseError`.
+    ChronoParse(chrono::ParseError),
+    /// The data file is not a TOA5 file, or is missing a header line.
+    FileFormat(String),
+    /// Wrapper around `std::io::Error`.
+    Io(io::Error),
+    /// Wrapper around `std::num::ParseFloatError`.
+    ParseFloat(ParseFloatError),
+    /// A data row has a different number of values than the file has columns.
+    RowLength {
+        /// The number of columns in the file.
+        expected: usize,
+        /// The number of values in the row.
+        actual: usize,
+    },
+}
+
+/// Our custom result type.
+pub type Result<T> = result::Result<T, Error>;
+
+/// One row of weather station data.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Observation {
+    /// The date and time of the observation.
+    pub datetime: DateTime<Utc>,
+    /// Air temperature, in degrees Celsius.
+    pub air_temperature: Option<f64>,
+    /// Relative humidity, in percent.
+    pub relative_humidity: Option<f64>,
+    /// Barometric pressure, in millibars.
+    pub pressure: Option<f64>,
+    /// Wind speed, in meters per second.
+    pub wind_speed: Option<f64>,
+    /// Wind direction, in degrees clockwise from north.
+    pub wind_direction: Option<f64>,
+    /// Incoming solar radiation, in watts per square meter.
+    pub solar_radiation: Option<f64>,
+    /// The data logger's battery voltage.
+    pub battery_voltage: Option<f64>,
+}
+
+/// Structure for retrieving observations from a station's TOA5 data files.
+///
+/// The source is either a single `.dat` file or a directory of them.
+#[derive(Debug)]
+pub struct DatSource {
+    path: PathBuf,
+    tables: Vec<String>,
+}
+
+/// An iterator over the observations provided by a `DatSource`.
+///
+/// Files are read in file name order, and rows in file order. The iterator type is a
+/// `Result<Observation>`, because one bad row shouldn't spoil the rest of the file.
+#[derive(Debug)]
+pub struct ReadDat {
+    paths: IntoIter<PathBuf>,
+    tables: Vec<String>,
+    file: Option<DatFile>,
+}
+
+#[derive(Debug)]
+struct DatFile {
+    lines: Lines<BufReader<File>>,
+    columns: Columns,
+}
+
+#[derive(Debug)]
+struct Columns {
+    len: usize,
+    air_temperature: Option<usize>,
+    relative_humidity: Option<usize>,
+    pressure: Option<usize>,
+    wind_speed: Option<usize>,
+    wind_direction: Option<usize>,
+    solar_radiation: Option<usize>,
+    battery_voltage: Option<usize>,
+}
+
+impl Observation {
+    /// Creates a new observation at this datetime, with no measurements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate chrono;
+    /// # extern crate glacio;
+    /// # use chrono::Utc;
+    /// # use glacio::weather::Observation;
+    /// # fn main() {
+    /// let observation = Observation::new(Utc::now());
+    /// assert_eq!(None, observation.air_temperature);
+    /// # }
+    /// ```
+    pub fn new(datetime: DateTime<Utc>) -> Observation {
+        Observation {
+            datetime: datetime,
+            air_temperature: None,
+            relative_humidity: None,
+            pressure: None,
+            wind_speed: None,
+            wind_direction: None,
+            solar_radiation: None,
+            battery_voltage: None,
+        }
+    }
+}
+
+impl DatSource {
+    /// Creates a new source for a data file, or a directory of data files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::weather::DatSource;
+    /// let source = DatSource::new("data/HEL_WX");
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P) -> DatSource {
+        DatSource {
+            path: path.as_ref().to_path_buf(),
+            tables: Vec::new(),
+        }
+    }
+
+    /// Sets (or clears) the logger tables to read observations from.
+    ///
+    /// Stations usually log several tables (e.g. `Hourly` and `Daily`), so this keeps different
+    /// intervals from getting mixed together. If the slice is empty, all tables are read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::weather::DatSource;
+    /// let source = DatSource::new("data/HEL_WX").tables(&["Hourly"]);
+    /// ```
+    pub fn tables(mut self, tables: &[&str]) -> DatSource {
+        self.tables = tables.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Returns an iterator over the observations in this source.
+    ///
+    /// Returns an error if the path can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use glacio::weather::DatSource;
+    /// for observation in DatSource::new("data/HEL_WX").iter().unwrap() {
+    ///     println!("{:?}", observation);
+    /// }
+    /// ```
+    pub fn iter(&self) -> Result<ReadDat> {
+        let mut paths = Vec::new();
+        if self.path.is_dir() {
+            for entry in fs::read_dir(&self.path)? {
+                let path = entry?.path();
+                if path.extension().map_or(false, |extension| extension == "dat") {
+                    paths.push(path);
+                }
+            }
+            paths.sort();
+        } else {
+            File::open(&self.path)?;
+            paths.push(self.path.clone());
+        }
+        Ok(ReadDat {
+            paths: paths.into_iter(),
+            tables: self.tables.clone(),
+            file: None,
+        })
+    }
+}
+
+impl Iterator for ReadDat {
+    type Item = Result<Observation>;
+
+    fn next(&mut self) -> Option<Result<Observation>> {
+        loop {
+            if let Some(ref mut file) = self.file {
+                match file.lines.next() {
+                    Some(Ok(ref line)) if line.trim().is_empty() => continue,
+                    Some(Ok(line)) => return Some(file.columns.observation(&line)),
+                    Some(Err(err)) => return Some(Err(err.into())),
+                    None => {}
+                }
+            }
+            let path = match self.paths.next() {
+                Some(path) => path,
+                None => return None,
+            };
+            self.file = None;
+            match DatFile::open(&path) {
+                Ok((table, file)) => {
+                    if self.tables.is_empty() || self.tables.contains(&table) {
+                        self.file = Some(file);
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl DatFile {
+    fn open(path: &Path) -> Result<(String, DatFile)> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let mut header = Vec::new();
+        for _ in 0..4 {
+            match lines.next() {
+                Some(line) => header.push(line?),
+                None => {
+                    return Err(Error::FileFormat(
+                        format!("missing header lines in {}", path.display()),
+                    ))
+                }
+            }
+        }
+        let environment = split(&header[0]);
+        if environment.first().map(|s| s.as_str()) != Some("TOA5") {
+            return Err(Error::FileFormat(
+                format!("{} is not a TOA5 file", path.display()),
+            ));
+        }
+        let table = environment.get(7).cloned().unwrap_or_default();
+        let names = split(&header[1]);
+        if names.first().map(|s| s.as_str()) != Some("TIMESTAMP") {
+            return Err(Error::FileFormat(format!(
+                "the first column of {} is not TIMESTAMP",
+                path.display()
+            )));
+        }
+        let column = |candidates: &[&str]| {
+            candidates.iter().filter_map(|candidate| {
+                names.iter().position(|name| name == candidate)
+            }).next()
+        };
+        let columns = Columns {
+            len: names.len(),
+            air_temperature: column(AIR_TEMPERATURE_COLUMNS),
+            relative_humidity: column(RELATIVE_HUMIDITY_COLUMNS),
+            pressure: column(PRESSURE_COLUMNS),
+            wind_speed: column(WIND_SPEED_COLUMNS),
+            wind_direction: column(WIND_DIRECTION_COLUMNS),
+            solar_radiation: column(SOLAR_RADIATION_COLUMNS),
+            battery_voltage: column(BATTERY_VOLTAGE_COLUMNS),
+        };
+        Ok((
+            table,
+            DatFile {
+                lines: lines,
+                columns: columns,
+            },
+        ))
+    }
+}
+
+impl Columns {
+    fn observation(&self, line: &str) -> Result<Observation> {
+        let values = split(line);
+        if values.len() != self.len {
+            return Err(Error::RowLength {
+                expected: self.len,
+                actual: values.len(),
+            });
+        }
+        let value = |index: Option<usize>| -> Result<Option<f64>> {
+            match index.map(|index| values[index].as_str()) {
+                None | Some("NAN") | Some("INF") | Some("-INF") | Some("") => Ok(None),
+                Some(value) => value.parse().map(Some).map_err(Error::from),
+            }
+        };
+        Ok(Observation {
+            datetime: Utc.datetime_from_str(&values[0], TIMESTAMP_FORMAT)?,
+            air_temperature: value(self.air_temperature)?,
+            relative_humidity: value(self.relative_humidity)?,
+            pressure: value(self.pressure)?,
+            wind_speed: value(self.wind_speed)?,
+            wind_direction: value(self.wind_direction)?,
+            solar_radiation: value(self.solar_radiation)?,
+            battery_voltage: value(self.battery_voltage)?,
+        })
+    }
+}
+
+impl From<chrono::ParseError> for Error {
+    fn from(err: chrono::ParseError) -> Error {
+        Error::ChronoParse(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(err: ParseFloatError) -> Error {
+        Error::ParseFloat(err)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::ChronoParse(ref err) => err.description(),
+            Error::FileFormat(_) => "invalid TOA5 file",
+            Error::Io(ref err) => err.description(),
+            Error::ParseFloat(ref err) => err.description(),
+            Error::RowLength { .. } => "the row has the wrong number of values",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::ChronoParse(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            Error::ParseFloat(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::ChronoParse(ref err) => err.fmt(f),
+            Error::FileFormat(ref msg) => write!(f, "invalid TOA5 file: {}", msg),
+            Error::Io(ref err) => err.fmt(f),
+            Error::ParseFloat(ref err) => err.fmt(f),
+            Error::RowLength { expected, actual } => {
+                write!(f, "expected {} values in the row, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+/// Splits a TOA5 line into its (unquoted) values.
+fn split(line: &str) -> Vec<String> {
+    line.trim_right_matches(|c| c == '\r' || c == '\n')
+        .split(',')
+        .map(|value| value.trim().trim_matches('"').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observations() {
+        let observations = DatSource::new("data/HEL_WX/HEL_WX_Hourly.dat")
+            .iter()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(6, observations.len());
+        let observation = &observations[0];
+        assert_eq!(Utc.ymd(2017, 8, 25).and_hms(10, 0, 0), observation.datetime);
+        assert_eq!(Some(5.02), observation.air_temperature);
+        assert_eq!(Some(76.1), observation.relative_humidity);
+        assert_eq!(Some(1002.9), observation.pressure);
+        assert_eq!(Some(2.84), observation.wind_speed);
+        assert_eq!(Some(238.0), observation.wind_direction);
+        assert_eq!(Some(286.3), observation.solar_radiation);
+        assert_eq!(Some(12.94), observation.battery_voltage);
+    }
+
+    #[test]
+    fn nan() {
+        let observation = DatSource::new("data/HEL_WX/HEL_WX_Hourly.dat")
+            .iter()
+            .unwrap()
+            .nth(3)
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, observation.wind_speed);
+        assert_eq!(None, observation.wind_direction);
+        assert_eq!(Some(6.48), observation.air_temperature);
+    }
+
+    #[test]
+    fn tables() {
+        let all = DatSource::new("data/HEL_WX").iter().unwrap().count();
+        let hourly = DatSource::new("data/HEL_WX")
+            .tables(&["Hourly"])
+            .iter()
+            .unwrap()
+            .count();
+        let daily = DatSource::new("data/HEL_WX")
+            .tables(&["Daily"])
+            .iter()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(6, hourly);
+        assert_eq!(2, daily.len());
+        assert_eq!(8, all);
+        assert_eq!(None, daily[0].relative_humidity);
+    }
+
+    #[test]
+    fn missing() {
+        assert!(DatSource::new("data/notastation").iter().is_err());
+    }
+
+    #[test]
+    fn bad_row() {
+        assert!(
+            Columns {
+                len: 3,
+                air_temperature: Some(2),
+                relative_humidity: None,
+                pressure: None,
+                wind_speed: None,
+                wind_direction: None,
+                solar_radiation: None,
+                battery_voltage: None,
+            }.observation("\"2017-08-25 12:00:00\",1")
+                .is_err()
+        );
+    }
+}